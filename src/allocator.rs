@@ -0,0 +1,324 @@
+//! `map_in_place` for `Vec`s in any allocator, through the nightly `allocator_api`.
+//!
+//! This replaces the impl for `Vec<A>`, which is the case where the allocator is `Global`. The
+//! buffer is only ever handed back to the allocator instance of the `Vec` it came from, be it
+//! when shrinking it or when freeing it after a panic.
+
+use alloc::alloc::{handle_alloc_error, Allocator, Layout};
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::mem::{self, ManuallyDrop};
+use core::ptr::{self, NonNull};
+
+use {checked_capacity, collect_new, expect_layout, poison_slot, poison_tail, DropSlice,
+     IncompatibleLayout, MapInPlace, NoLarger};
+
+impl<A, B, Al: Allocator + Clone> MapInPlace<A, B> for Vec<A, Al> {
+    type Output = Vec<B, Al>;
+
+    /// As for a `Vec<A>` in the global allocator. A new allocation is only needed when the
+    /// alignments of `A` and `B` differ, or when `A` is zero-sized and `B` isn't, and it's made
+    /// in a clone of the allocator of `self`, which by the contract of `Allocator` behaves as
+    /// the same allocator.
+    ///
+    /// A `B` larger than `A` is a compile error, unless one of them is zero-sized.
+    #[inline]
+    fn map_in_place<F>(self, f: F) -> Self::Output
+        where F: FnMut(A) -> B
+    {
+        let () = NoLarger::<A, B>::OK;
+
+        expect_layout(self.map_in_place_checked(f))
+    }
+
+    /// Fails if `B` is larger than `A` and has the same alignment, or if the buffer is too large
+    /// to be a `Vec<B>` (`MapError::CapacityOverflow` or `CapacityTooLarge`). Otherwise, where
+    /// the buffer can't be reused because one of them is zero-sized or they have different
+    /// alignments, the results are collected into a new allocation from the allocator of `self`.
+    ///
+    /// With the `small-code` feature, `f` is called through a trait object, as in
+    /// `map_in_place_dyn`.
+    #[inline]
+    fn map_in_place_checked<F>(self, mut f: F) -> Result<Self::Output, IncompatibleLayout<Self>>
+        where F: FnMut(A) -> B
+    {
+        if cfg!(feature = "small-code") {
+            map_vec_in::<A, B, Al, &mut dyn FnMut(A) -> B>(self, &mut f)
+        } else {
+            map_vec_in(self, f)
+        }
+    }
+}
+
+#[inline]
+fn map_vec_in<A, B, Al, F>(v: Vec<A, Al>,
+                           mut f: F)
+                           -> Result<Vec<B, Al>, IncompatibleLayout<Vec<A, Al>>>
+    where Al: Allocator + Clone,
+          F: FnMut(A) -> B
+{
+    let len = v.len();
+
+    if mem::size_of::<B>() == 0 {
+        // the result never allocates, so it can be handed to the allocator of `self` as it is
+        let alloc = v.allocator().clone();
+        let mut out = ManuallyDrop::new(collect_new(v.into_iter().map(f)));
+
+        return Ok(unsafe {
+            Vec::from_raw_parts_in(out.as_mut_ptr(), out.len(), out.capacity(), alloc)
+        });
+    }
+
+    if mem::size_of::<A>() == 0 || mem::align_of::<A>() != mem::align_of::<B>() {
+        // as in `collect_new`, but allocated in the allocator of `self`
+        let alloc = v.allocator().clone();
+        let mut iter = v.into_iter();
+        let mut out = Vec::with_capacity_in(len, alloc);
+
+        for e in iter.by_ref() {
+            out.push(f(e));
+        }
+
+        return Ok(out);
+    }
+
+    let cap = v.capacity();
+
+    if let Err(e) = checked_capacity::<A, B>(cap) {
+        return Err(IncompatibleLayout::new::<A, B>(v, "map_in_place(Vec<A>)", e));
+    }
+
+    let mut dropper = AllocDropper::<A, B, Al>::new(v);
+
+    let ptr_a = dropper.ptr;
+    let ptr_b = ptr_a as *mut B;
+
+    unsafe {
+        for i in 0..len {
+            let v = ptr::read(ptr_a.add(i));
+            poison_slot(ptr_a.add(i));
+            ptr::write(ptr_b.add(i), f(v));
+
+            dropper.idx += 1;
+        }
+
+        poison_tail::<A, B>(ptr_b, len);
+
+        // everything went well; take back the allocator the buffer belongs to
+        let mut dropper = ManuallyDrop::new(dropper);
+        let alloc = ManuallyDrop::take(&mut dropper.alloc);
+
+        Ok(mapped_vec_in::<A, B, Al>(ptr_b, len, cap, alloc))
+    }
+}
+
+/// `mapped_vec` for a buffer from `alloc`, which shrinks it with `Allocator::shrink` rather
+/// than `realloc`.
+///
+/// Requires `size_of::<B>()` to be nonzero, and `A` and `B` to have the same alignment.
+#[inline]
+unsafe fn mapped_vec_in<A, B, Al: Allocator>(ptr: *mut B, len: usize, cap: usize, alloc: Al)
+                                             -> Vec<B, Al> {
+    let a_size = mem::size_of::<A>();
+    let b_size = mem::size_of::<B>();
+
+    if a_size == b_size || cap == 0 {
+        return Vec::from_raw_parts_in(ptr, len, cap, alloc);
+    }
+
+    let n_bytes = cap * a_size;
+    let new_cap = n_bytes / b_size;
+
+    if n_bytes.is_multiple_of(b_size) {
+        return Vec::from_raw_parts_in(ptr, len, new_cap, alloc);
+    }
+
+    let old_layout = Layout::array::<A>(cap).unwrap();
+    let ptr = NonNull::new_unchecked(ptr as *mut u8);
+
+    if new_cap == 0 {
+        alloc.deallocate(ptr, old_layout);
+
+        return Vec::new_in(alloc);
+    }
+
+    let new_layout = Layout::array::<B>(new_cap).unwrap();
+
+    match alloc.shrink(ptr, old_layout, new_layout) {
+        Ok(ptr) => Vec::from_raw_parts_in(ptr.as_ptr() as *mut B, len, new_cap, alloc),
+        Err(_) => handle_alloc_error(new_layout),
+    }
+}
+
+/// `VecDropper` for a `Vec` in any allocator, which holds on to the allocator along with the
+/// raw parts of the buffer. Only dropped if `f` panics, the success path takes the allocator
+/// back instead.
+struct AllocDropper<A, B, Al: Allocator> {
+    idx: usize,
+    ptr: *mut A,
+    len: usize,
+    cap: usize,
+    alloc: ManuallyDrop<Al>,
+    _marker: PhantomData<B>,
+}
+
+impl<A, B, Al: Allocator> AllocDropper<A, B, Al> {
+    /// Takes over the elements, the buffer and the allocator of `v`.
+    #[inline]
+    fn new(v: Vec<A, Al>) -> Self {
+        let mut v = ManuallyDrop::new(v);
+
+        AllocDropper {
+            idx: 0,
+            ptr: v.as_mut_ptr(),
+            len: v.len(),
+            cap: v.capacity(),
+            // moved out of `v`, which is never dropped
+            alloc: ManuallyDrop::new(unsafe { ptr::read(v.allocator()) }),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<A, B, Al: Allocator> Drop for AllocDropper<A, B, Al> {
+    #[inline]
+    fn drop(&mut self) {
+        // panicked; manual cleanup needed
+        let idx = self.idx;
+        let len = self.len;
+        let ptr_a = self.ptr;
+        let ptr_b = ptr_a as *mut B;
+
+        unsafe {
+            // frees the buffer once the elements are gone
+            let alloc = ManuallyDrop::take(&mut self.alloc);
+            let _owned = Vec::from_raw_parts_in(ptr_a, 0, self.cap, alloc);
+
+            let _rest = DropSlice(ptr::slice_from_raw_parts_mut(ptr_a.add(idx + 1),
+                                                                len - idx - 1));
+
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(ptr_b, idx));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use MapInPlace;
+
+    use std::alloc::{AllocError, Allocator, Global, Layout};
+    use std::cell::{Cell, RefCell};
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::ptr::NonNull;
+
+    /// Hands everything on to `Global`, keeping count.
+    #[derive(Default)]
+    struct Counting {
+        allocs: Cell<usize>,
+        deallocs: RefCell<Vec<Layout>>,
+        shrinks: RefCell<Vec<(Layout, Layout)>>,
+    }
+
+    unsafe impl Allocator for &Counting {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            self.allocs.set(self.allocs.get() + 1);
+            Global.allocate(layout)
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            self.deallocs.borrow_mut().push(layout);
+            Global.deallocate(ptr, layout)
+        }
+
+        unsafe fn shrink(&self,
+                         ptr: NonNull<u8>,
+                         old_layout: Layout,
+                         new_layout: Layout)
+                         -> Result<NonNull<[u8]>, AllocError> {
+            self.shrinks.borrow_mut().push((old_layout, new_layout));
+            Global.shrink(ptr, old_layout, new_layout)
+        }
+    }
+
+    fn vec_in<T>(alloc: &Counting, elems: Vec<T>) -> Vec<T, &Counting> {
+        let mut v = Vec::with_capacity_in(elems.len(), alloc);
+        v.extend(elems);
+        v
+    }
+
+    #[test]
+    fn vec_in_allocator() {
+        let alloc = Counting::default();
+        let v = vec_in(&alloc, vec![1u32, 2, 3]);
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.map_in_place(|x| x as i32 * 2);
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, [2, 4, 6]);
+        assert!(alloc.deallocs.borrow().is_empty());
+
+        drop(v);
+
+        assert_eq!(alloc.allocs.get(), 1);
+        assert_eq!(*alloc.deallocs.borrow(), [Layout::array::<u32>(3).unwrap()]);
+    }
+
+    #[test]
+    fn vec_in_allocator_shrink() {
+        let alloc = Counting::default();
+        let v = vec_in(&alloc, vec![[1u16; 3], [2; 3], [3; 3]]);
+
+        // 18 bytes hold 4 `[u16; 2]`s, with 2 bytes to spare
+        let v = v.map_in_place(|[x, y, _]| [x, y]);
+
+        assert_eq!(v, [[1, 1], [2, 2], [3, 3]]);
+        assert_eq!(v.capacity(), 4);
+        let old_layout = Layout::array::<[u16; 3]>(3).unwrap();
+        let new_layout = Layout::array::<[u16; 2]>(4).unwrap();
+        assert_eq!(*alloc.shrinks.borrow(), [(old_layout, new_layout)]);
+
+        drop(v);
+
+        assert_eq!(alloc.allocs.get(), 1);
+        assert_eq!(*alloc.deallocs.borrow(), [new_layout]);
+    }
+
+    #[test]
+    fn vec_in_allocator_panic() {
+        let alloc = Counting::default();
+        let v = vec_in(&alloc, vec![String::from("a"), "b".into(), "c".into()]);
+
+        let r = catch_unwind(AssertUnwindSafe(|| {
+            v.map_in_place(|s| -> Box<str> {
+                if s == "b" {
+                    panic!();
+                }
+
+                s.into_boxed_str()
+            })
+        }));
+
+        assert!(r.is_err());
+        assert_eq!(alloc.allocs.get(), 1);
+        assert_eq!(*alloc.deallocs.borrow(), [Layout::array::<String>(3).unwrap()]);
+    }
+
+    #[test]
+    fn vec_in_allocator_alignment_mismatch() {
+        let alloc = Counting::default();
+        let v = vec_in(&alloc, vec![1u32, 2, 3]);
+
+        // the results go in a new allocation, the buffer goes back to `alloc`
+        let v = v.map_in_place(|x| [x as u16; 2]);
+        assert_eq!(v, [[1, 1], [2, 2], [3, 3]]);
+        assert_eq!(*alloc.deallocs.borrow(), [Layout::array::<u32>(3).unwrap()]);
+
+        drop(v);
+
+        assert_eq!(alloc.allocs.get(), 2);
+        assert_eq!(*alloc.deallocs.borrow(),
+                   [Layout::array::<u32>(3).unwrap(), Layout::array::<[u16; 2]>(3).unwrap()]);
+    }
+}
@@ -0,0 +1,844 @@
+//! Impls for fixed-size arrays, on the stack or boxed.
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use core::marker::PhantomData;
+use core::mem::{self, ManuallyDrop};
+use core::ptr;
+
+use {check_layout, collect_new, expect_layout, map_slice, needs_new_buffer, shrink_allocation,
+     ChunkInPlace, DropSlice, FlatMapExactInPlace, FlattenInPlace, IncompatibleLayout,
+     MapChunksInPlace, MapError, MapInPlace, MapPairsInPlace};
+
+/// Fails to compile (when `OK` is used) unless `A` and `B` have the same size.
+struct SameSize<A, B>(PhantomData<(A, B)>);
+
+impl<A, B> SameSize<A, B> {
+    const OK: () = assert!(mem::size_of::<A>() == mem::size_of::<B>(),
+                           "map_in_place: Size of A must be equal to size of B");
+}
+
+/// Storage for an array that is mapped from `[A; N]` to `[B; N]` element by element.
+#[repr(C)]
+union ArraySlots<A, B, const N: usize> {
+    a: ManuallyDrop<[A; N]>,
+    b: ManuallyDrop<[B; N]>,
+}
+
+/// No-op; the array being mapped isn't an allocation of its own.
+unsafe fn free_nothing<A>(_: *mut [A]) {}
+
+impl<A, B, const N: usize> MapInPlace<A, B> for [A; N] {
+    type Output = [B; N];
+
+    /// Maps the elements in place and returns them, without any heap allocation and without
+    /// another array-sized temporary.
+    ///
+    /// `A` and `B` must have the same size; this is checked at compile time:
+    ///
+    /// ```compile_fail
+    /// use map_in_place::MapInPlace;
+    ///
+    /// let _ = [0u32; 4].map_in_place(|x| x as u64);
+    /// ```
+    #[inline]
+    fn map_in_place<F>(self, f: F) -> Self::Output
+        where F: FnMut(A) -> B
+    {
+        #[allow(clippy::let_unit_value)]
+        let () = SameSize::<A, B>::OK;
+
+        expect_layout(self.map_in_place_checked(f))
+    }
+
+    /// Fails unless `A` and `B` have the same size.
+    #[inline]
+    fn map_in_place_checked<F>(self, f: F) -> Result<Self::Output, IncompatibleLayout<Self>>
+        where F: FnMut(A) -> B
+    {
+        if mem::size_of::<A>() != mem::size_of::<B>() {
+            return Err(IncompatibleLayout::new::<A, B>(self,
+                                                       "map_in_place([A; N])",
+                                                       MapError::SizeMismatch));
+        }
+
+        let mut slots = ArraySlots::<A, B, N> { a: ManuallyDrop::new(self) };
+
+        unsafe {
+            let ptr = ptr::slice_from_raw_parts_mut(&mut slots as *mut _ as *mut A, N);
+            map_slice::<A, B, F>(ptr, f, free_nothing::<A>);
+
+            Ok(ManuallyDrop::into_inner(slots.b))
+        }
+    }
+}
+
+impl<A, B, const N: usize> MapInPlace<A, B> for Box<[A; N]> {
+    type Output = Box<[B; N]>;
+
+    /// Reuses the allocation, like `Box<[A]>` does.
+    ///
+    /// `A` and `B` must have the same size, which is checked at compile time by
+    /// `map_in_place`. Panics if they differ in alignment.
+    #[inline]
+    fn map_in_place<F>(self, f: F) -> Self::Output
+        where F: FnMut(A) -> B
+    {
+        #[allow(clippy::let_unit_value)]
+        let () = SameSize::<A, B>::OK;
+
+        expect_layout(self.map_in_place_checked(f))
+    }
+
+    /// Fails unless `A` and `B` have the same size and alignment.
+    #[inline]
+    fn map_in_place_checked<F>(self, f: F) -> Result<Self::Output, IncompatibleLayout<Self>>
+        where F: FnMut(A) -> B
+    {
+        if mem::size_of::<A>() != mem::size_of::<B>() {
+            return Err(IncompatibleLayout::new::<A, B>(self,
+                                                       "map_in_place(Box<[A; N]>)",
+                                                       MapError::SizeMismatch));
+        }
+
+        let slice: Box<[A]> = self;
+
+        match slice.map_in_place_checked(f) {
+            Ok(mapped) => Ok(boxed_array(mapped)),
+            Err(e) => Err(e.map(boxed_array)),
+        }
+    }
+}
+
+/// Turns a boxed slice made from a `Box<[T; N]>` back into one.
+#[inline]
+fn boxed_array<T, const N: usize>(slice: Box<[T]>) -> Box<[T; N]> {
+    match slice.try_into() {
+        Ok(array) => array,
+        Err(_) => unreachable!(),
+    }
+}
+
+impl<A, const N: usize> FlattenInPlace for Vec<[A; N]> {
+    type Output = Vec<A>;
+
+    /// The result has `N` times the length and capacity of `self`, at the same address. If `N`
+    /// is zero, it is empty.
+    ///
+    /// Panics (before anything is moved) if `A` is zero-sized and the resulting length
+    /// overflows `usize`.
+    #[inline]
+    fn flatten_in_place(self) -> Self::Output {
+        if N == 0 {
+            // `[A; 0]` is zero-sized and holds nothing; there is no allocation either
+            return Vec::new();
+        }
+
+        let len = match self.len().checked_mul(N) {
+            Some(len) => len,
+            None => panic!("flatten_in_place(Vec<[A; N]>): Length overflow"),
+        };
+
+        let mut v = ManuallyDrop::new(self);
+
+        // the byte size of the buffer doesn't change, so it fits in `isize` as before;
+        // zero-sized elements never have a real capacity
+        let cap = if mem::size_of::<A>() == 0 {
+            len
+        } else {
+            v.capacity() * N
+        };
+
+        unsafe { Vec::from_raw_parts(v.as_mut_ptr() as *mut A, len, cap) }
+    }
+}
+
+impl<A> ChunkInPlace<A> for Vec<A> {
+    /// Fails if `N` is zero, or if the length or the capacity of `self` isn't a multiple of `N`.
+    /// A capacity with room to spare can usually be trimmed with `shrink_to_fit` first.
+    ///
+    /// The result has `1 / N` of the length and capacity of `self`, at the same address.
+    #[inline]
+    fn chunk_in_place<const N: usize>(self) -> Result<Vec<[A; N]>, Self> {
+        if N == 0 || !self.len().is_multiple_of(N) {
+            return Err(self);
+        }
+
+        // zero-sized elements never have a real capacity
+        let zst = mem::size_of::<A>() == 0;
+
+        if !zst && !self.capacity().is_multiple_of(N) {
+            return Err(self);
+        }
+
+        let mut v = ManuallyDrop::new(self);
+        let len = v.len() / N;
+        let cap = if zst { len } else { v.capacity() / N };
+
+        unsafe { Ok(Vec::from_raw_parts(v.as_mut_ptr() as *mut [A; N], len, cap)) }
+    }
+}
+
+impl<A, B> MapChunksInPlace<A, B> for Vec<A> {
+    type Output = Vec<B>;
+
+    /// Fails if `N` is zero or the length of `self` isn't a multiple of `N`, or if `B` is larger
+    /// than `[A; N]` and has the same alignment.
+    ///
+    /// As in `map_in_place`, the results are collected into a new allocation instead if `A` and
+    /// `B` have different alignments (e.g. when bytes are grouped into a `u32`), or if `A` is
+    /// zero-sized and `B` isn't.
+    ///
+    /// Otherwise the result keeps the address of `self`, unless its buffer has to be shrunk to fit
+    /// a whole number of `B`s. If `f` panics, the `B`s produced so far and the `A`s not yet
+    /// consumed are dropped and the allocation is freed.
+    #[inline]
+    fn map_chunks_in_place<const N: usize, F>(self, mut f: F) -> Result<Self::Output, Self>
+        where F: FnMut([A; N]) -> B
+    {
+        if N == 0 || !self.len().is_multiple_of(N) {
+            return Err(self);
+        }
+
+        let len = self.len() / N;
+
+        if needs_new_buffer::<A, B>() {
+            let mut iter = self.into_iter();
+            let chunks = (0..len).map(|_| ::core::array::from_fn(|_| iter.next().unwrap()));
+
+            return Ok(collect_new(chunks.map(f)));
+        }
+
+        if mem::size_of::<B>() > mem::size_of::<[A; N]>() {
+            return Err(self);
+        }
+
+        let cap = self.capacity();
+
+        let mut dropper = StridedDropper::<A, B>::new(self, N, 1);
+
+        let ptr_a = dropper.ptr;
+        let ptr_b = ptr_a as *mut B;
+
+        unsafe {
+            for i in 0..len {
+                // the `B`s never reach past the chunks that have been read
+                let chunk = ptr::read(ptr_a.add(i * N) as *const [A; N]);
+                ptr::write(ptr_b.add(i), f(chunk));
+
+                dropper.idx += 1;
+            }
+
+            mem::forget(dropper);
+
+            let (ptr_b, cap) = shrink_allocation::<A, B>(ptr_b, cap);
+
+            Ok(Vec::from_raw_parts(ptr_b, len, cap))
+        }
+    }
+}
+
+impl<A, B> MapPairsInPlace<A, B> for Vec<A> {
+    type Output = Vec<B>;
+
+    /// Same as `map_chunks_in_place` with chunks of two, including when it fails or allocates.
+    #[inline]
+    fn map_pairs_in_place<F>(self, mut f: F) -> Result<Self::Output, Self>
+        where F: FnMut(A, A) -> B
+    {
+        self.map_chunks_in_place(|[a, b]: [A; 2]| f(a, b))
+    }
+}
+
+impl<A, B> FlatMapExactInPlace<A, B> for Vec<A> {
+    type Output = Vec<B>;
+
+    /// The result has `N` times the length of `self`.
+    ///
+    /// As in `map_in_place`, the results are collected into a new allocation if `A` and `B` have
+    /// different alignments (e.g. when a `u32` is split into bytes), or if `A` is zero-sized and
+    /// `B` isn't. Panics if `[B; N]` is larger than `A` otherwise.
+    ///
+    /// Otherwise the result keeps the address of `self`, unless its buffer has to be shrunk to fit
+    /// a whole number of `B`s. If `f` panics, the `B`s produced so far and the `A`s not yet
+    /// consumed are dropped and the allocation is freed. Each group of `N` is only written once
+    /// `f` has returned all of it, so there is never a partially written group to clean up.
+    #[inline]
+    fn flat_map_exact_in_place<const N: usize, F>(self, mut f: F) -> Self::Output
+        where F: FnMut(A) -> [B; N]
+    {
+        let len = match self.len().checked_mul(N) {
+            Some(len) => len,
+            None => panic!("flat_map_exact_in_place(Vec<A>): Length overflow"),
+        };
+
+        if needs_new_buffer::<A, B>() || N == 0 {
+            return collect_new(self.into_iter().flat_map(f));
+        }
+
+        if mem::size_of::<[B; N]>() > mem::size_of::<A>() {
+            check_layout::<A, [B; N]>(Err(MapError::SizeIncrease),
+                                      "flat_map_exact_in_place(Vec<A>)");
+        }
+
+        let cap = self.capacity();
+        let n_groups = self.len();
+
+        let mut dropper = StridedDropper::<A, B>::new(self, 1, N);
+
+        let ptr_a = dropper.ptr;
+        let ptr_b = ptr_a as *mut B;
+
+        unsafe {
+            for i in 0..n_groups {
+                // the `B`s never reach past the elements that have been read
+                let v = ptr::read(ptr_a.add(i));
+                ptr::write(ptr_b.add(i * N) as *mut [B; N], f(v));
+
+                dropper.idx += 1;
+            }
+
+            mem::forget(dropper);
+
+            let (ptr_b, cap) = shrink_allocation::<A, B>(ptr_b, cap);
+
+            Vec::from_raw_parts(ptr_b, len, cap)
+        }
+    }
+}
+
+/// `idx` is the number of steps that have been mapped, each of which consumed `a_stride` `A`s
+/// and produced `b_stride` `B`s; if `f` panics, the `A`s of the step at `idx` have already been
+/// moved out.
+///
+/// The buffer is held as raw parts, and panicking destructors are handled, as in `VecDropper`.
+struct StridedDropper<A, B> {
+    idx: usize,
+    a_stride: usize,
+    b_stride: usize,
+    ptr: *mut A,
+    len: usize,
+    cap: usize,
+    _marker: PhantomData<B>,
+}
+
+impl<A, B> StridedDropper<A, B> {
+    /// Takes over the elements and the buffer of `v`.
+    #[inline]
+    fn new(v: Vec<A>, a_stride: usize, b_stride: usize) -> Self {
+        let mut v = ManuallyDrop::new(v);
+
+        StridedDropper {
+            idx: 0,
+            a_stride,
+            b_stride,
+            ptr: v.as_mut_ptr(),
+            len: v.len(),
+            cap: v.capacity(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<A, B> Drop for StridedDropper<A, B> {
+    #[inline]
+    fn drop(&mut self) {
+        // panicked; manual cleanup needed
+        let ptr_a = self.ptr;
+        let ptr_b = ptr_a as *mut B;
+
+        unsafe {
+            // frees the buffer once the elements are gone
+            let _owned = Vec::from_raw_parts(ptr_a, 0, self.cap);
+
+            let rest = (self.idx + 1) * self.a_stride;
+            let _rest = DropSlice(ptr::slice_from_raw_parts_mut(ptr_a.add(rest), self.len - rest));
+
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(ptr_b, self.idx * self.b_stride));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {ChunkInPlace, FlatMapExactInPlace, FlattenInPlace, MapChunksInPlace, MapInPlace,
+         MapPairsInPlace};
+    use testing::{DropTracker, Tracked};
+
+    use std::convert::TryInto;
+    use std::panic::catch_unwind;
+    use std::ptr::NonNull;
+
+    #[test]
+    fn same_size_array() {
+        let a = [0u32, 1, 2, 3];
+        let a = a.map_in_place(|x| (x * x) as i32);
+
+        assert_eq!(a, [0, 1, 4, 9]);
+    }
+
+    #[test]
+    fn different_align_array() {
+        let a = [[1u8, 0, 0, 0], [2, 0, 0, 0]];
+        let a = a.map_in_place(u32::from_le_bytes);
+
+        assert_eq!(a, [1, 2]);
+
+        let a = a.map_in_place(u32::to_le_bytes);
+
+        assert_eq!(a, [[1, 0, 0, 0], [2, 0, 0, 0]]);
+    }
+
+    #[test]
+    fn large_array() {
+        let a = Box::new([7u64; 4096]);
+        let a = (*a).map_in_place(|x| x as f64);
+
+        assert!(a.iter().all(|&x| x == 7.0));
+    }
+
+    #[test]
+    fn zst_array() {
+        let a = [(); 4].map_in_place(|()| ());
+        assert_eq!(a.len(), 4);
+
+        let a: [u32; 0] = [];
+        let a = a.map_in_place(|x| x as i32);
+        assert_eq!(a.len(), 0);
+    }
+
+    #[test]
+    fn array_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let a = [DROPS.track(1u32), DROPS.track(2), DROPS.track(3), DROPS.track(4)];
+
+        assert!(catch_unwind(|| {
+            a.map_in_place(|x| {
+                if x.0 == 3 {
+                    panic!();
+                } else {
+                    DROPS.track(-(x.0 as i32))
+                }
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&["1", "2", "3", "-1", "-2", "4"]);
+    }
+
+    #[test]
+    fn same_size_boxed_array() {
+        let a: Box<[f32; 65536]> = vec![0.5; 65536].into_boxed_slice().try_into().unwrap();
+
+        let bp = a.as_ptr() as *const ();
+        let a = a.map_in_place(f32::to_bits);
+        let ap = a.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert!(a.iter().all(|&x| x == 0.5f32.to_bits()));
+    }
+
+    #[test]
+    #[should_panic]
+    fn different_align_boxed_array() {
+        let a = Box::new([[0u8; 4]; 16]);
+        let _ = a.map_in_place(u32::from_ne_bytes);
+    }
+
+    #[test]
+    fn boxed_array_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let a = Box::new([DROPS.track(1u32), DROPS.track(2), DROPS.track(3), DROPS.track(4)]);
+
+        assert!(catch_unwind(|| {
+            a.map_in_place(|x: Tracked<u32>| {
+                if x.0 == 4 {
+                    panic!();
+                } else {
+                    DROPS.track(-(x.0 as i32))
+                }
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&["1", "2", "3", "4", "-1", "-2", "-3"]);
+    }
+
+    #[test]
+    fn flatten_vec() {
+        let mut v: Vec<[u8; 4]> = Vec::with_capacity(5);
+        v.extend_from_slice(&[[0, 1, 2, 3], [4, 5, 6, 7], [8, 9, 10, 11]]);
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.flatten_in_place();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v.capacity(), 20);
+        assert_eq!(v, (0..12).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn flatten_vec_empty_arrays() {
+        let v: Vec<[String; 0]> = vec![[], [], []];
+        let v = v.flatten_in_place();
+
+        assert!(v.is_empty());
+    }
+
+    #[test]
+    fn flatten_vec_zst() {
+        let v = vec![[(); 3]; 5];
+        let v = v.flatten_in_place();
+
+        assert_eq!(v.len(), 15);
+    }
+
+    #[test]
+    #[should_panic]
+    fn flatten_vec_zst_overflow() {
+        let v: Vec<[(); 2]> =
+            unsafe { Vec::from_raw_parts(NonNull::dangling().as_ptr(), usize::MAX, usize::MAX) };
+
+        let _ = v.flatten_in_place();
+    }
+
+    #[test]
+    fn flatten_vec_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v = vec![[DROPS.track(0), DROPS.track(1)], [DROPS.track(2), DROPS.track(3)]];
+        let v = v.flatten_in_place();
+
+        DROPS.assert_count(0);
+
+        drop(v);
+        DROPS.assert_drops(&["0", "1", "2", "3"]);
+    }
+
+    #[test]
+    fn chunk_vec() {
+        let v: Vec<u8> = (0..12).collect();
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.chunk_in_place::<4>().unwrap();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v.capacity(), 3);
+        assert_eq!(v, vec![[0, 1, 2, 3], [4, 5, 6, 7], [8, 9, 10, 11]]);
+    }
+
+    #[test]
+    fn chunk_vec_flatten_roundtrip() {
+        let v: Vec<u32> = (0..64).collect();
+        let w = v.clone().chunk_in_place::<8>().unwrap().flatten_in_place();
+
+        assert_eq!(v, w);
+    }
+
+    #[test]
+    fn chunk_vec_uneven_len() {
+        let v: Vec<u8> = (0..10).collect();
+        let bp = v.as_ptr();
+
+        let v = v.chunk_in_place::<4>().unwrap_err();
+
+        assert_eq!(bp, v.as_ptr());
+        assert_eq!(v, (0..10).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn chunk_vec_uneven_capacity() {
+        let mut v: Vec<u8> = Vec::with_capacity(10);
+        v.extend(0..8);
+        let cap = v.capacity();
+
+        if cap.is_multiple_of(4) {
+            return; // allocator rounded it up for us
+        }
+
+        let v = v.chunk_in_place::<4>().unwrap_err();
+
+        assert_eq!(v.capacity(), cap);
+        assert_eq!(v, (0..8).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn chunk_vec_zero_n() {
+        let v = vec![String::from("a")];
+        let v = v.chunk_in_place::<0>().unwrap_err();
+
+        assert_eq!(v, vec!["a"]);
+    }
+
+    #[test]
+    fn chunk_vec_zst() {
+        let v = vec![(); 15];
+        let v = v.chunk_in_place::<5>().unwrap();
+
+        assert_eq!(v.len(), 3);
+    }
+
+    #[test]
+    fn chunk_vec_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (0..4).map(|x| DROPS.track(x)).collect();
+        let v = v.chunk_in_place::<2>().unwrap();
+
+        DROPS.assert_count(0);
+
+        drop(v);
+        DROPS.assert_drops(&["0", "1", "2", "3"]);
+    }
+
+    #[test]
+    fn map_chunks_vec() {
+        let v: Vec<u8> = (0..12).collect();
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.map_chunks_in_place(|[a, b, c]: [u8; 3]| [c, b, a]).unwrap();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![[2, 1, 0], [5, 4, 3], [8, 7, 6], [11, 10, 9]]);
+    }
+
+    #[test]
+    fn map_chunks_vec_shrinking() {
+        let mut v: Vec<f32> = Vec::with_capacity(8);
+        v.extend_from_slice(&[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+
+        let v = v.map_chunks_in_place(|c: [f32; 3]| c.iter().sum::<f32>()).unwrap();
+
+        assert_eq!(v.capacity(), 8);
+        assert_eq!(v, vec![6.0, 15.0]);
+    }
+
+    #[test]
+    fn map_chunks_vec_awkward_capacity() {
+        let mut v: Vec<u16> = Vec::with_capacity(7);
+        v.extend_from_slice(&[1, 2, 3, 4]);
+        let cap = v.capacity();
+
+        let v = v.map_chunks_in_place(|[a, b]: [u16; 2]| [a + b, a * b]).unwrap();
+
+        assert_eq!(v.capacity(), cap / 2);
+        assert_eq!(v, vec![[3, 2], [7, 12]]);
+    }
+
+    #[test]
+    fn map_chunks_vec_partial_chunk() {
+        let v: Vec<u8> = (0..10).collect();
+        let v = v.map_chunks_in_place(|c: [u8; 4]| c).unwrap_err();
+
+        assert_eq!(v, (0..10).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn map_chunks_vec_zst() {
+        let v = vec![String::from("a"), String::from("b")];
+        let v = v.map_chunks_in_place(|_: [String; 2]| ()).unwrap();
+
+        assert_eq!(v.len(), 1);
+    }
+
+    #[test]
+    fn map_chunks_vec_different_align() {
+        let v: Vec<u8> = (0..8).collect();
+        let v = v.map_chunks_in_place(u32::from_le_bytes).unwrap();
+
+        assert_eq!(v, vec![0x03020100, 0x07060504]);
+    }
+
+    #[test]
+    fn map_chunks_vec_size_increase() {
+        let v: Vec<u32> = vec![1, 2];
+        let v = v.map_chunks_in_place(|[a]: [u32; 1]| [a, a]).unwrap_err();
+
+        assert_eq!(v, vec![1, 2]);
+    }
+
+    #[test]
+    fn map_chunks_vec_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (1..7).map(|x| DROPS.track(x)).collect();
+
+        assert!(catch_unwind(|| {
+            v.map_chunks_in_place(|[a, b]: [Tracked<u32>; 2]| {
+                if a.0 == 3 {
+                    panic!();
+                } else {
+                    DROPS.track(-((a.0 + b.0) as i32))
+                }
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&[// consume chunks
+                             "2",
+                             "1",
+                             "4",
+                             "3",
+                             // panic here
+                             // drop mapped elements
+                             "-3",
+                             // drop remaining unprocessed elements
+                             "5",
+                             "6"]);
+    }
+
+    #[test]
+    fn map_pairs_vec() {
+        #[derive(Debug, PartialEq)]
+        struct Frame {
+            left: f32,
+            right: f32,
+        }
+
+        let v: Vec<f32> = vec![0.0, 1.0, 0.5, -0.5];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.map_pairs_in_place(|left, right| Frame { left, right }).unwrap();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v,
+                   vec![Frame { left: 0.0, right: 1.0 }, Frame { left: 0.5, right: -0.5 }]);
+    }
+
+    #[test]
+    fn map_pairs_vec_nibbles() {
+        let mut v: Vec<u8> = Vec::with_capacity(7);
+        v.extend_from_slice(&[0xa, 0xb, 0x1, 0x2]);
+        let cap = v.capacity();
+
+        let v = v.map_pairs_in_place(|hi, lo| hi << 4 | lo).unwrap();
+
+        assert_eq!(v.capacity(), cap);
+        assert_eq!(v, vec![0xab, 0x12]);
+    }
+
+    #[test]
+    fn map_pairs_vec_different_align() {
+        let v: Vec<u8> = vec![0x1, 0x2, 0x3, 0x4];
+        let v = v.map_pairs_in_place(|lo, hi| u16::from_le_bytes([lo, hi])).unwrap();
+
+        assert_eq!(v, vec![0x0201, 0x0403]);
+    }
+
+    #[test]
+    fn map_pairs_vec_odd_len() {
+        let v: Vec<u8> = vec![1, 2, 3];
+        let v = v.map_pairs_in_place(|a, b| a + b).unwrap_err();
+
+        assert_eq!(v, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn map_pairs_vec_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (1..9).map(|x| DROPS.track(x)).collect();
+
+        assert!(catch_unwind(|| {
+            v.map_pairs_in_place(|a: Tracked<u32>, b: Tracked<u32>| {
+                if a.0 == 5 {
+                    panic!();
+                } else {
+                    DROPS.track(-((a.0 + b.0) as i32))
+                }
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&[// consume pairs
+                             "2",
+                             "1",
+                             "4",
+                             "3",
+                             // panic on the third pair
+                             "6",
+                             "5",
+                             // drop mapped elements
+                             "-3",
+                             "-7",
+                             // drop remaining unprocessed elements
+                             "7",
+                             "8"]);
+    }
+
+    #[test]
+    fn flat_map_exact_vec() {
+        let v: Vec<(u32, u32)> = vec![(0, 1), (2, 3)];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.flat_map_exact_in_place(|(a, b)| [a, b]);
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn flat_map_exact_vec_shrinking() {
+        let mut v: Vec<[u16; 3]> = Vec::with_capacity(5);
+        v.extend_from_slice(&[[1, 2, 3], [4, 5, 6]]);
+
+        let v = v.flat_map_exact_in_place(|[a, b, c]| [[a + c, b]]);
+
+        assert_eq!(v.capacity(), 5 * 6 / 4);
+        assert_eq!(v, vec![[4, 2], [10, 5]]);
+    }
+
+    #[test]
+    fn flat_map_exact_vec_zst() {
+        let v: Vec<u32> = vec![1, 2];
+
+        assert_eq!(v.clone().flat_map_exact_in_place(|_| [(); 3]).len(), 6);
+        assert!(v.flat_map_exact_in_place(|_| [0u8; 0]).is_empty());
+    }
+
+    #[test]
+    fn flat_map_exact_vec_different_align() {
+        let v: Vec<u32> = vec![1, 0x0302];
+        let v = v.flat_map_exact_in_place(u32::to_le_bytes);
+
+        assert_eq!(v, vec![1, 0, 0, 0, 2, 3, 0, 0]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn flat_map_exact_vec_size_increase() {
+        let v: Vec<u32> = vec![1, 2];
+        let _ = v.flat_map_exact_in_place(|a| [a, a]);
+    }
+
+    #[test]
+    fn flat_map_exact_vec_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (0..3).map(|i| (DROPS.track(2 * i), DROPS.track(2 * i + 1))).collect();
+
+        assert!(catch_unwind(|| {
+            v.flat_map_exact_in_place(|(a, b)| {
+                if a.0 == 2 {
+                    panic!();
+                } else {
+                    [a, b]
+                }
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&[// consumed pair dropped while unwinding out of `f`
+                             "3",
+                             "2",
+                             // drop mapped elements
+                             "0",
+                             "1",
+                             // drop remaining unprocessed elements
+                             "4",
+                             "5"]);
+    }
+}
@@ -0,0 +1,306 @@
+//! A single entry point for `Vec::map_in_place` with configurable policies, see
+//! `MapInPlaceBuilder`.
+//!
+//! The policies are plain fields, so at their defaults the checks fold away and `map` is the
+//! same as `map_in_place`.
+
+use alloc::vec::Vec;
+use core::mem;
+
+use {checked_capacity, expect_layout, IncompatibleLayout, InPlace, MapError, MapInPlace,
+     MapInPlaceRev};
+
+/// The order in which the mapping function is called on the elements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    /// From the first element to the last, as in `map_in_place`.
+    Forward,
+    /// From the last element to the first, as in `map_in_place_rev`. The buffer can only be
+    /// reused when `A` and `B` have the same size.
+    Reverse,
+}
+
+/// What to do when the buffer can't hold the mapped elements.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fallback {
+    /// Do as `map_in_place_checked` does, panicking where it fails: in `Order::Forward` the
+    /// elements are still mapped into a new allocation if `A` and `B` have different alignments,
+    /// or if `A` is zero-sized, so only a larger `B` of the same alignment panics. In
+    /// `Order::Reverse`, panic whenever the buffer can't be reused.
+    Panic,
+    /// Map the elements into a new allocation instead, as `map_in_place_or_alloc` does.
+    Alloc,
+}
+
+/// Maps the elements of a `Vec<A>` in place, as configured. Made with `in_place`.
+#[derive(Debug)]
+pub struct MapInPlaceBuilder<A> {
+    vec: Vec<A>,
+    order: Order,
+    fallback: Fallback,
+    trim_capacity: bool,
+}
+
+impl<A> InPlace<A> for Vec<A> {
+    #[inline]
+    fn in_place(self) -> MapInPlaceBuilder<A> {
+        MapInPlaceBuilder {
+            vec: self,
+            order: Order::Forward,
+            fallback: Fallback::Panic,
+            trim_capacity: false,
+        }
+    }
+}
+
+impl<A> MapInPlaceBuilder<A> {
+    /// Set the order in which the elements are mapped. Defaults to `Order::Forward`.
+    #[inline]
+    pub fn order(mut self, order: Order) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Set what `map` does when the buffer can't be reused. Defaults to `Fallback::Panic`.
+    /// `try_map` ignores it.
+    #[inline]
+    pub fn on_incompatible(mut self, fallback: Fallback) -> Self {
+        self.fallback = fallback;
+        self
+    }
+
+    /// Whether to free the capacity the mapped elements don't need, with `Vec::shrink_to_fit`,
+    /// which may move them to a smaller allocation. Defaults to `false`.
+    #[inline]
+    pub fn trim_capacity(mut self, trim: bool) -> Self {
+        self.trim_capacity = trim;
+        self
+    }
+
+    /// Apply `f` to every element, in the configured order.
+    ///
+    /// Panics if the buffer can't be reused and the fallback is `Fallback::Panic`, except where
+    /// `map_in_place` would allocate instead, see `Fallback::Panic`.
+    #[inline]
+    pub fn map<B, F>(self, f: F) -> Vec<B>
+        where F: FnMut(A) -> B
+    {
+        let fallback = self.fallback;
+
+        match self.try_map(f) {
+            Ok(v) => v,
+            Err((builder, f, _)) if fallback == Fallback::Alloc => builder.map_alloc(f),
+            Err((builder, f, _)) if builder.order == Order::Forward => builder.map_checked(f),
+            Err((_, _, e)) => {
+                expect_layout(Err(IncompatibleLayout::new::<A, B>((),
+                                                                  "map(MapInPlaceBuilder<A>)",
+                                                                  e)))
+            }
+        }
+    }
+
+    /// Like `map`, but when the buffer can't be reused, hands back `self` and `f` untouched
+    /// along with the reason, whatever the fallback. Panics from `f` are unaffected.
+    #[inline]
+    pub fn try_map<B, F>(self, f: F) -> Result<Vec<B>, (Self, F, MapError)>
+        where F: FnMut(A) -> B
+    {
+        if let Err(e) = self.check::<B>() {
+            return Err((self, f, e));
+        }
+
+        match self.order {
+            Order::Forward => Ok(self.map_checked(f)),
+            Order::Reverse => {
+                let trim = self.trim_capacity;
+                let mut v = self.vec.map_in_place_rev(f);
+
+                if trim {
+                    v.shrink_to_fit();
+                }
+
+                Ok(v)
+            }
+        }
+    }
+
+    /// Maps the elements forward with `map_in_place_checked`, panicking where it fails.
+    #[inline]
+    fn map_checked<B, F>(self, f: F) -> Vec<B>
+        where F: FnMut(A) -> B
+    {
+        let mut v = expect_layout(self.vec.map_in_place_checked(f));
+
+        if self.trim_capacity {
+            v.shrink_to_fit();
+        }
+
+        v
+    }
+
+    /// Whether the buffer can hold the elements mapped in the configured order.
+    #[inline]
+    fn check<B>(&self) -> Result<(), MapError> {
+        if mem::size_of::<B>() == 0 {
+            return Ok(());
+        }
+
+        if mem::align_of::<A>() != mem::align_of::<B>() {
+            return Err(MapError::AlignmentMismatch);
+        }
+
+        if self.order == Order::Reverse && mem::size_of::<A>() != mem::size_of::<B>() {
+            return Err(if mem::size_of::<B>() > mem::size_of::<A>() {
+                MapError::SizeIncrease
+            } else {
+                MapError::SizeMismatch
+            });
+        }
+
+        checked_capacity::<A, B>(self.vec.capacity())
+    }
+
+    #[inline]
+    fn map_alloc<B, F>(self, f: F) -> Vec<B>
+        where F: FnMut(A) -> B
+    {
+        match self.order {
+            Order::Forward => self.vec.into_iter().map(f).collect(),
+            Order::Reverse => {
+                let mut v: Vec<B> = self.vec.into_iter().rev().map(f).collect();
+                v.reverse();
+                v
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Fallback, Order};
+    use {InPlace, MapError};
+
+    use std::panic::catch_unwind;
+
+    #[test]
+    fn defaults() {
+        let v = vec![1u32, 2, 3];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.in_place().map(|x| x as i32 * 2);
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![2, 4, 6]);
+    }
+
+    #[test]
+    fn order() {
+        let mut calls = vec![];
+
+        let v = vec![1u32, 2, 3];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.in_place().order(Order::Reverse).map(|x| {
+            calls.push(x);
+            x as i32
+        });
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![1, 2, 3]);
+        assert_eq!(calls, vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn on_incompatible_alloc() {
+        let v = vec![1u16, 2, 3];
+
+        let v = v.in_place().on_incompatible(Fallback::Alloc).map(u64::from);
+        assert_eq!(v, vec![1, 2, 3]);
+
+        let mut calls = vec![];
+
+        // a smaller `B` can't be written in reverse
+        let v = vec![[1u32; 2], [2; 2], [3; 2]];
+        let v = v.in_place()
+                 .order(Order::Reverse)
+                 .on_incompatible(Fallback::Alloc)
+                 .map(|[x, _]| {
+                          calls.push(x);
+                          x
+                      });
+
+        assert_eq!(v, vec![1, 2, 3]);
+        assert_eq!(calls, vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn on_incompatible_panic() {
+        assert!(catch_unwind(|| vec![1u16, 2, 3].in_place().map(|x| [x, x])).is_err());
+        assert!(catch_unwind(|| {
+            vec![[1u32; 2]].in_place().order(Order::Reverse).map(|[x, _]| x)
+        }).is_err());
+    }
+
+    #[test]
+    fn on_incompatible_panic_alignment_mismatch() {
+        // allocates, as `map_in_place` does
+        let v = vec![[1u8, 0, 0, 0], [2, 0, 0, 0]].in_place().map(u32::from_le_bytes);
+        assert_eq!(v, vec![1, 2]);
+
+        assert!(catch_unwind(|| {
+            vec![[1u8, 0, 0, 0]].in_place().order(Order::Reverse).map(u32::from_le_bytes)
+        }).is_err());
+    }
+
+    #[test]
+    fn on_incompatible_panic_zst() {
+        // allocates, as `map_in_place` does
+        let v = vec![(); 3].in_place().map(|_| 1u32);
+        assert_eq!(v, vec![1, 1, 1]);
+    }
+
+    #[test]
+    fn trim_capacity() {
+        fn pairs() -> Vec<[u32; 2]> {
+            let mut v = Vec::with_capacity(7);
+            v.extend_from_slice(&[[1, 1], [2, 2], [3, 3]]);
+            v
+        }
+
+        let v = pairs().in_place().map(|[x, _]| x);
+        assert_eq!(v.capacity(), 14);
+
+        let v = pairs().in_place().trim_capacity(true).map(|[x, _]| x);
+        assert_eq!(v, vec![1, 2, 3]);
+        assert_eq!(v.capacity(), 3);
+    }
+
+    #[test]
+    fn try_map() {
+        let v = vec![1u16, 2, 3];
+        let bp = v.as_ptr() as *const ();
+
+        let (b, f, e) = v.in_place()
+                         .on_incompatible(Fallback::Alloc)
+                         .try_map(u64::from)
+                         .unwrap_err();
+
+        assert_eq!(e, MapError::AlignmentMismatch);
+        assert_eq!(f(7), 7);
+
+        let v = match b.order(Order::Reverse).try_map(|x| x as i16) {
+            Ok(v) => v,
+            Err(_) => unreachable!(),
+        };
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![1, 2, 3]);
+
+        let v = vec![[1u32; 2]];
+        let (_, _, e) = v.in_place().order(Order::Reverse).try_map(|[x, _]| x).unwrap_err();
+        assert_eq!(e, MapError::SizeMismatch);
+    }
+}
@@ -0,0 +1,131 @@
+//! Impls for std collections that don't expose their raw parts.
+//!
+//! Only a collection that can be converted to a `Vec` and back without allocating or moving
+//! its elements, as `BinaryHeap` can, is mapped, through that `Vec` and under the same rules.
+//! The others can't be taken apart and rebuilt around their storage with another element
+//! type, and moving their elements into a new collection is no better than collecting them,
+//! so they aren't mapped at all:
+//!
+//! - `VecDeque` can only be handed to a `Vec` after rotating its elements to the start of its
+//!   buffer. std gives no way to find the start otherwise, or to build a deque around a
+//!   buffer with its head anywhere but at the start.
+//! - The nodes of a `LinkedList` can only be reused for elements of the same type.
+//! - `HashMap`, `HashSet`, `BTreeMap` and `BTreeSet` only give access to their elements
+//!   through references, which can't be overwritten with values of another type, so neither
+//!   their values nor their keys can be mapped without rehashing or allocating every node
+//!   again.
+
+use alloc::collections::BinaryHeap;
+use core::mem;
+
+use {checked_capacity, expect_layout, IncompatibleLayout, MapInPlace, MapInPlaceMonotonic,
+     NoLarger};
+
+impl<A, B> MapInPlace<A, B> for BinaryHeap<A>
+    where B: Ord
+{
+    type Output = BinaryHeap<B>;
+
+    /// Maps the backing `Vec` in place (so the same size rules apply), then restores the heap
+    /// invariant in a single `O(n)` pass.
+    #[inline]
+    fn map_in_place<F>(self, f: F) -> Self::Output
+        where F: FnMut(A) -> B
+    {
+        let () = NoLarger::<A, B>::OK;
+
+        expect_layout(self.map_in_place_checked(f))
+    }
+
+    #[inline]
+    fn map_in_place_checked<F>(self, f: F) -> Result<Self::Output, IncompatibleLayout<Self>>
+        where F: FnMut(A) -> B
+    {
+        // checked up front, as a `Vec<A>` handed back can't be turned into a heap again
+        if mem::size_of::<A>() != 0 && mem::size_of::<B>() != 0 &&
+           mem::align_of::<A>() == mem::align_of::<B>() {
+            if let Err(e) = checked_capacity::<A, B>(self.capacity()) {
+                return Err(IncompatibleLayout::new::<A, B>(self, "map_in_place(BinaryHeap<A>)", e));
+            }
+        }
+
+        match self.into_vec().map_in_place_checked(f) {
+            Ok(v) => Ok(BinaryHeap::from(v)),
+            Err(_) => unreachable!(),
+        }
+    }
+}
+
+impl<A, B> MapInPlaceMonotonic<A, B> for BinaryHeap<A>
+    where B: Ord
+{
+    type Output = BinaryHeap<B>;
+
+    /// Maps the backing `Vec` in place (so the same size rules apply). std gives no way to
+    /// turn a `Vec` into a heap without restoring the invariant, so that pass is still made,
+    /// but as the mapped elements already form a heap it doesn't move any of them.
+    #[inline]
+    unsafe fn map_in_place_monotonic<F>(self, f: F) -> Self::Output
+        where F: FnMut(A) -> B
+    {
+        BinaryHeap::from(self.into_vec().map_in_place(f))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {MapError, MapInPlace, MapInPlaceMonotonic};
+
+    use std::cmp::Reverse;
+    use std::collections::BinaryHeap;
+
+    #[test]
+    fn same_size_binary_heap() {
+        let h: BinaryHeap<u32> = (0..100).collect();
+
+        let bp = h.as_slice().as_ptr() as *const ();
+        let h = h.map_in_place(|x| Reverse(x as i32));
+        let ap = h.as_slice().as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(h.into_sorted_vec(), (0..100).rev().map(Reverse).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn different_sizes_binary_heap() {
+        let h: BinaryHeap<(u32, u32)> = (0..100).map(|x| (x, 0)).collect();
+
+        let bp = h.as_slice().as_ptr() as *const ();
+        let h = h.map_in_place(|(x, _)| Reverse(x));
+        let ap = h.as_slice().as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(h.peek(), Some(&Reverse(0)));
+        assert_eq!(h.into_sorted_vec(), (0..100).rev().map(Reverse).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn checked_binary_heap() {
+        let h: BinaryHeap<u32> = (0..10).collect();
+
+        let e = h.map_in_place_checked(|x| [x; 2]).unwrap_err();
+        assert_eq!(e.reason(), MapError::SizeIncrease);
+
+        // handed back as it was
+        assert_eq!(e.into_inner().into_sorted_vec(), (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn monotonic_binary_heap() {
+        let h: BinaryHeap<u32> = (0..100).collect();
+        let layout: Vec<i32> = h.iter().map(|&x| x as i32 * 2).collect();
+
+        let bp = h.as_slice().as_ptr() as *const ();
+        let h = unsafe { h.map_in_place_monotonic(|x| x as i32 * 2) };
+        let ap = h.as_slice().as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(h.as_slice(), &layout[..]); // nothing moved
+        assert_eq!(h.into_sorted_vec(), (0..100).map(|x| x * 2).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,1103 @@
+//! Mapping that drops some of the elements, compacting the rest.
+
+use alloc::vec::Vec;
+use core::cmp;
+use core::convert::Infallible;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr;
+
+use {can_reuse_buffer, check_capacity, collect_new, collect_new_with, mapped_vec, needs_new_buffer,
+     shrink_allocation, try_collect_new_with, DedupMapInPlace, DropSlice, Either, FilterMapInPlace,
+     FlattenOptionsInPlace, MapWhileInPlace, PartitionMapInPlace, RetainMap, SiftMapInPlace,
+     SplitMapInPlace, TryFilterMapInPlace};
+
+impl<A, B> FilterMapInPlace<A, B> for Vec<A> {
+    type Output = Vec<B>;
+
+    /// The result keeps the address and (converted) capacity of `self`, with its length reduced
+    /// by the number of `None`s. As in `map_in_place`, the results are collected into a new
+    /// allocation instead if `A` and `B` have different alignments, or if `A` is zero-sized
+    /// and `B` isn't. Panics if `B` is larger than `A` otherwise.
+    ///
+    /// If `f` panics, the `B`s produced so far and the `A`s not yet consumed are dropped and the
+    /// allocation is freed.
+    #[inline]
+    fn filter_map_in_place<F>(self, f: F) -> Self::Output
+        where F: FnMut(A) -> Option<B>
+    {
+        if needs_new_buffer::<A, B>() {
+            return collect_new(self.into_iter().filter_map(f));
+        }
+
+        let cap = self.capacity();
+        check_capacity::<A, B>(cap, "filter_map_in_place(Vec<A>)");
+
+        unsafe {
+            let (ptr_b, written) = filter_map_elements(self, f);
+            mapped_vec::<A, B>(ptr_b, written, cap)
+        }
+    }
+}
+
+impl<A> FlattenOptionsInPlace<A> for Vec<Option<A>> {
+    type Output = Vec<A>;
+
+    /// The buffer is kept (and shrunk to fit a whole number of `A`s if needed) unless `A` is
+    /// zero-sized, in which case the values are collected into a new `Vec`.
+    ///
+    /// Nothing is dropped along the way, as `None` has nothing to drop.
+    #[inline]
+    fn flatten_options_in_place(self) -> Self::Output {
+        let cap = self.capacity();
+
+        if !can_reuse_buffer::<Option<A>, A>() {
+            return self.into_iter().flatten().collect();
+        }
+
+        unsafe {
+            let (ptr, written) = filter_map_elements(self, |o| o);
+            let (ptr, cap) = shrink_allocation::<Option<A>, A>(ptr, cap);
+            Vec::from_raw_parts(ptr, written, cap)
+        }
+    }
+}
+
+/// Maps every element of `v`, writing the `Some`s to the front of its buffer, and returns the
+/// buffer retyped as `B` along with the number of `B`s written. The caller takes ownership of
+/// the `B`s and of the allocation, as with `map_elements`.
+///
+/// If `f` panics, the `B`s produced so far and the `A`s not yet consumed are dropped and the
+/// allocation is freed.
+///
+/// Requires `size_of::<B>()` to be nonzero and no greater than `size_of::<A>()`.
+#[inline]
+unsafe fn filter_map_elements<A, B, F>(v: Vec<A>, mut f: F) -> (*mut B, usize)
+    where F: FnMut(A) -> Option<B>
+{
+    match try_filter_map_elements(v, |a| Ok::<Option<B>, Infallible>(f(a))) {
+        Ok(r) => r,
+        Err(e) => match e {},
+    }
+}
+
+/// Like `filter_map_elements`, but stops at the first `Err` returned by `f`, cleaning up as if
+/// `f` had panicked there before returning the error.
+#[inline]
+unsafe fn try_filter_map_elements<A, B, E, F>(v: Vec<A>, mut f: F) -> Result<(*mut B, usize), E>
+    where F: FnMut(A) -> Result<Option<B>, E>
+{
+    let len = v.len();
+
+    let mut dropper = FilterMapDropper::<A, B>::new(v);
+
+    let ptr_a = dropper.ptr;
+    let ptr_b = ptr_a as *mut B;
+
+    for i in 0..len {
+        let v = ptr::read(ptr_a.add(i));
+
+        // `B`s are no larger than `A`s, so this never overwrites an unread `A`
+        if let Some(b) = f(v)? {
+            ptr::write(ptr_b.add(dropper.written), b);
+
+            dropper.written += 1;
+        }
+
+        dropper.read += 1;
+    }
+
+    let written = dropper.written;
+    mem::forget(dropper);
+
+    Ok((ptr_b, written))
+}
+
+impl<A, B, E> TryFilterMapInPlace<A, B, E> for Vec<A> {
+    type Output = Vec<B>;
+
+    /// On the first `Err`, the `B`s produced so far and the remaining `A`s are dropped and the
+    /// allocation is freed before the error is returned. Otherwise, works like
+    /// `filter_map_in_place`, including when a new allocation is made.
+    #[inline]
+    fn try_filter_map_in_place<F>(self, mut f: F) -> Result<Self::Output, E>
+        where F: FnMut(A) -> Result<Option<B>, E>
+    {
+        if needs_new_buffer::<A, B>() {
+            return try_collect_new_with(self.into_iter(), |_, a| f(a));
+        }
+
+        let cap = self.capacity();
+        check_capacity::<A, B>(cap, "try_filter_map_in_place(Vec<A>)");
+
+        unsafe {
+            let (ptr_b, written) = try_filter_map_elements(self, f)?;
+            Ok(mapped_vec::<A, B>(ptr_b, written, cap))
+        }
+    }
+}
+
+impl<A, B: PartialEq> DedupMapInPlace<A, B> for Vec<A> {
+    type Output = Vec<B>;
+
+    /// Like `Vec::dedup`, only consecutive duplicates are removed; they are dropped as soon as
+    /// they are compared. The result keeps the address and (converted) capacity of `self`,
+    /// unless a new allocation is made as in `filter_map_in_place`.
+    ///
+    /// If `f` (or a comparison) panics, the `B`s produced so far and the `A`s not yet consumed
+    /// are dropped and the allocation is freed.
+    #[inline]
+    fn dedup_map_in_place<F>(self, mut f: F) -> Self::Output
+        where F: FnMut(A) -> B
+    {
+        if needs_new_buffer::<A, B>() {
+            return collect_new_with(self.into_iter(), |out, a| {
+                let b = f(a);
+                if out.last() != Some(&b) { Some(b) } else { None }
+            });
+        }
+
+        let cap = self.capacity();
+        check_capacity::<A, B>(cap, "dedup_map_in_place(Vec<A>)");
+        let len = self.len();
+
+        let mut dropper = FilterMapDropper::<A, B>::new(self);
+
+        let ptr_a = dropper.ptr;
+        let ptr_b = ptr_a as *mut B;
+
+        unsafe {
+            for i in 0..len {
+                let v = ptr::read(ptr_a.add(i));
+                let b = f(v);
+
+                let written = dropper.written;
+
+                if written == 0 || *ptr_b.add(written - 1) != b {
+                    ptr::write(ptr_b.add(written), b);
+
+                    dropper.written += 1;
+                } else {
+                    // while the dropper still counts its `A` as the one in flight
+                    drop(b);
+                }
+
+                dropper.read += 1;
+            }
+
+            let written = dropper.written;
+            mem::forget(dropper);
+
+            mapped_vec::<A, B>(ptr_b, written, cap)
+        }
+    }
+}
+
+impl<A, B> PartitionMapInPlace<A, B> for Vec<A> {
+    type Output = Vec<B>;
+
+    /// The `Left`s are compacted into the allocation of `self`, keeping its address and
+    /// (converted) capacity, unless a new allocation is made for them as in
+    /// `filter_map_in_place`. The `Right`s are pushed onto a new `Vec`, which grows as needed.
+    ///
+    /// If `f` panics, the `B`s and `C`s produced so far and the `A`s not yet consumed are
+    /// dropped and both allocations are freed.
+    #[inline]
+    fn partition_map_in_place<C, F>(self, mut f: F) -> (Self::Output, Vec<C>)
+        where F: FnMut(A) -> Either<B, C>
+    {
+        let mut rights = Vec::new();
+
+        if needs_new_buffer::<A, B>() {
+            let lefts = collect_new(self.into_iter().filter_map(|a| match f(a) {
+                Either::Left(b) => Some(b),
+                Either::Right(c) => {
+                    rights.push(c);
+                    None
+                }
+            }));
+
+            return (lefts, rights);
+        }
+
+        let cap = self.capacity();
+        check_capacity::<A, B>(cap, "partition_map_in_place(Vec<A>)");
+        let len = self.len();
+
+        let mut dropper = FilterMapDropper::<A, B>::new(self);
+
+        let ptr_a = dropper.ptr;
+        let ptr_b = ptr_a as *mut B;
+
+        unsafe {
+            for i in 0..len {
+                let v = ptr::read(ptr_a.add(i));
+
+                match f(v) {
+                    Either::Left(b) => {
+                        ptr::write(ptr_b.add(dropper.written), b);
+
+                        dropper.written += 1;
+                    }
+                    Either::Right(c) => rights.push(c),
+                }
+
+                dropper.read += 1;
+            }
+
+            let written = dropper.written;
+            mem::forget(dropper);
+
+            (mapped_vec::<A, B>(ptr_b, written, cap), rights)
+        }
+    }
+}
+
+impl<A, B> SiftMapInPlace<A, B> for Vec<A> {
+    type Output = Vec<B>;
+
+    /// Works like `partition_map_in_place`, so nothing is allocated unless there's an error.
+    #[inline]
+    fn sift_map_in_place<E, F>(self, mut f: F) -> (Self::Output, Vec<(usize, E)>)
+        where F: FnMut(A) -> Result<B, E>
+    {
+        let mut i = 0;
+
+        self.partition_map_in_place(|a| {
+            let r = match f(a) {
+                Ok(b) => Either::Left(b),
+                Err(e) => Either::Right((i, e)),
+            };
+
+            i += 1;
+            r
+        })
+    }
+}
+
+impl<A, B> SplitMapInPlace<A, B> for Vec<A> {
+    type Output = Vec<B>;
+
+    /// Works like `partition_map_in_place`. If `pred` panics, the element it was given is
+    /// dropped along with the others.
+    #[inline]
+    fn split_map_in_place<C, P, F, G>(self, mut pred: P, mut left: F, mut right: G)
+                                      -> (Self::Output, Vec<C>)
+        where P: FnMut(&A) -> bool,
+              F: FnMut(A) -> B,
+              G: FnMut(A) -> C
+    {
+        self.partition_map_in_place(|a| {
+            if pred(&a) { Either::Left(left(a)) } else { Either::Right(right(a)) }
+        })
+    }
+}
+
+impl<A, B> MapWhileInPlace<A, B> for Vec<A> {
+    type Output = Vec<B>;
+
+    /// The elements after the first `None` are dropped before returning. The result keeps the
+    /// address and (converted) capacity of `self`, so the whole buffer can be reused, unless a
+    /// new allocation is made as in `filter_map_in_place`.
+    ///
+    /// If `f` panics, the `B`s produced so far and the `A`s not yet consumed are dropped and the
+    /// allocation is freed.
+    #[inline]
+    fn map_while_in_place<F>(self, mut f: F) -> Self::Output
+        where F: FnMut(A) -> Option<B>
+    {
+        if needs_new_buffer::<A, B>() {
+            return collect_new(self.into_iter().map_while(f));
+        }
+
+        let cap = self.capacity();
+        check_capacity::<A, B>(cap, "map_while_in_place(Vec<A>)");
+        let len = self.len();
+
+        let mut dropper = FilterMapDropper::<A, B>::new(self);
+
+        let ptr_a = dropper.ptr;
+        let ptr_b = ptr_a as *mut B;
+
+        unsafe {
+            while dropper.read < len {
+                let v = ptr::read(ptr_a.add(dropper.read));
+
+                match f(v) {
+                    Some(b) => {
+                        ptr::write(ptr_b.add(dropper.written), b);
+
+                        dropper.written += 1;
+                        dropper.read += 1;
+                    }
+                    None => {
+                        // drop the rest one by one, so that if a drop panics, the dropper
+                        // takes care of whatever comes after it
+                        while dropper.read + 1 < len {
+                            dropper.read += 1;
+                            ptr::drop_in_place(ptr_a.add(dropper.read));
+                        }
+
+                        break;
+                    }
+                }
+            }
+
+            let written = dropper.written;
+            mem::forget(dropper);
+
+            mapped_vec::<A, B>(ptr_b, written, cap)
+        }
+    }
+}
+
+/// `read` is the number of elements that have been passed to `f`, and `written` the number of
+/// `B`s produced from them; if `f` panics, the element at `read` has already been moved out.
+///
+/// The slots in between the two hold nothing. The buffer is held as raw parts, and panicking
+/// destructors are handled, as in `VecDropper`.
+struct FilterMapDropper<A, B> {
+    read: usize,
+    written: usize,
+    ptr: *mut A,
+    len: usize,
+    cap: usize,
+    _marker: PhantomData<B>,
+}
+
+impl<A, B> FilterMapDropper<A, B> {
+    /// Takes over the elements and the buffer of `v`.
+    #[inline]
+    fn new(v: Vec<A>) -> Self {
+        let mut v = mem::ManuallyDrop::new(v);
+
+        FilterMapDropper {
+            read: 0,
+            written: 0,
+            ptr: v.as_mut_ptr(),
+            len: v.len(),
+            cap: v.capacity(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<A, B> Drop for FilterMapDropper<A, B> {
+    #[inline]
+    fn drop(&mut self) {
+        // panicked; manual cleanup needed
+        let len = self.len;
+        let ptr_a = self.ptr;
+        let ptr_b = ptr_a as *mut B;
+
+        unsafe {
+            // frees the buffer once the elements are gone
+            let _owned = Vec::from_raw_parts(ptr_a, 0, self.cap);
+
+            let rest = cmp::min(self.read + 1, len);
+            let _rest = DropSlice(ptr::slice_from_raw_parts_mut(ptr_a.add(rest), len - rest));
+
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(ptr_b, self.written));
+        }
+    }
+}
+
+impl<A> RetainMap<A> for Vec<A> {
+    /// The order of the elements that are kept is preserved.
+    ///
+    /// If `f` panics, the element it was given is lost, and `self` is left holding the elements
+    /// kept so far followed by the ones not yet passed to `f`.
+    #[inline]
+    fn retain_map<F>(&mut self, mut f: F)
+        where F: FnMut(A) -> Option<A>
+    {
+        let len = self.len();
+
+        // the elements are owned by `guard` while they are being moved around
+        unsafe {
+            self.set_len(0);
+        }
+
+        let mut guard = RetainGuard {
+            read: 0,
+            written: 0,
+            len,
+            owned: self,
+        };
+
+        unsafe {
+            let ptr = guard.owned.as_mut_ptr();
+
+            while guard.read < len {
+                let v = ptr::read(ptr.add(guard.read));
+
+                // set before calling `f`, leaving nothing at `read` if it panics
+                guard.read += 1;
+
+                if let Some(v) = f(v) {
+                    ptr::write(ptr.add(guard.written), v);
+
+                    guard.written += 1;
+                }
+            }
+        }
+    }
+}
+
+/// `read` is the number of elements that have been moved out and passed to `f`, and `written`
+/// the number of them that have been kept and moved back in.
+///
+/// On drop, the elements not yet read are moved back next to the written ones, and the length
+/// of `owned` is restored to cover both.
+struct RetainGuard<'a, A: 'a> {
+    read: usize,
+    written: usize,
+    len: usize,
+    owned: &'a mut Vec<A>,
+}
+
+impl<'a, A> Drop for RetainGuard<'a, A> {
+    #[inline]
+    fn drop(&mut self) {
+        let rest = self.len - self.read;
+
+        unsafe {
+            let ptr = self.owned.as_mut_ptr();
+
+            if rest > 0 && self.read != self.written {
+                ptr::copy(ptr.add(self.read), ptr.add(self.written), rest);
+            }
+
+            self.owned.set_len(self.written + rest);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {DedupMapInPlace, Either, FilterMapInPlace, FlattenOptionsInPlace, MapWhileInPlace,
+         PartitionMapInPlace, RetainMap, SiftMapInPlace, SplitMapInPlace,
+         TryFilterMapInPlace};
+    use testing::{DropTracker, Tracked};
+
+    use std::num::ParseIntError;
+    use std::panic::catch_unwind;
+
+    #[test]
+    fn filter_map_vec() {
+        let v: Vec<&str> = vec!["1", "x", "3", "", "5"];
+
+        let bp = v.as_ptr() as *const ();
+        let cap = v.capacity();
+        let v = v.filter_map_in_place(|s| s.parse::<usize>().ok());
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v.capacity(), cap * 2);
+        assert_eq!(v, vec![1, 3, 5]);
+    }
+
+    #[test]
+    fn filter_map_vec_awkward_capacity() {
+        let mut v: Vec<String> = Vec::with_capacity(7);
+        v.extend(vec!["1".into(), "x".into(), "3".into()]);
+
+        let v = v.filter_map_in_place(|s| s.parse::<u64>().ok().map(|n| (n, n)));
+
+        assert_eq!(v, vec![(1, 1), (3, 3)]);
+        assert_eq!(v.capacity(), 10);
+    }
+
+    #[test]
+    fn filter_map_vec_none() {
+        let v: Vec<u32> = vec![1, 2, 3];
+        let v = v.filter_map_in_place(|_| None::<u32>);
+
+        assert!(v.is_empty());
+        assert!(v.capacity() >= 3);
+    }
+
+    #[test]
+    fn filter_map_vec_zst() {
+        let v: Vec<u32> = vec![1, 2, 3, 4];
+        let v = v.filter_map_in_place(|x| if x % 2 == 0 { Some(()) } else { None });
+
+        assert_eq!(v.len(), 2);
+    }
+
+    #[test]
+    fn filter_map_vec_alignment_mismatch() {
+        // the results go in a new allocation
+        let v = vec![1u32, 2, 3].filter_map_in_place(|x| if x != 2 { Some(x as u8) } else { None });
+        assert_eq!(v, vec![1, 3]);
+
+        let v = vec![(); 3].filter_map_in_place(|()| Some(1u32));
+        assert_eq!(v, vec![1, 1, 1]);
+    }
+
+    #[test]
+    fn filter_map_vec_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (1..5).map(|x| DROPS.track(x as u32)).collect();
+        let v = v.filter_map_in_place(|x| {
+            if x.0 % 2 == 1 { Some(DROPS.track(-(x.0 as i32))) } else { None }
+        });
+
+        drop(v);
+        DROPS.assert_drops(&["1", "2", "3", "4", "-1", "-3"]);
+    }
+
+    #[test]
+    fn filter_map_vec_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (1..7).map(|x| DROPS.track(x as u32)).collect();
+
+        assert!(catch_unwind(|| {
+            v.filter_map_in_place(|x| {
+                if x.0 == 5 {
+                    panic!();
+                } else if x.0 % 3 == 1 {
+                    Some(DROPS.track(-(x.0 as i32)))
+                } else {
+                    None
+                }
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&[// consume Xs
+                             "1",
+                             "2",
+                             "3",
+                             "4",
+                             "5",
+                             // panic here
+                             // drop mapped elements, skipping the gap
+                             "-1",
+                             "-4",
+                             // drop remaining unprocessed elements
+                             "6"]);
+    }
+
+
+    #[test]
+    fn try_filter_map_vec() {
+        fn parse(s: &str) -> Result<Option<u64>, ParseIntError> {
+            match s {
+                "" => Ok(None),
+                s => s.parse().map(Some),
+            }
+        }
+
+        let v: Vec<&str> = vec!["1", "", "3", "", "5"];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.try_filter_map_in_place(parse).unwrap();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![1, 3, 5]);
+
+        let v: Vec<&str> = vec!["1", "", "#", "5"];
+        assert!(v.try_filter_map_in_place(parse).is_err());
+    }
+
+    #[test]
+    fn try_filter_map_vec_alignment_mismatch() {
+        let v = vec![1u32, 2, 3].try_filter_map_in_place(|x| -> Result<_, ()> {
+            Ok(if x != 2 { Some(x as u8) } else { None })
+        });
+        assert_eq!(v, Ok(vec![1, 3]));
+
+        let v = vec![1u32, 2, 3].try_filter_map_in_place(|x| {
+            if x != 2 { Ok(Some(x as u8)) } else { Err(x) }
+        });
+        assert_eq!(v, Err(2));
+    }
+
+    #[test]
+    fn try_filter_map_vec_error_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (1..6).map(|x| DROPS.track(x as u32)).collect();
+
+        let r = v.try_filter_map_in_place(|x| match x.0 {
+            2 => Ok(None),
+            4 => Err(()),
+            v => Ok(Some(DROPS.track(-(v as i32)))),
+        });
+        assert!(r.is_err());
+
+        DROPS.assert_drops(&[// consume Xs
+                             "1",
+                             "2",
+                             "3",
+                             // fail here
+                             "4",
+                             // drop mapped elements
+                             "-1",
+                             "-3",
+                             // drop remaining unprocessed elements
+                             "5"]);
+    }
+
+    #[test]
+    fn try_filter_map_vec_error_drop_panic() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        // the first `B`
+        DROPS.panic_on_drop(4);
+
+        let v: Vec<_> = (1..6).map(|x| DROPS.track(x as u32)).collect();
+
+        let e = catch_unwind(|| {
+                v.try_filter_map_in_place(|x| match x.0 {
+                    2 => Ok(None),
+                    4 => Err(()),
+                    v => Ok(Some(DROPS.track(-(v as i32)))),
+                })
+            })
+            .unwrap_err();
+        assert_eq!(e.downcast_ref::<String>().map(|s| &**s), Some("-1"));
+
+        DROPS.assert_drops(&[// consume Xs
+                             "1",
+                             "2",
+                             "3",
+                             // fail here
+                             "4",
+                             // drop mapped elements, the first one panics
+                             "-1",
+                             "-3",
+                             // drop remaining unprocessed elements
+                             "5"]);
+    }
+
+    #[test]
+    fn flatten_options_vec() {
+        let mut v: Vec<Option<u32>> = Vec::with_capacity(7);
+        v.extend([Some(1), None, None, Some(4), Some(5)]);
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.flatten_options_in_place();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![1, 4, 5]);
+        assert_eq!(v.capacity(), 14);
+    }
+
+    #[test]
+    fn flatten_options_vec_niche() {
+        let v: Vec<Option<Box<u32>>> = vec![None, Some(Box::new(2)), None, Some(Box::new(4))];
+
+        let bp = v.as_ptr() as *const ();
+        let cap = v.capacity();
+        let v = v.flatten_options_in_place();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![Box::new(2), Box::new(4)]);
+        assert_eq!(v.capacity(), cap);
+    }
+
+    #[test]
+    fn flatten_options_vec_zst() {
+        let v: Vec<Option<()>> = vec![Some(()), None, Some(())];
+        let v = v.flatten_options_in_place();
+
+        assert_eq!(v.len(), 2);
+    }
+
+    #[test]
+    fn dedup_map_vec() {
+        let v: Vec<&str> = vec!["a", "A", "b", "B", "b", "a"];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.dedup_map_in_place(|s| s.to_lowercase().into_boxed_str());
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec!["a".into(), "b".into(), "a".into()]);
+    }
+
+    #[test]
+    fn dedup_map_vec_single_survivor() {
+        let v: Vec<u64> = vec![100, 101, 150, 199];
+        let v = v.dedup_map_in_place(|t| t / 100 * 100);
+
+        assert_eq!(v, vec![100]);
+    }
+
+    #[test]
+    fn dedup_map_vec_zst() {
+        let v: Vec<u32> = vec![1, 2, 3];
+        let v = v.dedup_map_in_place(|_| ());
+
+        assert_eq!(v.len(), 1);
+    }
+
+    #[test]
+    fn dedup_map_vec_alignment_mismatch() {
+        let v = vec![1u32, 1, 2, 2, 1].dedup_map_in_place(|x| x as u8);
+        assert_eq!(v, vec![1, 2, 1]);
+    }
+
+    #[test]
+    fn dedup_map_vec_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<u32> = vec![1, 1, 2, 2, 2, 1];
+
+        let v = v.dedup_map_in_place(|x| DROPS.track(x));
+
+        // duplicates are dropped right away
+        DROPS.assert_drops(&["1", "2", "2"]);
+
+        drop(v);
+        DROPS.assert_drops(&["1", "2", "2", "1", "2", "1"]);
+    }
+
+    #[test]
+    fn dedup_map_vec_duplicate_drop_panic() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        // the duplicate 99, dropped after the last `A` was consumed
+        DROPS.panic_on_drop(0);
+
+        assert!(catch_unwind(|| vec![1u32, 99, 99].dedup_map_in_place(|x| DROPS.track(x)))
+            .is_err());
+
+        DROPS.assert_drops(&[// drop the duplicate, panic here
+                             "99",
+                             // drop mapped elements
+                             "1",
+                             "99"]);
+    }
+
+    #[test]
+    fn dedup_map_vec_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = [1, 1, 2, 3, 4].iter().map(|&x| DROPS.track(x)).collect();
+
+        assert!(catch_unwind(|| {
+            v.dedup_map_in_place(|x: Tracked<u32>| {
+                if x.0 == 3 {
+                    panic!();
+                } else {
+                    DROPS.track(-(x.0 as i32))
+                }
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&[// consume Xs, dropping the duplicate
+                             "1",
+                             "1",
+                             "-1",
+                             "2",
+                             "3",
+                             // panic here
+                             // drop mapped elements
+                             "-1",
+                             "-2",
+                             // drop remaining unprocessed elements
+                             "4"]);
+    }
+
+    #[test]
+    fn partition_map_vec() {
+        let v: Vec<u64> = (0..6).collect();
+
+        let bp = v.as_ptr() as *const ();
+        let (evens, odds) = v.partition_map_in_place(|x| {
+            if x % 2 == 0 {
+                Either::Left(x as i64)
+            } else {
+                Either::Right(x.to_string())
+            }
+        });
+        let ap = evens.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(evens, vec![0, 2, 4]);
+        assert_eq!(odds, vec!["1", "3", "5"]);
+    }
+
+    #[test]
+    fn partition_map_vec_zst() {
+        let v: Vec<u32> = vec![1, 2, 3];
+        let (l, r) = v.partition_map_in_place(|x| {
+            if x == 2 { Either::Right(x) } else { Either::Left(()) }
+        });
+
+        assert_eq!(l.len(), 2);
+        assert_eq!(r, vec![2]);
+    }
+
+    #[test]
+    fn partition_map_vec_alignment_mismatch() {
+        let (l, r) = vec![1u32, 2, 3].partition_map_in_place(|x| {
+            if x == 2 { Either::Right(x) } else { Either::Left(x as u8) }
+        });
+
+        assert_eq!(l, vec![1, 3]);
+        assert_eq!(r, vec![2]);
+    }
+
+    #[test]
+    fn partition_map_vec_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (1..7).map(|x| DROPS.track(x as u32)).collect();
+
+        assert!(catch_unwind(|| {
+            v.partition_map_in_place(|x| {
+                if x.0 == 5 {
+                    panic!();
+                } else if x.0 % 2 == 1 {
+                    Either::Left(DROPS.track(-(x.0 as i32)))
+                } else {
+                    Either::Right(x)
+                }
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&[// consume Xs
+                             "1",
+                             "3",
+                             "5",
+                             // panic here
+                             // drop mapped elements
+                             "-1",
+                             "-3",
+                             // drop remaining unprocessed elements
+                             "6",
+                             // drop the side vec
+                             "2",
+                             "4"]);
+    }
+
+    #[test]
+    fn sift_map_vec() {
+        let v: Vec<&str> = vec!["1", "x", "3", "", "5"];
+
+        let bp = v.as_ptr() as *const ();
+        let (ok, errors) = v.sift_map_in_place(|s| s.parse::<u64>());
+        let ap = ok.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(ok, vec![1, 3, 5]);
+
+        let errors: Vec<usize> = errors.into_iter().map(|(i, _)| i).collect();
+        assert_eq!(errors, vec![1, 3]);
+    }
+
+    #[test]
+    fn sift_map_vec_no_errors() {
+        let (ok, errors) = vec![1u32, 2, 3].sift_map_in_place(|x| -> Result<i32, ()> {
+                                                                  Ok(x as i32)
+                                                              });
+
+        assert_eq!(ok, vec![1, 2, 3]);
+        assert_eq!(errors.capacity(), 0);
+    }
+
+    #[test]
+    fn sift_map_vec_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (0..5).map(|x| DROPS.track(x)).collect();
+
+        assert!(catch_unwind(|| {
+            v.sift_map_in_place(|x| {
+                if x.0 == 3 {
+                    panic!();
+                }
+
+                if x.0 % 2 == 0 { Ok(x) } else { Err(x) }
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&[// panic here
+                             "3",
+                             // drop the successes
+                             "0",
+                             "2",
+                             // drop remaining unprocessed elements
+                             "4",
+                             // drop the errors
+                             "1"]);
+    }
+
+    #[test]
+    fn split_map_vec() {
+        let v: Vec<u64> = vec![1, 200, 3, 400];
+
+        let bp = v.as_ptr() as *const ();
+        let (hot, cold) = v.split_map_in_place(|x| *x < 100, |x| x as i64, Box::new);
+        let ap = hot.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(hot, vec![1, 3]);
+        assert_eq!(cold, vec![Box::new(200), Box::new(400)]);
+    }
+
+    #[test]
+    fn split_map_vec_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (0..5).map(|x| DROPS.track(x)).collect();
+
+        assert!(catch_unwind(|| {
+            v.split_map_in_place(|x| {
+                                     if x.0 == 3 {
+                                         panic!();
+                                     }
+
+                                     x.0 % 2 == 0
+                                 },
+                                 |x| x,
+                                 |x| x)
+        }).is_err());
+
+        DROPS.assert_drops(&[// panic here
+                             "3",
+                             // drop mapped elements
+                             "0",
+                             "2",
+                             // drop remaining unprocessed elements
+                             "4",
+                             // drop the side vec
+                             "1"]);
+    }
+
+    #[test]
+    fn map_while_vec() {
+        let v: Vec<&str> = vec!["1", "2", "x", "4"];
+
+        let bp = v.as_ptr() as *const ();
+        let cap = v.capacity();
+        let v = v.map_while_in_place(|s| s.parse::<usize>().ok());
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v.capacity(), cap * 2);
+        assert_eq!(v, vec![1, 2]);
+    }
+
+    #[test]
+    fn map_while_vec_zst() {
+        let v: Vec<u32> = vec![1, 2, 3, 4];
+        let v = v.map_while_in_place(|x| if x < 3 { Some(()) } else { None });
+
+        assert_eq!(v.len(), 2);
+    }
+
+    #[test]
+    fn map_while_vec_alignment_mismatch() {
+        let v = vec![1u32, 2, 3, 4].map_while_in_place(|x| {
+            if x < 3 { Some(x as u8) } else { None }
+        });
+        assert_eq!(v, vec![1, 2]);
+    }
+
+    #[test]
+    fn map_while_vec_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (1..6).map(|x| DROPS.track(x as u32)).collect();
+        let v = v.map_while_in_place(|x| {
+            if x.0 != 3 { Some(DROPS.track(-(x.0 as i32))) } else { None }
+        });
+
+        // the tail is dropped eagerly
+        DROPS.assert_drops(&["1", "2", "3", "4", "5"]);
+
+        drop(v);
+        DROPS.assert_drops(&["1", "2", "3", "4", "5", "-1", "-2"]);
+    }
+
+    #[test]
+    fn map_while_vec_panic_in_tail_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        // the drop of 4, in the tail after the one `f` stopped at
+        DROPS.panic_on_drop(3);
+
+        let v: Vec<_> = (1..6).map(|x| DROPS.track(x as u32)).collect();
+
+        assert!(catch_unwind(|| {
+            v.map_while_in_place(|x| if x.0 != 2 { Some(DROPS.track(-(x.0 as i32))) } else { None })
+        }).is_err());
+
+        DROPS.assert_drops(&["1",
+                             // stop here
+                             "2",
+                             "3",
+                             // panic here
+                             "4",
+                             // drop mapped elements
+                             "-1",
+                             // drop remaining elements
+                             "5"]);
+    }
+
+    #[test]
+    fn retain_map_vec() {
+        let mut v: Vec<String> = vec!["a".into(), "".into(), "c".into(), "".into()];
+        let bp = v.as_ptr();
+
+        v.retain_map(|s| if s.is_empty() { None } else { Some(s + "!") });
+
+        assert_eq!(bp, v.as_ptr()); // still at same memory addr
+        assert_eq!(v, vec!["a!", "c!"]);
+    }
+
+    #[test]
+    fn retain_map_vec_zst() {
+        let mut v = vec![(); 5];
+        let mut n = 0;
+
+        v.retain_map(|x| {
+            n += 1;
+
+            if n % 2 == 0 { Some(x) } else { None }
+        });
+
+        assert_eq!(v.len(), 2);
+    }
+
+    #[test]
+    fn retain_map_vec_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let mut v: Vec<_> = (0..6).map(|x| DROPS.track(x)).collect();
+
+        assert!(catch_unwind(::std::panic::AssertUnwindSafe(|| {
+            v.retain_map(|x| {
+                if x.0 == 3 {
+                    panic!();
+                } else if x.0 % 2 == 0 {
+                    Some(DROPS.track(x.0 + 10))
+                } else {
+                    None
+                }
+            })
+        })).is_err());
+
+        DROPS.assert_drops(&[// removed, or replaced by a new element
+                             "0",
+                             "1",
+                             "2",
+                             "3"]);
+        DROPS.clear();
+
+        // left with the kept elements, followed by the unprocessed ones
+        assert_eq!(v.iter().map(|x| x.0).collect::<Vec<_>>(), vec![10, 12, 4, 5]);
+
+        drop(v);
+        DROPS.assert_drops(&["10", "12", "4", "5"]);
+    }
+}
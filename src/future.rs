@@ -0,0 +1,418 @@
+//! Mapping the elements of a `Vec` in place with a function that returns a future, see
+//! `MapInPlaceAsync`.
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::future::Future;
+use core::mem;
+use core::pin::Pin;
+use core::ptr;
+use core::task::{Context, Poll};
+
+use {check_capacity, collect_new, mapped_vec, needs_new_buffer, DropSlice, MapInPlaceAsync};
+
+/// The future returned by `map_in_place_async`. Resolves to the `Vec<B>` occupying the buffer
+/// of the original `Vec<A>`, or to a new one where `map_in_place` would allocate.
+///
+/// Dropping it before it resolves drops the elements that have been mapped, the future of the
+/// element being mapped and the elements that haven't been reached, in that order, as when
+/// one of the futures panics.
+#[must_use = "futures do nothing unless polled"]
+pub struct MapAsync<A, F, Fut: Future> {
+    /// The raw parts of the buffer of the original `Vec<A>`, as in `VecDropper`.
+    ptr: *mut A,
+    len: usize,
+    cap: usize,
+    read: usize,
+    written: usize,
+    fut: Option<Fut>,
+    f: F,
+    done: bool,
+    /// Where the results go when they can't be written over the `A`s, see `collects`.
+    collected: Vec<Fut::Output>,
+}
+
+// the buffer holds `A`s and `B`s, and is only reached through `&mut self`, as in a `Vec`
+unsafe impl<A: Send, F: Send, Fut: Future + Send> Send for MapAsync<A, F, Fut>
+    where Fut::Output: Send {}
+unsafe impl<A: Sync, F: Sync, Fut: Future + Sync> Sync for MapAsync<A, F, Fut>
+    where Fut::Output: Sync {}
+
+impl<A, B> MapInPlaceAsync<A, B> for Vec<A> {
+    /// As in `map_in_place`, the results are collected into a new allocation if `A` and `B`
+    /// have different alignments, or if `A` is zero-sized and `B` isn't. Panics straight away
+    /// if `B` is larger than `A` otherwise.
+    #[inline]
+    fn map_in_place_async<F, Fut>(self, f: F) -> MapAsync<A, F, Fut>
+        where F: FnMut(A) -> Fut,
+              Fut: Future<Output = B>
+    {
+        let collected = if MapAsync::<A, F, Fut>::collects() {
+            Vec::with_capacity(self.len())
+        } else {
+            if mem::size_of::<B>() != 0 {
+                check_capacity::<A, B>(self.capacity(), "map_in_place_async(Vec<A>)");
+            }
+
+            Vec::new()
+        };
+
+        let mut v = mem::ManuallyDrop::new(self);
+
+        MapAsync {
+            ptr: v.as_mut_ptr(),
+            len: v.len(),
+            cap: v.capacity(),
+            read: 0,
+            written: 0,
+            fut: None,
+            f,
+            done: false,
+            collected,
+        }
+    }
+}
+
+impl<A, F, Fut: Future> MapAsync<A, F, Fut> {
+    /// Whether the results are pushed onto `collected` rather than written over the `A`s, as
+    /// `needs_new_buffer` has it. Zero-sized ones are written to a dangling pointer instead.
+    #[inline]
+    fn collects() -> bool {
+        mem::size_of::<Fut::Output>() != 0 && needs_new_buffer::<A, Fut::Output>()
+    }
+
+    #[inline]
+    fn ptr_b(&mut self) -> *mut Fut::Output {
+        if mem::size_of::<Fut::Output>() == 0 {
+            ptr::NonNull::dangling().as_ptr()
+        } else {
+            self.ptr as *mut Fut::Output
+        }
+    }
+}
+
+impl<A, B, F, Fut> Future for MapAsync<A, F, Fut>
+    where F: FnMut(A) -> Fut,
+          Fut: Future<Output = B>
+{
+    type Output = Vec<B>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Vec<B>> {
+        // only `fut` is pinned, and it's never moved out of its place
+        let this = unsafe { self.get_unchecked_mut() };
+
+        assert!(!this.done, "map_in_place_async(Vec<A>): polled after completion");
+
+        let len = this.len;
+
+        loop {
+            if let Some(ref mut fut) = this.fut {
+                let b = match unsafe { Pin::new_unchecked(fut) }.poll(cx) {
+                    Poll::Ready(b) => b,
+                    Poll::Pending => return Poll::Pending,
+                };
+
+                this.fut = None;
+
+                if Self::collects() {
+                    this.collected.push(b);
+                } else {
+                    unsafe {
+                        ptr::write(this.ptr_b().add(this.written), b);
+                    }
+                }
+
+                this.written += 1;
+            }
+
+            if this.read == len {
+                break;
+            }
+
+            let a = unsafe { ptr::read(this.ptr.add(this.read)) };
+            this.read += 1;
+            this.fut = Some((this.f)(a));
+        }
+
+        this.done = true;
+        this.read = 0;
+        this.written = 0;
+
+        // the buffer is handed on to the result or freed here, leaving none to `drop`
+        let ptr_a = mem::replace(&mut this.ptr, ptr::NonNull::dangling().as_ptr());
+        let cap = mem::replace(&mut this.cap, 0);
+        this.len = 0;
+
+        unsafe {
+            if Self::collects() {
+                drop(Vec::from_raw_parts(ptr_a, 0, cap));
+
+                Poll::Ready(mem::take(&mut this.collected))
+            } else if mem::size_of::<B>() == 0 {
+                drop(Vec::from_raw_parts(ptr_a, 0, cap));
+
+                // the `B`s were written to a dangling pointer, they can be read back from it
+                let ptr_b = this.ptr_b();
+                Poll::Ready(collect_new((0..len).map(|_| ptr::read(ptr_b))))
+            } else {
+                Poll::Ready(mapped_vec::<A, B>(ptr_a as *mut B, len, cap))
+            }
+        }
+    }
+}
+
+impl<A, F, Fut: Future> Drop for MapAsync<A, F, Fut> {
+    fn drop(&mut self) {
+        let len = self.len;
+        let ptr_a = self.ptr;
+        let ptr_b = self.ptr_b();
+
+        unsafe {
+            // frees the buffer once the elements are gone
+            let _owned = Vec::from_raw_parts(ptr_a, 0, self.cap);
+
+            // dropped in reverse, so that a panicking destructor doesn't leak what comes after
+            let _rest = DropSlice(ptr::slice_from_raw_parts_mut(ptr_a.add(self.read),
+                                                                len - self.read));
+            // the element being mapped, if any, is owned by `fut`
+            let _fut = self.fut.take();
+
+            if Self::collects() {
+                drop(mem::take(&mut self.collected));
+            } else {
+                ptr::drop_in_place(ptr::slice_from_raw_parts_mut(ptr_b, self.written));
+            }
+        }
+    }
+}
+
+/// Only shows how far along the mapping is: the buffer holds `A`s, `B`s and moved-out slots.
+impl<A, F, Fut: Future> fmt::Debug for MapAsync<A, F, Fut> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MapAsync")
+            .field("read", &self.read)
+            .field("written", &self.written)
+            .field("done", &self.done)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use MapInPlaceAsync;
+    use testing::{DropTracker, Tracked};
+
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use std::task::{Context, Poll, Waker};
+
+    /// Maps its input with `g` the second time it's polled.
+    struct Later<T, U> {
+        input: Option<T>,
+        g: fn(T) -> U,
+        polled: bool,
+    }
+
+    fn later<T, U>(input: T, g: fn(T) -> U) -> Later<T, U> {
+        Later {
+            input: Some(input),
+            g,
+            polled: false,
+        }
+    }
+
+    impl<T: Unpin, U> Future for Later<T, U> {
+        type Output = U;
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<U> {
+            if !self.polled {
+                self.polled = true;
+                cx.waker().wake_by_ref();
+                return Poll::Pending;
+            }
+
+            let input = self.input.take().unwrap();
+            Poll::Ready((self.g)(input))
+        }
+    }
+
+    fn block_on<T>(mut fut: impl Future<Output = T> + Unpin) -> T {
+        let mut cx = Context::from_waker(Waker::noop());
+
+        loop {
+            if let Poll::Ready(t) = Pin::new(&mut fut).poll(&mut cx) {
+                return t;
+            }
+        }
+    }
+
+    #[test]
+    fn map_async_vec() {
+        let v: Vec<u32> = vec![1, 2, 3];
+
+        let bp = v.as_ptr() as *const ();
+        let v = block_on(v.map_in_place_async(|x| later(x, |x| x as i32)));
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn map_async_vec_send() {
+        fn assert_send<T: Send>(t: T) -> T {
+            t
+        }
+
+        let v = block_on(assert_send(vec![1u32, 2].map_in_place_async(|x| later(x, |x| x + 1))));
+        assert_eq!(v, vec![2, 3]);
+    }
+
+    #[test]
+    fn map_async_vec_zst() {
+        let v = block_on(vec![1u32, 2, 3].map_in_place_async(|x| later(x, |_| ())));
+        assert_eq!(v.len(), 3);
+    }
+
+    #[test]
+    fn map_async_vec_alignment_mismatch() {
+        // the results go in a new allocation
+        let v = block_on(vec![1u32, 2, 3].map_in_place_async(|x| later(x, |x| x as u8)));
+        assert_eq!(v, vec![1, 2, 3]);
+
+        let v = block_on(vec![(); 2].map_in_place_async(|x| later(x, |()| 1u32)));
+        assert_eq!(v, vec![1, 1]);
+    }
+
+    #[test]
+    fn map_async_vec_alignment_mismatch_cancel() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        #[derive(Debug)]
+        #[repr(align(16))]
+        #[allow(dead_code)]
+        struct Aligned(u32);
+
+        let v = vec![DROPS.track('a'), DROPS.track('b'), DROPS.track('c')];
+        let mut fut = v.map_in_place_async(|x| later(x, |x| DROPS.track(Aligned(x.0 as u32))));
+        let mut cx = Context::from_waker(Waker::noop());
+
+        for _ in 0..2 {
+            assert!(Pin::new(&mut fut).poll(&mut cx).is_pending());
+        }
+
+        drop(fut);
+        DROPS.assert_drops(&[// consume Xs
+                             "'a'",
+                             // drop generated Ys
+                             "Aligned(97)",
+                             // drop the element being mapped
+                             "'b'",
+                             // drop remaining unprocessed Xs
+                             "'c'"]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn map_async_vec_polled_after_completion() {
+        let mut fut = vec![1u32].map_in_place_async(|x| later(x, |x| x));
+        let mut cx = Context::from_waker(Waker::noop());
+
+        while Pin::new(&mut fut).poll(&mut cx).is_pending() {}
+        let _ = Pin::new(&mut fut).poll(&mut cx);
+    }
+
+    #[test]
+    fn map_async_vec_debug() {
+        let mut fut = vec![String::from("a"), "b".into()].map_in_place_async(|s| later(s, |s| s));
+        let mut cx = Context::from_waker(Waker::noop());
+
+        assert!(Pin::new(&mut fut).poll(&mut cx).is_pending());
+        assert_eq!(format!("{:?}", fut), "MapAsync { read: 1, written: 0, done: false }");
+    }
+
+    #[test]
+    fn map_async_vec_cancel_drop_panic() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        // the drop of the first `B`, after its `A` was consumed
+        DROPS.panic_on_drop(1);
+
+        let v = vec![DROPS.track('a'), DROPS.track('b'), DROPS.track('c')];
+        let mut fut = v.map_in_place_async(|x| later(x, |x| DROPS.track(x.0 as u32)));
+        let mut cx = Context::from_waker(Waker::noop());
+
+        for _ in 0..2 {
+            assert!(Pin::new(&mut fut).poll(&mut cx).is_pending());
+        }
+
+        // the first `B` panics when dropped, the rest are dropped all the same
+        assert!(catch_unwind(AssertUnwindSafe(|| drop(fut))).is_err());
+        DROPS.assert_drops(&[// consume Xs
+                             "'a'",
+                             // drop generated Ys, panic here
+                             "97",
+                             // drop the element being mapped
+                             "'b'",
+                             // drop remaining unprocessed Xs
+                             "'c'"]);
+    }
+
+    macro_rules! async_drop_test {
+        ($name:ident, $polls:expr, $expected:expr) => {
+            #[test]
+            fn $name() {
+                static DROPS: DropTracker = DropTracker::new();
+
+                fn y(x: Tracked<char>) -> Tracked<u32> {
+                    if x.0 == 'c' {
+                        panic!();
+                    }
+
+                    DROPS.track(x.0 as u32)
+                }
+
+                let v: Vec<_> = "abcd".chars().map(|c| DROPS.track(c)).collect();
+                let mut fut = v.map_in_place_async(|x| later(x, y));
+                let mut cx = Context::from_waker(Waker::noop());
+
+                let _ = catch_unwind(AssertUnwindSafe(|| {
+                    for _ in 0..$polls {
+                        assert!(Pin::new(&mut fut).poll(&mut cx).is_pending());
+                    }
+                }));
+
+                drop(fut);
+                DROPS.assert_drops(&$expected);
+            }
+        }
+    }
+
+    // cancelled while `b` is being mapped
+    async_drop_test!(map_async_vec_cancel_drop,
+                     2,
+                     [// consume Xs
+                      "'a'",
+                      // drop generated Ys
+                      "97",
+                      // drop the element being mapped
+                      "'b'",
+                      // drop remaining unprocessed Xs
+                      "'c'",
+                      "'d'"]);
+
+    // the future for `c` panics, and is dropped along with the rest
+    async_drop_test!(map_async_vec_panic_drop,
+                     4,
+                     [// consume Xs
+                      "'a'",
+                      "'b'",
+                      "'c'",
+                      // panic here
+                      // drop generated Ys
+                      "97",
+                      "98",
+                      // drop remaining unprocessed Xs
+                      "'d'"]);
+}
@@ -0,0 +1,255 @@
+//! Mapping groups of consecutive elements whose length is only known at runtime.
+
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr;
+
+use {check_layout, collect_new, needs_new_buffer, shrink_allocation, DropSlice, MapError,
+     MapGroupsInPlace};
+
+impl<A, B> MapGroupsInPlace<A, B> for Vec<A> {
+    type Output = Vec<B>;
+
+    /// The elements of a group that `f` doesn't take from the iterator are dropped after it
+    /// returns, before its result is written.
+    ///
+    /// The result keeps the address of `self`, unless its buffer has to be shrunk to fit a
+    /// whole number of `B`s. If `f` (or dropping an element) panics, the `B`s produced so far
+    /// and the `A`s not yet consumed are dropped and the allocation is freed.
+    ///
+    /// Panics if `B` is larger than `group` `A`s. As in `map_in_place`, the results are
+    /// collected into a new allocation instead if `A` and `B` have different alignments, or if
+    /// `A` is zero-sized and `B` isn't.
+    #[inline]
+    fn map_groups_in_place<F>(self, group: usize, mut f: F) -> Result<Self::Output, Self>
+        where F: FnMut(&mut dyn Iterator<Item = A>) -> B
+    {
+        if group == 0 || !self.len().is_multiple_of(group) {
+            return Err(self);
+        }
+
+        let len = self.len() / group;
+
+        if needs_new_buffer::<A, B>() {
+            let mut iter = self.into_iter();
+
+            return Ok(collect_new((0..len).map(|_| {
+                let mut rest = iter.by_ref().take(group);
+                let b = f(&mut rest);
+                rest.for_each(drop);
+                b
+            })));
+        }
+
+        if mem::size_of::<A>().checked_mul(group).is_none_or(|n| n < mem::size_of::<B>()) {
+            check_layout::<A, B>(Err(MapError::SizeIncrease), "map_groups_in_place(Vec<A>)");
+        }
+
+        let cap = self.capacity();
+
+        let mut dropper = GroupDropper::<A, B>::new(self);
+
+        let ptr_a = dropper.ptr;
+        let ptr_b = ptr_a as *mut B;
+
+        unsafe {
+            for i in 0..len {
+                let end = (i + 1) * group;
+
+                let b = f(&mut Group {
+                    ptr: ptr_a,
+                    read: &mut dropper.read,
+                    end,
+                });
+
+                while dropper.read < end {
+                    let a = ptr::read(ptr_a.add(dropper.read));
+                    dropper.read += 1;
+
+                    drop(a);
+                }
+
+                // the `B`s never reach past the groups that have been read
+                ptr::write(ptr_b.add(i), b);
+
+                dropper.written += 1;
+            }
+
+            mem::forget(dropper);
+
+            let (ptr_b, cap) = shrink_allocation::<A, B>(ptr_b, cap);
+
+            Ok(Vec::from_raw_parts(ptr_b, len, cap))
+        }
+    }
+}
+
+/// Yields the elements of the current group, moving them out of the buffer.
+struct Group<'a, A> {
+    ptr: *const A,
+    read: &'a mut usize,
+    end: usize,
+}
+
+impl<'a, A> Iterator for Group<'a, A> {
+    type Item = A;
+
+    #[inline]
+    fn next(&mut self) -> Option<A> {
+        if *self.read == self.end {
+            return None;
+        }
+
+        let a = unsafe { ptr::read(self.ptr.add(*self.read)) };
+        *self.read += 1;
+
+        Some(a)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.end - *self.read;
+        (n, Some(n))
+    }
+}
+
+/// `written` is the number of groups that have been mapped, and `read` the number of elements
+/// that have been moved out.
+///
+/// The buffer is held as raw parts, and panicking destructors are handled, as in `VecDropper`.
+struct GroupDropper<A, B> {
+    read: usize,
+    written: usize,
+    ptr: *mut A,
+    len: usize,
+    cap: usize,
+    _marker: PhantomData<B>,
+}
+
+impl<A, B> GroupDropper<A, B> {
+    /// Takes over the elements and the buffer of `v`.
+    #[inline]
+    fn new(v: Vec<A>) -> Self {
+        let mut v = mem::ManuallyDrop::new(v);
+
+        GroupDropper {
+            read: 0,
+            written: 0,
+            ptr: v.as_mut_ptr(),
+            len: v.len(),
+            cap: v.capacity(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<A, B> Drop for GroupDropper<A, B> {
+    #[inline]
+    fn drop(&mut self) {
+        // panicked; manual cleanup needed
+        let ptr_a = self.ptr;
+        let ptr_b = ptr_a as *mut B;
+
+        unsafe {
+            // frees the buffer once the elements are gone
+            let _owned = Vec::from_raw_parts(ptr_a, 0, self.cap);
+
+            let _rest = DropSlice(ptr::slice_from_raw_parts_mut(ptr_a.add(self.read),
+                                                                self.len - self.read));
+
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(ptr_b, self.written));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use MapGroupsInPlace;
+    use testing::DropTracker;
+
+    use std::panic::catch_unwind;
+
+    #[test]
+    fn groups_vec() {
+        let channels = 3;
+        let v: Vec<i16> = vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.map_groups_in_place(channels, |frame| frame.sum::<i16>() / 3).unwrap();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![2, 5, 8, 11]);
+    }
+
+    #[test]
+    fn groups_vec_uneven() {
+        let v: Vec<u32> = vec![1, 2, 3, 4, 5];
+        let v = v.map_groups_in_place(2, |_| -> u32 { unreachable!() }).unwrap_err();
+        assert_eq!(v, vec![1, 2, 3, 4, 5]);
+
+        let v = v.map_groups_in_place(0, |_| -> u32 { unreachable!() }).unwrap_err();
+        assert_eq!(v, vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn groups_vec_zst() {
+        let v = vec![1u32, 2, 3, 4];
+        let mut sums = vec![];
+        let v = v.map_groups_in_place(2, |g| sums.push(g.next().unwrap())).unwrap();
+
+        assert_eq!(v.len(), 2);
+        assert_eq!(sums, vec![1, 3]);
+    }
+
+    #[test]
+    fn groups_vec_alignment_mismatch() {
+        // the results go in a new allocation
+        let v = vec![1u8, 2, 3, 4];
+        let v = v.map_groups_in_place(2, |g| g.map(u32::from).sum::<u32>()).unwrap();
+
+        assert_eq!(v, vec![3, 7]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn groups_vec_too_large() {
+        let v: Vec<u16> = vec![1, 2];
+        let _ = v.map_groups_in_place(1, |_| [0u16; 2]);
+    }
+
+    #[test]
+    fn groups_vec_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (1..7).map(|x| DROPS.track(x)).collect();
+
+        assert!(catch_unwind(|| {
+            v.map_groups_in_place(2, |g| {
+                // only take the first of each group
+                let x: u32 = g.next().unwrap().0;
+
+                if x == 5 {
+                    panic!();
+                }
+
+                DROPS.track(-(x as i32))
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&[// consume Xs
+                             "1",
+                             // drop the rest of the group
+                             "2",
+                             "3",
+                             "4",
+                             // panic here
+                             "5",
+                             // drop mapped elements
+                             "-1",
+                             "-3",
+                             // drop remaining unprocessed elements
+                             "6"]);
+    }
+}
@@ -0,0 +1,653 @@
+//! Iterator adapters whose results can be collected back into the buffer of the `Vec` they
+//! were made from, see `IntoInPlaceIter` and `CollectInPlace`.
+//!
+//! Each adapter produces at most one item for every element it consumes, so the items can
+//! always be written behind the elements that have been read. The adapters aren't
+//! double-ended, as reading from the back would break that.
+
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::mem::{self, ManuallyDrop};
+use core::ptr;
+
+use {CollectInPlace, DrainMap, InPlaceIterable, IntoInPlaceIter};
+
+/// Owns the buffer of a `Vec<A>`, and yields its elements from front to back.
+#[derive(Debug)]
+pub struct InPlaceIter<A> {
+    buf: *mut A,
+    cap: usize,
+    len: usize,
+    read: usize,
+    _marker: PhantomData<A>,
+}
+
+impl<A> InPlaceIter<A> {
+    /// Drops the elements that haven't been read and gives up ownership of the buffer, which
+    /// is left for the caller to free with the layout of a `Vec<A>` with capacity `cap`.
+    ///
+    /// If an element panics when dropped, the rest are still dropped and the buffer is kept.
+    #[inline]
+    fn take_buffer(&mut self) -> (*mut A, usize) {
+        let rest = ptr::slice_from_raw_parts_mut(unsafe { self.buf.add(self.read) },
+                                                 self.len - self.read);
+        self.read = self.len;
+
+        unsafe {
+            ptr::drop_in_place(rest);
+        }
+
+        (self.buf, mem::replace(&mut self.cap, 0))
+    }
+}
+
+impl<A> Iterator for InPlaceIter<A> {
+    type Item = A;
+
+    #[inline]
+    fn next(&mut self) -> Option<A> {
+        if self.read == self.len {
+            return None;
+        }
+
+        let a = unsafe { ptr::read(self.buf.add(self.read)) };
+        self.read += 1;
+
+        Some(a)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.len - self.read;
+        (n, Some(n))
+    }
+}
+
+impl<A> Drop for InPlaceIter<A> {
+    #[inline]
+    fn drop(&mut self) {
+        let (buf, cap) = self.take_buffer();
+
+        if cap != 0 {
+            unsafe {
+                drop(Vec::from_raw_parts(buf, 0, cap));
+            }
+        }
+    }
+}
+
+unsafe impl<A> InPlaceIterable for InPlaceIter<A> {
+    type Source = A;
+
+    #[inline]
+    fn as_source(&mut self) -> &mut InPlaceIter<A> {
+        self
+    }
+}
+
+impl<A> IntoInPlaceIter<A> for Vec<A> {
+    #[inline]
+    fn in_place_iter(self) -> InPlaceIter<A> {
+        let mut v = ManuallyDrop::new(self);
+
+        InPlaceIter {
+            buf: v.as_mut_ptr(),
+            cap: v.capacity(),
+            len: v.len(),
+            read: 0,
+            _marker: PhantomData,
+        }
+    }
+}
+
+/// Iterator returned by `drain_map`.
+pub type DrainMapIter<A, F> = Map<InPlaceIter<A>, F>;
+
+impl<A> DrainMap<A> for Vec<A> {
+    #[inline]
+    fn drain_map<B, F>(self, f: F) -> DrainMapIter<A, F>
+        where F: FnMut(A) -> B
+    {
+        self.in_place_iter().map(f)
+    }
+}
+
+impl<I: InPlaceIterable> CollectInPlace for I {
+    /// The items are written into the buffer of the source as they are produced, then the
+    /// elements the source didn't get to are dropped.
+    ///
+    /// The buffer is kept (and shrunk to fit a whole number of items if needed) when the items
+    /// are no larger than the elements of the source and have the same alignment, and aren't
+    /// zero-sized. Otherwise the items are collected into a new `Vec`, and the buffer is freed.
+    ///
+    /// If anything panics, the items produced so far and the elements the source didn't get
+    /// to are dropped and the buffer is freed.
+    ///
+    /// Panics if the adapters yield an item that doesn't fit in the space of the elements they
+    /// consumed, which only an incorrect `InPlaceIterable` impl can cause.
+    #[inline]
+    fn collect_in_place(mut self) -> Vec<Self::Item> {
+        let a_size = mem::size_of::<I::Source>();
+        let b_size = mem::size_of::<Self::Item>();
+
+        if b_size == 0 || b_size > a_size ||
+           mem::align_of::<I::Source>() != mem::align_of::<Self::Item>() {
+            return self.collect();
+        }
+
+        // `guard` is dropped before `self` if anything panics, so the items are dropped before
+        // the source frees the buffer
+        let mut guard = CollectGuard::<I::Source, Self::Item> {
+            buf: self.as_source().buf as *mut Self::Item,
+            written: 0,
+            cap: 0,
+            _marker: PhantomData,
+        };
+
+        while let Some(b) = self.next() {
+            let read = self.as_source().read;
+
+            if (guard.written + 1) * b_size > read * a_size {
+                panic!("collect_in_place: An item doesn't fit in the elements read so far")
+            }
+
+            unsafe {
+                ptr::write(guard.buf.add(guard.written), b);
+            }
+
+            guard.written += 1;
+        }
+
+        let (_, cap) = self.as_source().take_buffer();
+        guard.cap = cap;
+
+        // the adapters may panic when dropped, the guard still owns everything
+        drop(self);
+
+        let guard = ManuallyDrop::new(guard);
+
+        unsafe {
+            let (buf, cap) = ::shrink_allocation::<I::Source, Self::Item>(guard.buf, guard.cap);
+
+            Vec::from_raw_parts(buf, guard.written, cap)
+        }
+    }
+}
+
+/// Owns the items written into the buffer of the source, and the buffer itself once it has
+/// been taken from the source (when `cap` is nonzero).
+struct CollectGuard<A, B> {
+    buf: *mut B,
+    written: usize,
+    cap: usize,
+    _marker: PhantomData<A>,
+}
+
+impl<A, B> Drop for CollectGuard<A, B> {
+    #[inline]
+    fn drop(&mut self) {
+        // panicked; manual cleanup needed
+        unsafe {
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(self.buf, self.written));
+
+            if self.cap != 0 {
+                drop(Vec::from_raw_parts(self.buf as *mut A, 0, self.cap));
+            }
+        }
+    }
+}
+
+/// Adapter returned by `map`.
+#[derive(Debug)]
+pub struct Map<I, F> {
+    iter: I,
+    f: F,
+}
+
+impl<B, I: InPlaceIterable, F> Iterator for Map<I, F>
+    where F: FnMut(I::Item) -> B
+{
+    type Item = B;
+
+    #[inline]
+    fn next(&mut self) -> Option<B> {
+        self.iter.next().map(&mut self.f)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// Adapter returned by `filter`.
+#[derive(Debug)]
+pub struct Filter<I, P> {
+    iter: I,
+    predicate: P,
+}
+
+impl<I: InPlaceIterable, P> Iterator for Filter<I, P>
+    where P: FnMut(&I::Item) -> bool
+{
+    type Item = I::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<I::Item> {
+        let predicate = &mut self.predicate;
+        self.iter.by_ref().find(|x| predicate(x))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+/// Adapter returned by `filter_map`.
+#[derive(Debug)]
+pub struct FilterMap<I, F> {
+    iter: I,
+    f: F,
+}
+
+impl<B, I: InPlaceIterable, F> Iterator for FilterMap<I, F>
+    where F: FnMut(I::Item) -> Option<B>
+{
+    type Item = B;
+
+    #[inline]
+    fn next(&mut self) -> Option<B> {
+        self.iter.by_ref().find_map(&mut self.f)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+/// Adapter returned by `map_while`.
+#[derive(Debug)]
+pub struct MapWhile<I, F> {
+    iter: I,
+    f: F,
+    done: bool,
+}
+
+impl<B, I: InPlaceIterable, F> Iterator for MapWhile<I, F>
+    where F: FnMut(I::Item) -> Option<B>
+{
+    type Item = B;
+
+    #[inline]
+    fn next(&mut self) -> Option<B> {
+        if self.done {
+            return None;
+        }
+
+        let b = self.iter.next().and_then(&mut self.f);
+        self.done = b.is_none();
+
+        b
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.done { (0, Some(0)) } else { (0, self.iter.size_hint().1) }
+    }
+}
+
+/// Adapter returned by `take_while`.
+#[derive(Debug)]
+pub struct TakeWhile<I, P> {
+    iter: I,
+    predicate: P,
+    done: bool,
+}
+
+impl<I: InPlaceIterable, P> Iterator for TakeWhile<I, P>
+    where P: FnMut(&I::Item) -> bool
+{
+    type Item = I::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<I::Item> {
+        if self.done {
+            return None;
+        }
+
+        let x = self.iter.next().filter(&mut self.predicate);
+        self.done = x.is_none();
+
+        x
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.done { (0, Some(0)) } else { (0, self.iter.size_hint().1) }
+    }
+}
+
+/// Adapter returned by `enumerate`.
+#[derive(Debug)]
+pub struct Enumerate<I> {
+    iter: I,
+    count: usize,
+}
+
+impl<I: InPlaceIterable> Iterator for Enumerate<I> {
+    type Item = (usize, I::Item);
+
+    #[inline]
+    fn next(&mut self) -> Option<(usize, I::Item)> {
+        let x = self.iter.next()?;
+        let i = self.count;
+        self.count += 1;
+
+        Some((i, x))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// Adapter returned by `take`.
+#[derive(Debug)]
+pub struct Take<I> {
+    iter: I,
+    n: usize,
+}
+
+impl<I: InPlaceIterable> Iterator for Take<I> {
+    type Item = I::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<I::Item> {
+        if self.n == 0 {
+            return None;
+        }
+
+        self.n -= 1;
+        self.iter.next()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lower, upper) = self.iter.size_hint();
+        (lower.min(self.n), Some(upper.map_or(self.n, |u| u.min(self.n))))
+    }
+}
+
+/// Adapter returned by `skip`.
+#[derive(Debug)]
+pub struct Skip<I> {
+    iter: I,
+    n: usize,
+}
+
+impl<I: InPlaceIterable> Iterator for Skip<I> {
+    type Item = I::Item;
+
+    #[inline]
+    fn next(&mut self) -> Option<I::Item> {
+        while self.n > 0 {
+            self.n -= 1;
+            self.iter.next()?;
+        }
+
+        self.iter.next()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lower, upper) = self.iter.size_hint();
+        (lower.saturating_sub(self.n), upper.map(|u| u.saturating_sub(self.n)))
+    }
+}
+
+macro_rules! in_place_iterable {
+    ($($ty:ident<I $(, $g:ident)*>),+) => {
+        $(
+            unsafe impl<I: InPlaceIterable $(, $g)*> InPlaceIterable for $ty<I $(, $g)*>
+                where Self: Iterator
+            {
+                type Source = I::Source;
+
+                #[inline]
+                fn as_source(&mut self) -> &mut InPlaceIter<I::Source> {
+                    self.iter.as_source()
+                }
+            }
+        )+
+    }
+}
+
+in_place_iterable!(Map<I, F>,
+                   Filter<I, P>,
+                   FilterMap<I, F>,
+                   MapWhile<I, F>,
+                   TakeWhile<I, P>,
+                   Enumerate<I>,
+                   Take<I>,
+                   Skip<I>);
+
+// inherent methods take precedence over those of `Iterator`, so chains of these adapters keep
+// the source reachable for `collect_in_place`
+macro_rules! adapters {
+    ($($ty:ident<$($g:ident),+>),+) => {
+        $(
+            impl<$($g),+> $ty<$($g),+> where Self: InPlaceIterable {
+                /// Like `Iterator::map`.
+                #[inline]
+                pub fn map<B, F>(self, f: F) -> Map<Self, F>
+                    where F: FnMut(<Self as Iterator>::Item) -> B
+                {
+                    Map { iter: self, f }
+                }
+
+                /// Like `Iterator::filter`.
+                #[inline]
+                pub fn filter<P>(self, predicate: P) -> Filter<Self, P>
+                    where P: FnMut(&<Self as Iterator>::Item) -> bool
+                {
+                    Filter { iter: self, predicate }
+                }
+
+                /// Like `Iterator::filter_map`.
+                #[inline]
+                pub fn filter_map<B, F>(self, f: F) -> FilterMap<Self, F>
+                    where F: FnMut(<Self as Iterator>::Item) -> Option<B>
+                {
+                    FilterMap { iter: self, f }
+                }
+
+                /// Like `Iterator::map_while`.
+                #[inline]
+                pub fn map_while<B, F>(self, f: F) -> MapWhile<Self, F>
+                    where F: FnMut(<Self as Iterator>::Item) -> Option<B>
+                {
+                    MapWhile { iter: self, f, done: false }
+                }
+
+                /// Like `Iterator::take_while`.
+                #[inline]
+                pub fn take_while<P>(self, predicate: P) -> TakeWhile<Self, P>
+                    where P: FnMut(&<Self as Iterator>::Item) -> bool
+                {
+                    TakeWhile { iter: self, predicate, done: false }
+                }
+
+                /// Like `Iterator::enumerate`.
+                #[inline]
+                pub fn enumerate(self) -> Enumerate<Self> {
+                    Enumerate { iter: self, count: 0 }
+                }
+
+                /// Like `Iterator::take`.
+                #[inline]
+                pub fn take(self, n: usize) -> Take<Self> {
+                    Take { iter: self, n }
+                }
+
+                /// Like `Iterator::skip`.
+                #[inline]
+                pub fn skip(self, n: usize) -> Skip<Self> {
+                    Skip { iter: self, n }
+                }
+            }
+        )+
+    }
+}
+
+adapters!(InPlaceIter<A>,
+          Map<I, G>,
+          Filter<I, Q>,
+          FilterMap<I, G>,
+          MapWhile<I, G>,
+          TakeWhile<I, Q>,
+          Enumerate<I>,
+          Take<I>,
+          Skip<I>);
+
+#[cfg(test)]
+mod tests {
+    use {CollectInPlace, DrainMap, IntoInPlaceIter};
+    use testing::DropTracker;
+
+    use std::panic::catch_unwind;
+
+    #[test]
+    fn collect_chain() {
+        let v: Vec<[u32; 2]> = (0..10).map(|x| [x, 0]).collect();
+
+        let bp = v.as_ptr() as *const ();
+        let cap = v.capacity();
+        let v: Vec<u32> = v.in_place_iter()
+            .skip(1)
+            .filter(|x| x[0] % 3 != 0)
+            .map(|x| x[0] * 10)
+            .take(4)
+            .collect_in_place();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v.capacity(), cap * 2);
+        assert_eq!(v, vec![10, 20, 40, 50]);
+    }
+
+    #[test]
+    fn collect_source_only() {
+        let v: Vec<String> = vec!["a".into(), "b".into()];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.in_place_iter().collect_in_place();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn collect_other_adapters() {
+        let v: Vec<&str> = vec!["1", "2", "x", "4", "5"];
+
+        let w: Vec<(usize, usize)> = v.clone()
+            .in_place_iter()
+            .map_while(|s| s.parse().ok())
+            .enumerate()
+            .collect_in_place();
+        assert_eq!(w, vec![(0, 1), (1, 2)]);
+
+        let w: Vec<usize> = v.in_place_iter()
+            .take_while(|s| s.len() == 1)
+            .filter_map(|s| s.parse().ok())
+            .collect_in_place();
+        assert_eq!(w, vec![1, 2, 4, 5]);
+    }
+
+    #[test]
+    fn collect_larger_items() {
+        let v: Vec<u32> = vec![1, 2, 3];
+        let v: Vec<u64> = v.in_place_iter().map(|x| x as u64).collect_in_place();
+
+        assert_eq!(v, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn drain_map_partially_consumed() {
+        let v: Vec<u64> = vec![1, 2, 3, 4];
+
+        let bp = v.as_ptr() as *const ();
+        let mut iter = v.drain_map(|x| x * 10);
+        assert_eq!(iter.next(), Some(10));
+
+        let v = iter.collect_in_place();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![20, 30, 40]);
+    }
+
+    #[test]
+    fn drain_map_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (0..4).map(|x| DROPS.track(x)).collect();
+        let mut iter = v.drain_map(|x| x.0);
+
+        assert_eq!(iter.next(), Some(0));
+        assert_eq!(iter.next(), Some(1));
+
+        drop(iter);
+        DROPS.assert_drops(&["0", "1", "2", "3"]);
+    }
+
+    #[test]
+    fn collect_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (0..5).map(|x| DROPS.track(x)).collect();
+        let v = v.in_place_iter().filter(|x| x.0 != 1).take(2).collect_in_place();
+
+        // the rest of the source is dropped before returning
+        DROPS.assert_drops(&["1", "3", "4"]);
+
+        drop(v);
+        DROPS.assert_drops(&["1", "3", "4", "0", "2"]);
+    }
+
+    #[test]
+    fn collect_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (1..6).map(|x| DROPS.track(x as u32)).collect();
+
+        assert!(catch_unwind(|| {
+            v.in_place_iter()
+                .filter(|x| {
+                    if x.0 == 4 {
+                        panic!();
+                    }
+
+                    x.0 != 2
+                })
+                .map(|x| DROPS.track(-(x.0 as i32)))
+                .collect_in_place()
+        }).is_err());
+
+        DROPS.assert_drops(&[// consume Xs
+                             "1",
+                             "2",
+                             "3",
+                             // panic here
+                             "4",
+                             // drop mapped elements
+                             "-1",
+                             "-3",
+                             // drop remaining unprocessed elements
+                             "5"]);
+    }
+}
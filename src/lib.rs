@@ -1,11 +1,48 @@
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+#![cfg_attr(feature = "allocator-api", feature(allocator_api))]
 
-#[cfg(test)]
 #[macro_use]
-extern crate lazy_static;
+extern crate alloc;
+#[cfg(any(feature = "std", test))]
+extern crate core;
+
+#[cfg(feature = "allocator-api")]
+mod allocator;
+mod array;
+pub mod builder;
+mod collections;
+mod filter;
+#[cfg(feature = "async")]
+pub mod future;
+mod group;
+pub mod iter;
+mod nested;
+mod payload;
+mod num;
+mod rc;
+mod retype;
+mod slice;
+mod string;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+mod tuple;
+mod uninit;
+mod zip;
 
-use std::marker::PhantomData;
-use std::ptr;
-use std::mem;
+use alloc::alloc::{dealloc, handle_alloc_error, realloc, Layout};
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::any;
+use core::convert::{Infallible, TryFrom};
+use core::error::Error;
+use core::fmt;
+use core::marker::PhantomData;
+use core::ptr;
+use core::mem;
+use core::ops::ControlFlow;
+#[cfg(feature = "std")]
+use std::panic::{self, AssertUnwindSafe};
 
 pub trait MapInPlace<A, B>: Sized {
     /// Should be of the same base type as the implementor.  
@@ -18,407 +55,4566 @@ pub trait MapInPlace<A, B>: Sized {
     /// `self.as_ptr() as *const () == self.map_in_place(..).as_ptr() as *const ()`  
     ///  
     /// An example of a case where this isn't possible is for Vec where B is zero-sized but A is not.
-    fn map_in_place<F>(self, f: F) -> Self::Output where F: FnMut(A) -> B;
+    ///
+    /// A buffer is only ever reused when it can be freed with the layout it was allocated with,
+    /// so `A` and `B` must have the same alignment. Otherwise the implementor either allocates
+    /// or panics, as documented for each.
+    ///
+    /// Panics where `map_in_place_checked` would return an error. Where the types alone rule
+    /// it out, such as a `B` larger than `A` for `Vec`, it's a compile error instead, as
+    /// documented for each implementor; `map_in_place_checked` is never one.
+    #[inline]
+    fn map_in_place<F>(self, f: F) -> Self::Output
+        where F: FnMut(A) -> B
+    {
+        expect_layout(self.map_in_place_checked(f))
+    }
+
+    /// Like `map_in_place`, but instead of panicking because of the layouts of `A` and `B`,
+    /// hands back `self` untouched in the error. Panics from `f` are unaffected.
+    fn map_in_place_checked<F>(self, f: F) -> Result<Self::Output, IncompatibleLayout<Self>>
+        where F: FnMut(A) -> B;
+
+    /// Like `map_in_place`, but `f` is also given the position of each element, in the order
+    /// the implementor maps them.
+    #[inline]
+    fn map_in_place_enumerated<F>(self, mut f: F) -> Self::Output
+        where F: FnMut(usize, A) -> B
+    {
+        let mut i = 0;
+
+        self.map_in_place(|a| {
+            let b = f(i, a);
+            i += 1;
+            b
+        })
+    }
+
+    /// Like `map_in_place`, but `f` is also given mutable access to some state, which starts as
+    /// `init` and is returned along with the result.
+    ///
+    /// If `f` panics, the state is dropped along with the elements.
+    #[inline]
+    fn scan_in_place<S, F>(self, init: S, mut f: F) -> (Self::Output, S)
+        where F: FnMut(&mut S, A) -> B
+    {
+        let mut state = init;
+        let mapped = self.map_in_place(|a| f(&mut state, a));
+
+        (mapped, state)
+    }
+
+    /// Like `scan_in_place`, but the state is borrowed rather than owned, so `f` can be a plain
+    /// `fn` and the same state can be carried across calls. If `f` panics, the state keeps
+    /// whatever changes it made.
+    #[inline]
+    fn map_in_place_with<S, F>(self, state: &mut S, mut f: F) -> Self::Output
+        where F: FnMut(&mut S, A) -> B
+    {
+        self.map_in_place(|a| f(state, a))
+    }
+
+    /// Like `scan_in_place`, but the accumulator is passed to `f` by value, and `f` returns the
+    /// next one along with each result.
+    ///
+    /// If `f` panics, the accumulator it was given is dropped along with the elements. If the
+    /// implementor panics before calling `f`, it is leaked.
+    #[inline]
+    fn map_fold_in_place<R, F>(self, init: R, mut f: F) -> (Self::Output, R)
+        where F: FnMut(R, A) -> (R, B)
+    {
+        let mut acc = mem::ManuallyDrop::new(init);
+
+        let mapped = self.map_in_place(|a| {
+            // `acc` is refilled before it is taken again, and if `f` panics, it already owns
+            // the value that was taken out
+            let (r, b) = f(unsafe { mem::ManuallyDrop::take(&mut acc) }, a);
+            acc = mem::ManuallyDrop::new(r);
+            b
+        });
+
+        (mapped, mem::ManuallyDrop::into_inner(acc))
+    }
+
+    /// Like `map_in_place`, but `f` is also given the element before each one, or `None` for
+    /// the first, as it was before being mapped.
+    ///
+    /// Each element is kept aside until the next one has been mapped, so `f` only borrows it;
+    /// the last one is dropped at the end. If `f` panics, the element kept aside is dropped
+    /// after the others.
+    #[inline]
+    fn map_with_prev_in_place<F>(self, mut f: F) -> Self::Output
+        where F: FnMut(Option<&A>, &A) -> B
+    {
+        let mut prev = None;
+
+        self.map_in_place(|a| {
+            let b = f(prev.as_ref(), &a);
+            prev = Some(a);
+            b
+        })
+    }
+}
+
+pub trait MapInPlaceDyn<A, B>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Vec<B>` when implementing for `Vec<A>`
+    type Output;
+
+    /// Like `map_in_place`, but `f` is a trait object, so there's a single copy of the
+    /// implementation per `A` and `B` instead of one per closure type.
+    ///
+    /// This costs a virtual call per element, which matters most when the elements are small
+    /// and `f` would otherwise be inlined. Prefer it when the same pair of types is mapped
+    /// from many call sites and compile time or binary size is the concern; otherwise prefer
+    /// `map_in_place`.
+    fn map_in_place_dyn(self, f: &mut dyn FnMut(A) -> B) -> Self::Output;
+}
+
+#[cfg(feature = "async")]
+pub trait MapInPlaceAsync<A, B>: Sized {
+    /// Like `map_in_place`, but `f` returns a future for each element, which is driven to
+    /// completion before the next element is read. The result of each future is written where
+    /// the element it was made from was.
+    fn map_in_place_async<F, Fut>(self, f: F) -> future::MapAsync<A, F, Fut>
+        where F: FnMut(A) -> Fut,
+              Fut: ::core::future::Future<Output = B>;
+}
+
+pub trait MapWithMappedPrefix<A, B>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Vec<B>` when implementing for `Vec<A>`
+    type Output;
+
+    /// Like `map_in_place`, but `f` is also given the results produced so far, in order.
+    fn map_with_mapped_prefix<F>(self, f: F) -> Self::Output where F: FnMut(&[B], A) -> B;
+}
+
+pub trait MapWithNextInPlace<A, B>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Vec<B>` when implementing for `Vec<A>`
+    type Output;
+
+    /// Like `map_in_place`, but `f` is also given the element after each one, or `None` for
+    /// the last, before it has been mapped.
+    fn map_with_next_in_place<F>(self, f: F) -> Self::Output where F: FnMut(A, Option<&A>) -> B;
+}
+
+pub trait MapInPlaceRev<A, B>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Vec<B>` when implementing for `Vec<A>`
+    type Output;
+
+    /// Like `map_in_place`, but calls `f` on the elements back to front. Each result still
+    /// ends up where the element it was mapped from was.
+    fn map_in_place_rev<F>(self, f: F) -> Self::Output where F: FnMut(A) -> B;
+}
+
+pub trait MapInPlaceGrow<A, B>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Vec<B>` when implementing for `Vec<A>`
+    type Output;
+
+    /// Like `map_in_place`, but `B` may be larger than `A` as long as the results fit in the
+    /// buffer of `self`. Otherwise `self` is handed back untouched.
+    fn map_in_place_grow<F>(self, f: F) -> Result<Self::Output, Self> where F: FnMut(A) -> B;
+}
+
+pub trait MapInPlaceShrink<A, B>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Vec<B>` when implementing for `Vec<A>`
+    type Output;
+
+    /// Like `map_in_place`, but then gives the capacity that isn't needed to hold the results
+    /// back to the allocator where that can't copy them, instead of keeping it for reuse.
+    fn map_in_place_shrink<F>(self, f: F) -> Self::Output where F: FnMut(A) -> B;
+}
+
+pub trait ReplaceEach<A> {
+    /// Replace every element of `self` with the result of applying `f` to it by value, in
+    /// order.
+    ///
+    /// If `f` panics, the element it was given is gone and `self` can't be left with a hole,
+    /// so the process is aborted, as with `take_mut::take`.
+    fn replace_each<F>(&mut self, f: F) where F: FnMut(A) -> A;
+
+    /// Like `replace_each`, but if `f` panics, the element it was given is replaced with the
+    /// result of `recover` before the panic continues. The elements that weren't reached are
+    /// left as they are.
+    ///
+    /// If `recover` panics too, the process is aborted.
+    fn replace_each_or_else<F, R>(&mut self, f: F, recover: R)
+        where F: FnMut(A) -> A,
+              R: FnOnce() -> A;
+}
+
+pub trait MapInPlaceSame<A> {
+    /// Replace every element of `self` with the result of applying `f` to it by value, in
+    /// order, without taking ownership of the container.
+    ///
+    /// If `f` panics, `self` is truncated to the elements that were already replaced; the
+    /// element `f` was given and the ones that weren't reached are dropped.
+    fn map_in_place_same<F>(&mut self, f: F) where F: FnMut(A) -> A;
+}
+
+pub trait MapInPlaceOrAlloc<A, B>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Vec<B>` when implementing for `Vec<A>`
+    type Output;
+
+    /// Like `map_in_place`, but never panics because of the layouts of `A` and `B`: when the
+    /// buffer can't be reused, the elements are mapped into a new allocation instead.
+    fn map_in_place_or_alloc<F>(self, f: F) -> Self::Output where F: FnMut(A) -> B;
+}
+
+pub trait CheckedMapInPlace<A, B>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Vec<B>` when implementing for `Vec<A>`
+    type Output;
+
+    /// Like `map_in_place`, but instead of panicking because of the layouts of `A` and `B`,
+    /// hands back `self` untouched along with the reason. Panics from `f` are unaffected.
+    fn checked_map_in_place<F>(self, f: F) -> Result<Self::Output, (Self, MapError)>
+        where F: FnMut(A) -> B;
+}
+
+pub trait TryMapInPlace<A, B>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Vec<B>` when implementing for `Vec<A>`
+    type Output;
+
+    /// Apply a fallible mapping function to `self` without allocating, stopping at the first
+    /// error. Makes the same best effort to maintain the address of `self` as `map_in_place`.
+    fn try_map_in_place<F, E>(self, f: F) -> Result<Self::Output, E>
+        where F: FnMut(A) -> Result<B, E>;
+
+    /// Like `try_map_in_place`, but on error hands back what was mapped so far, the elements
+    /// that weren't reached yet, and the error. The element `f` failed on is consumed.
+    fn try_map_in_place_partial<F, E>(self, f: F) -> Result<Self::Output, (Self::Output, Self, E)>
+        where F: FnMut(A) -> Result<B, E>;
+}
+
+#[cfg(feature = "std")]
+pub trait MapInPlaceCatch<A, B>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Vec<B>` when implementing for `Vec<A>`
+    type Output;
+
+    /// Like `map_in_place`, but if `f` panics, the panic is caught rather than unwinding out of
+    /// here. The elements mapped so far are dropped, and the ones that weren't reached yet are
+    /// handed back in the error along with the panic's payload.
+    ///
+    /// `f` is treated as unwind safe, as with `AssertUnwindSafe`; whatever it captures may be
+    /// left in a broken state by the panic. The panic hook still runs as usual, and with
+    /// `panic = "abort"` there's nothing to catch.
+    fn map_in_place_catch<F>(self, f: F) -> Result<Self::Output, MapPanicked<A>>
+        where F: FnMut(A) -> B;
+}
+
+pub trait TryMapInPlaceWithRollback<A, B>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Vec<B>` when implementing for `Vec<A>`
+    type Output;
+
+    /// Like `try_map_in_place`, but on error, `undo` is used to turn everything mapped so far
+    /// back into what it was mapped from, and `self` is handed back as it was along with the
+    /// error.
+    ///
+    /// `f` has to give back the element it failed on with the error, as `self` couldn't be
+    /// restored without it.
+    fn try_map_in_place_with_rollback<F, G, E>(self,
+                                               f: F,
+                                               undo: G)
+                                               -> Result<Self::Output, (Self, E)>
+        where F: FnMut(A) -> Result<B, (A, E)>,
+              G: FnMut(B) -> A;
+}
+
+pub trait MapInPlaceCollectErrors<A, B>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Vec<B>` when implementing for `Vec<A>`
+    type Output;
+
+    /// Like `try_map_in_place`, but doesn't stop at the first error: every element is mapped,
+    /// and if any of them failed, all the errors are returned along with the position of the
+    /// element they came from.
+    fn map_in_place_collect_errors<F, E>(self, f: F) -> Result<Self::Output, Vec<(usize, E)>>
+        where F: FnMut(A) -> Result<B, E>;
+}
+
+pub trait FilterMapInPlace<A, B>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Vec<B>` when implementing for `Vec<A>`
+    type Output;
+
+    /// Apply a mapping function to `self` without allocating, keeping only the `Some`s and
+    /// compacting them in a single pass.
+    fn filter_map_in_place<F>(self, f: F) -> Self::Output where F: FnMut(A) -> Option<B>;
+}
+
+pub trait TryFilterMapInPlace<A, B, E>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Vec<B>` when implementing for `Vec<A>`
+    type Output;
+
+    /// Like `filter_map_in_place`, but stops at the first error `f` returns.
+    fn try_filter_map_in_place<F>(self, f: F) -> Result<Self::Output, E>
+        where F: FnMut(A) -> Result<Option<B>, E>;
+}
+
+pub trait FlattenOptionsInPlace<A>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Vec<A>` when implementing for `Vec<Option<A>>`
+    type Output;
+
+    /// Drop the `None`s of `self` and unwrap the `Some`s, compacting them to the front without
+    /// allocating.
+    fn flatten_options_in_place(self) -> Self::Output;
+}
+
+pub trait MapWhileInPlace<A, B>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Vec<B>` when implementing for `Vec<A>`
+    type Output;
+
+    /// Apply a mapping function to `self` without allocating, stopping at the first `None` and
+    /// dropping the rest of the elements.
+    fn map_while_in_place<F>(self, f: F) -> Self::Output where F: FnMut(A) -> Option<B>;
+}
+
+pub trait DedupMapInPlace<A, B: PartialEq>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Vec<B>` when implementing for `Vec<A>`
+    type Output;
+
+    /// Apply a mapping function to `self` without allocating, dropping each result that is
+    /// equal to the previous one kept, and compacting the rest in a single pass.
+    fn dedup_map_in_place<F>(self, f: F) -> Self::Output where F: FnMut(A) -> B;
+}
+
+pub trait PartitionMapInPlace<A, B>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Vec<B>` when implementing for `Vec<A>`
+    type Output;
+
+    /// Map each element of `self` to either a `B`, kept in place without allocating, or a `C`,
+    /// collected into a new `Vec`.
+    fn partition_map_in_place<C, F>(self, f: F) -> (Self::Output, Vec<C>)
+        where F: FnMut(A) -> Either<B, C>;
+}
+
+pub trait SiftMapInPlace<A, B>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Vec<B>` when implementing for `Vec<A>`
+    type Output;
+
+    /// Like `partition_map_in_place`, but with `Result`s: the successes are kept in place, and
+    /// the errors are collected into a new `Vec` along with the position of the element they
+    /// came from.
+    fn sift_map_in_place<E, F>(self, f: F) -> (Self::Output, Vec<(usize, E)>)
+        where F: FnMut(A) -> Result<B, E>;
+}
+
+pub trait SplitMapInPlace<A, B>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Vec<B>` when implementing for `Vec<A>`
+    type Output;
+
+    /// Like `partition_map_in_place`, but each element is routed by `pred` before being
+    /// consumed: the ones it accepts are mapped by `left` and kept in place, and the rest are
+    /// mapped by `right` and collected into a new `Vec`.
+    fn split_map_in_place<C, P, F, G>(self, pred: P, left: F, right: G) -> (Self::Output, Vec<C>)
+        where P: FnMut(&A) -> bool,
+              F: FnMut(A) -> B,
+              G: FnMut(A) -> C;
+}
+
+pub trait RetainMap<A> {
+    /// Pass each element of `self` by value to `f`, replacing it with the result or removing
+    /// it on `None`, and compact what's left, without moving `self` or allocating.
+    fn retain_map<F>(&mut self, f: F) where F: FnMut(A) -> Option<A>;
+}
+
+pub trait ZipMapInPlace<A, B>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Vec<B>` when implementing for `Vec<A>`
+    type Output;
+
+    /// Combine the elements of `self` with those of `other` pairwise, writing the results over
+    /// `self` without allocating.
+    ///
+    /// Returns both `self` and `other`, untouched, if their lengths differ.
+    fn zip_map_in_place<C, F>(self, other: Vec<C>, f: F) -> Result<Self::Output, (Self, Vec<C>)>
+        where F: FnMut(A, C) -> B;
+
+    /// Like `zip_map_in_place`, but also hands back the buffer of `other`, emptied, so it can be
+    /// refilled.
+    #[allow(clippy::type_complexity)]
+    fn zip_map_in_place_recycle<C, F>(self,
+                                      other: Vec<C>,
+                                      f: F)
+                                      -> Result<(Self::Output, Vec<C>), (Self, Vec<C>)>
+        where F: FnMut(A, C) -> B;
+}
+
+pub trait MapSplitInPlace<A, B>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Vec<B>` when implementing for `Vec<A>`
+    type Output;
+
+    /// Split each element of `self` in two, keeping the first part in place without allocating,
+    /// and collecting the second part into a new `Vec`.
+    fn map_split_in_place<C, F>(self, f: F) -> (Self::Output, Vec<C>) where F: FnMut(A) -> (B, C);
+}
+
+pub trait MapInPlaceUntil<A, B>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Vec<B>` when implementing for `Vec<A>`
+    type Output;
+
+    /// Apply a mapping function to `self` without allocating, until it breaks. On a break, also
+    /// returns its value along with the elements that weren't reached yet. The element `f`
+    /// broke on is consumed.
+    fn map_in_place_until<C, F>(self, f: F) -> (Self::Output, Option<(C, Self)>)
+        where F: FnMut(A) -> ControlFlow<C, B>;
+}
+
+pub trait RecycleInPlace<A>: Sized {
+    /// Drop every element of `self` and reuse its allocation as an empty `Vec<B>`, with as
+    /// much capacity as fits, without allocating.
+    ///
+    /// Named so as not to clash with the unstable `Vec::recycle`.
+    fn recycle_in_place<B>(self) -> Vec<B>;
+}
+
+pub trait ConvertInPlace<A>: Sized {
+    /// Convert every element of `self` with `B::from`, without allocating.
+    ///
+    /// `B` must be no larger than `A` and have the same alignment, unless it's zero-sized.
+    /// Otherwise it's a compile error:
+    ///
+    /// ```compile_fail
+    /// use map_in_place::ConvertInPlace;
+    ///
+    /// let _ = vec![0u16; 4].convert_in_place::<u32>();
+    /// ```
+    fn convert_in_place<B: From<A>>(self) -> Vec<B>;
+}
+
+pub trait TryConvertInPlace<A>: Sized {
+    /// Convert every element of `self` with `B::try_from`, without allocating, stopping at
+    /// the first error. `B` must fit in the buffer of `self` as for `convert_in_place`.
+    fn try_convert_in_place<B: TryFrom<A>>(self) -> Result<Vec<B>, B::Error>;
+}
+
+/// Types whose values can be reinterpreted from values of `A` as they are, byte for byte.
+///
+/// # Safety
+///
+/// `Self` must have the same size and alignment as `A`, and every valid `A` must also be a
+/// valid `Self`, e.g. because `Self` is a `#[repr(transparent)]` wrapper around `A`. Dropping
+/// the reinterpreted value must be correct in place of dropping the `A`.
+pub unsafe trait TransmutableFrom<A> {}
+
+pub trait RetypeInPlace<A>: Sized {
+    /// Reinterpret `self` as a `Vec<B>` without touching its elements.
+    ///
+    /// Fails to compile if `A` and `B` differ in size or alignment.
+    fn retype_in_place<B: TransmutableFrom<A>>(self) -> Vec<B>;
+}
+
+pub trait InPlace<A>: Sized {
+    /// Start configuring how `self` is mapped in place, see `builder::MapInPlaceBuilder`.
+    fn in_place(self) -> builder::MapInPlaceBuilder<A>;
+}
+
+pub trait IntoInPlaceIter<A>: Sized {
+    /// Turn `self` into an iterator over its elements, whose adapters can be collected back
+    /// into the buffer of `self` with `collect_in_place`.
+    fn in_place_iter(self) -> iter::InPlaceIter<A>;
+}
+
+/// Iterators made of the adapters in `iter`, which can reach the `InPlaceIter` at their
+/// source.
+///
+/// # Safety
+///
+/// `as_source` must always return the same `InPlaceIter`, being the one the items of `self`
+/// are made from, and may only read from it through its `Iterator` impl.
+pub unsafe trait InPlaceIterable: Iterator {
+    /// The element type of the `Vec` at the source.
+    type Source;
+
+    /// The iterator at the source of `self`.
+    fn as_source(&mut self) -> &mut iter::InPlaceIter<Self::Source>;
+}
+
+pub trait DrainMap<A>: Sized {
+    /// Turn `self` into an iterator that maps its elements lazily, front to back. Collecting
+    /// it with `collect_in_place` writes the rest of the results into the buffer of `self`.
+    ///
+    /// If the iterator is dropped early, the elements it didn't get to are dropped and the
+    /// buffer is freed.
+    fn drain_map<B, F>(self, f: F) -> iter::DrainMapIter<A, F> where F: FnMut(A) -> B;
+}
+
+pub trait CollectInPlace: InPlaceIterable + Sized {
+    /// Collect the items of `self` into the buffer of the `Vec` it was made from, without
+    /// allocating.
+    fn collect_in_place(self) -> Vec<Self::Item>;
+}
+
+pub trait MapValuesInPlace<A, B>: Sized {
+    /// Should be of the same base type as the implementor, with the same keys.  
+    /// E.g. `Vec<(K, B)>` when implementing for `Vec<(K, A)>`
+    type Output;
+
+    /// Apply a mapping function to the values of `self`, leaving the keys untouched. Whether
+    /// the storage of `self` is reused depends on the implementor, see its docs.
+    fn map_values_in_place<F>(self, f: F) -> Self::Output where F: FnMut(A) -> B;
+}
+
+pub trait MapOkInPlace<A, B, E>: Sized {
+    /// Should be of the same base type as the implementor, with the same error type.  
+    /// E.g. `Mapped<Vec<Result<B, E>>>` when implementing for `Vec<Result<A, E>>`
+    type Output;
+
+    /// Apply a mapping function to the `Ok` values of `self`, leaving the `Err`s as they are.
+    fn map_ok_in_place<F>(self, f: F) -> Self::Output where F: FnMut(A) -> B;
+}
+
+pub trait MapErrInPlace<A, E, E2>: Sized {
+    /// Should be of the same base type as the implementor, with the same value type.  
+    /// E.g. `Mapped<Vec<Result<A, E2>>>` when implementing for `Vec<Result<A, E>>`
+    type Output;
+
+    /// Apply a mapping function to the `Err` values of `self`, leaving the `Ok`s as they are.
+    fn map_err_in_place<F>(self, f: F) -> Self::Output where F: FnMut(E) -> E2;
+}
+
+pub trait MapSomeInPlace<A, B>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Mapped<Vec<Option<B>>>` when implementing for `Vec<Option<A>>`
+    type Output;
+
+    /// Apply a mapping function to the `Some` values of `self`, leaving the `None`s as they
+    /// are.
+    fn map_some_in_place<F>(self, f: F) -> Self::Output where F: FnMut(A) -> B;
+}
+
+pub trait CollectResultsInPlace<A, E>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Vec<A>` when implementing for `Vec<Result<A, E>>`
+    type Output;
+
+    /// Unwrap the `Ok` values of `self` without allocating, stopping at the first `Err`, like
+    /// collecting into a `Result<Vec<A>, E>`.
+    fn collect_results_in_place(self) -> Result<Self::Output, E>;
+}
+
+pub trait MapFirstInPlace<A, B>: Sized {
+    /// Should be of the same base type as the implementor, with the same second field.  
+    /// E.g. `Vec<(B, C)>` when implementing for `Vec<(A, C)>`
+    type Output;
+
+    /// Apply a mapping function to the first field of each pair of `self` without allocating,
+    /// leaving the second field where it is.
+    fn map_first_in_place<F>(self, f: F) -> Self::Output where F: FnMut(A) -> B;
+}
+
+pub trait MapTuplesInPlace<A, B, C>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Vec<C>` when implementing for `Vec<(A, B)>`
+    type Output;
+
+    /// Collapse each pair of `self` into a single value without allocating.
+    fn map_tuples_in_place<F>(self, f: F) -> Self::Output where F: FnMut((A, B)) -> C;
+}
+
+pub trait UnzipInPlace: Sized {
+    /// Should be a pair of containers of the same base type as the implementor.  
+    /// E.g. `(Vec<A>, Vec<B>)` when implementing for `Vec<(A, B)>`
+    type Output;
+
+    /// Split the pairs of `self` into two containers, reusing the allocation of `self` for the
+    /// first one where possible.
+    fn unzip_in_place(self) -> Self::Output;
+}
+
+pub trait MapInPlaceMonotonic<A, B>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `BinaryHeap<B>` when implementing for `BinaryHeap<A>`
+    type Output;
+
+    /// Like `map_in_place`, but may skip restoring the ordering invariant of `self` afterwards,
+    /// or some of the work of doing so.
+    ///
+    /// # Safety
+    ///
+    /// `f` must preserve the order of the elements: for any `x` and `y` in `self`,
+    /// `x.cmp(&y) == f(x).cmp(&f(y))`. Otherwise the result is a collection whose invariants
+    /// don't hold, and whose later behavior is unspecified.
+    unsafe fn map_in_place_monotonic<F>(self, f: F) -> Self::Output where F: FnMut(A) -> B;
 }
 
-impl<A, B> MapInPlace<A, B> for Box<A> {
-    type Output = Box<B>;
+pub trait MapInPlaceUnique<A, B>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Rc<[B]>` when implementing for `Rc<[A]>`
+    type Output;
+
+    /// Apply a mapping function to `self` without allocating, if `self` is the only reference
+    /// to its allocation and the allocation can hold the result. Otherwise returns `self`
+    /// untouched, e.g. so that the caller can fall back to cloning.
+    fn map_in_place_unique<F>(self, f: F) -> Result<Self::Output, Self> where F: FnMut(A) -> B;
+}
+
+pub trait MapNestedInPlace<A, B>: Sized {
+    /// Should be of the same base types as the implementor.  
+    /// E.g. `Vec<Vec<B>>` when implementing for `Vec<Vec<A>>`
+    type Output;
+
+    /// Apply a mapping function to every element of every inner container of `self`, without
+    /// allocating, reusing both the outer and the inner allocations.
+    fn map_nested_in_place<F>(self, f: F) -> Self::Output where F: FnMut(A) -> B;
+}
+
+pub trait MapBoxedInPlace<A, B>: Sized {
+    /// Should be of the same base types as the implementor.  
+    /// E.g. `Vec<Box<B>>` when implementing for `Vec<Box<A>>`
+    type Output;
+
+    /// Apply a mapping function to every boxed element of `self`, reusing the allocation of
+    /// `self` and, wherever `A` and `B` have the same layout, those of the boxes.
+    fn map_boxed_in_place<F>(self, f: F) -> Self::Output where F: FnMut(A) -> B;
+}
+
+pub trait ShrinkWrapInPlace: Sized {
+    /// Should be of the same base type as the implementor, with tightly-sized elements.  
+    /// E.g. `Vec<Box<[A]>>` when implementing for `Vec<Vec<A>>`
+    type Output;
+
+    /// Convert every element of `self` into its tightly-sized equivalent, reusing the
+    /// allocation of `self`.
+    fn shrink_wrap_in_place(self) -> Self::Output;
+}
+
+pub trait MapChunksInPlace<A, B>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Vec<B>` when implementing for `Vec<A>`
+    type Output;
+
+    /// Map each group of `N` consecutive elements of `self` into a single element without
+    /// allocating.
+    ///
+    /// Returns `Err(self)`, untouched, if the elements can't be evenly split into groups of `N`,
+    /// or if `B` doesn't fit in the space of a group.
+    fn map_chunks_in_place<const N: usize, F>(self, f: F) -> Result<Self::Output, Self>
+        where F: FnMut([A; N]) -> B;
+}
+
+pub trait MapGroupsInPlace<A, B>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Vec<B>` when implementing for `Vec<A>`
+    type Output;
+
+    /// Like `map_chunks_in_place`, but the length of the groups is only known at runtime, so
+    /// `f` is given an iterator over the elements of each group instead of an array.
+    ///
+    /// Returns `Err(self)`, untouched, if the elements can't be evenly split into groups of
+    /// `group`.
+    fn map_groups_in_place<F>(self, group: usize, f: F) -> Result<Self::Output, Self>
+        where F: FnMut(&mut dyn Iterator<Item = A>) -> B;
+}
+
+pub trait MapPairsInPlace<A, B>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Vec<B>` when implementing for `Vec<A>`
+    type Output;
+
+    /// Map each pair of adjacent elements of `self` into a single element without allocating.
+    ///
+    /// Returns `Err(self)`, untouched, if `self` has an odd number of elements, or if `B` doesn't
+    /// fit in the space of a pair.
+    fn map_pairs_in_place<F>(self, f: F) -> Result<Self::Output, Self> where F: FnMut(A, A) -> B;
+}
+
+pub trait FlatMapExactInPlace<A, B>: Sized {
+    /// Should be of the same base type as the implementor.  
+    /// E.g. `Vec<B>` when implementing for `Vec<A>`
+    type Output;
+
+    /// Map each element of `self` into `N` consecutive elements without allocating.
+    fn flat_map_exact_in_place<const N: usize, F>(self, f: F) -> Self::Output
+        where F: FnMut(A) -> [B; N];
+}
+
+pub trait FlattenInPlace: Sized {
+    /// Should be of the same base type as the implementor, with the inner elements.  
+    /// E.g. `Vec<A>` when implementing for `Vec<[A; N]>`
+    type Output;
+
+    /// Flatten `self` without allocating, reinterpreting its buffer as holding the inner
+    /// elements.
+    fn flatten_in_place(self) -> Self::Output;
+}
+
+pub trait ChunkInPlace<A>: Sized {
+    /// Group consecutive elements of `self` into arrays of `N` without allocating, reinterpreting
+    /// its buffer as holding the arrays.
+    ///
+    /// Returns `Err(self)`, untouched, if the elements (or the buffer) can't be evenly split
+    /// into arrays of `N`.
+    fn chunk_in_place<const N: usize>(self) -> Result<Vec<[A; N]>, Self>;
+}
+
+pub trait InitInPlace<A>: Sized {
+    /// Should be of the same base type as the implementor, holding initialized elements.  
+    /// E.g. `Vec<A>` when implementing for `Vec<MaybeUninit<A>>`
+    type Output;
+
+    /// Initialize every element of `self` with `f`, which is given its index, without
+    /// allocating.
+    fn init_in_place<F>(self, f: F) -> Self::Output where F: FnMut(usize) -> A;
+}
+
+pub trait DeinitInPlace: Sized {
+    /// Should be of the same base type as the implementor, holding possibly uninitialized
+    /// elements.  
+    /// E.g. `Vec<MaybeUninit<A>>` when implementing for `Vec<A>`
+    type Output;
+
+    /// Give up ownership of the elements of `self` without dropping them or allocating.
+    fn deinit_in_place(self) -> Self::Output;
+}
+
+/// Primitive numeric types that `Self` can be cast to with `as`, where both have the same size
+/// and the cast is a plain reinterpretation or rounding: integer to integer, integer to float
+/// and float to float.
+///
+/// This trait is sealed.
+pub trait NumCast<T>: num::Sealed<T> {}
+
+/// Float types that can be cast to a primitive integer type of the same size with `as`, which
+/// saturates: out of range values are clamped to the bounds of `T`, and NaN becomes zero.
+///
+/// This trait is sealed.
+pub trait SaturatingNumCast<T>: num::Sealed<T> {}
+
+pub trait CastNumInPlace<A>: Sized {
+    /// Cast every element of `self` to `T` with `as`, without allocating.
+    ///
+    /// Only casts between types of the same size are available, so widening (or narrowing) is
+    /// a compile error:
+    ///
+    /// ```compile_fail
+    /// use map_in_place::CastNumInPlace;
+    ///
+    /// let _ = vec![0u16; 4].cast_num_in_place::<u32>();
+    /// ```
+    fn cast_num_in_place<T>(self) -> Vec<T> where A: NumCast<T>;
+
+    /// Cast every float of `self` to the integer type `T` with `as`, without allocating.
+    /// Out of range values saturate, and NaN becomes zero.
+    fn saturating_cast_num_in_place<T>(self) -> Vec<T> where A: SaturatingNumCast<T>;
+}
+
+pub trait MapAsciiInPlace: Sized {
+    /// Apply a mapping function to every ASCII byte of `self` without allocating, passing
+    /// all other bytes (those making up multi-byte characters) through untouched.
+    ///
+    /// `f` must map ASCII bytes to ASCII bytes, so that `self` stays valid UTF-8.
+    fn map_ascii_in_place<F>(self, f: F) -> Self where F: FnMut(u8) -> u8;
+}
+
+pub trait MapBytesInPlace: Sized {
+    /// The byte buffer type `self` is converted into.  
+    /// E.g. `Vec<u8>` when implementing for `String`
+    type Output;
+
+    /// Apply a mapping function to every byte of `self` without allocating, giving up any
+    /// guarantee that the result is valid UTF-8.
+    fn map_bytes_in_place<F>(self, f: F) -> Self::Output where F: FnMut(u8) -> u8;
+}
+
+/// The result of mapping something that may or may not own a buffer to map into.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mapped<T> {
+    /// Mapped into the existing buffer.
+    InPlace(T),
+    /// Mapped into a newly allocated buffer.
+    Allocated(T),
+}
+
+impl<T> Mapped<T> {
+    pub fn is_in_place(&self) -> bool {
+        match *self {
+            Mapped::InPlace(_) => true,
+            Mapped::Allocated(_) => false,
+        }
+    }
+
+    pub fn into_inner(self) -> T {
+        match self {
+            Mapped::InPlace(t) | Mapped::Allocated(t) => t,
+        }
+    }
+}
+
+/// One of two possible results, e.g. of `partition_map_in_place`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Either<L, R> {
+    Left(L),
+    Right(R),
+}
+
+/// Why the storage of a container of `A`s can't hold the elements mapped from it, see
+/// `map_in_place_checked` and `checked_map_in_place`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum MapError {
+    /// `B` is larger than `A`.
+    SizeIncrease,
+    /// `B` and `A` have different alignments, so the buffer couldn't be freed with the layout
+    /// it was allocated with.
+    AlignmentMismatch,
+    /// `B` and `A` have different sizes, and the storage can only hold elements of one size.
+    SizeMismatch,
+    /// The size of the buffer in bytes isn't a multiple of the size of `B`. No longer returned,
+    /// as the buffer is shrunk to fit instead.
+    CapacityNotDivisible,
+    /// The size of the buffer in bytes overflows `usize`.
+    CapacityOverflow,
+    /// The buffer would hold more than `isize::MAX` `B`s, more than a `Vec` may have.
+    CapacityTooLarge,
+    /// `A` and `B` are stored next to other fields, e.g. the keys of a map, which would end up
+    /// at different offsets.
+    FieldOffsetMismatch,
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            MapError::SizeIncrease => "Size of A must be greater than or equal to size of B",
+            MapError::SizeMismatch => "Size of A must be equal to size of B",
+            MapError::AlignmentMismatch => "Alignment of A must be equal to alignment of B",
+            MapError::CapacityNotDivisible => {
+                "Size of the buffer must be a multiple of the size of B"
+            }
+            MapError::CapacityOverflow => "Size of the buffer overflows usize",
+            MapError::CapacityTooLarge => "Capacity of the buffer in Bs exceeds isize::MAX",
+            MapError::FieldOffsetMismatch => {
+                "Fields next to A and B must be stored at the same offsets"
+            }
+        })
+    }
+}
+
+impl Error for MapError {}
+
+/// The error returned by `map_in_place_checked`: the container couldn't be mapped in place,
+/// and is handed back untouched.
+#[derive(Debug)]
+pub struct IncompatibleLayout<T> {
+    container: T,
+    reason: MapError,
+    name: &'static str,
+    a: TypeLayout,
+    b: TypeLayout,
+    capacity: Option<usize>,
+}
+
+/// The name is looked up when the error is displayed, which keeps the error small enough to
+/// be returned cheaply.
+#[derive(Debug, Clone, Copy)]
+struct TypeLayout {
+    name: fn() -> &'static str,
+    size: usize,
+    align: usize,
+}
+
+impl TypeLayout {
+    #[inline]
+    fn of<T>() -> TypeLayout {
+        TypeLayout {
+            name: any::type_name::<T>,
+            size: mem::size_of::<T>(),
+            align: mem::align_of::<T>(),
+        }
+    }
+}
+
+impl<T> IncompatibleLayout<T> {
+    /// `name` is the operation that failed, e.g. `map_in_place(Vec<A>)`.
+    #[inline]
+    fn new<A, B>(container: T, name: &'static str, reason: MapError) -> Self {
+        IncompatibleLayout {
+            container,
+            reason,
+            name,
+            a: TypeLayout::of::<A>(),
+            b: TypeLayout::of::<B>(),
+            capacity: None,
+        }
+    }
+
+    /// Notes the capacity of the buffer, when it's what the failure depends on.
+    #[inline]
+    fn with_capacity(mut self, cap: usize) -> Self {
+        self.capacity = Some(cap);
+        self
+    }
+
+    /// Replaces the container, e.g. with the one it was taken out of.
+    #[inline]
+    fn map<U, G>(self, g: G) -> IncompatibleLayout<U>
+        where G: FnOnce(T) -> U
+    {
+        IncompatibleLayout {
+            container: g(self.container),
+            reason: self.reason,
+            name: self.name,
+            a: self.a,
+            b: self.b,
+            capacity: self.capacity,
+        }
+    }
+
+    /// Why the container couldn't be mapped.
+    #[inline]
+    pub fn reason(&self) -> MapError {
+        self.reason
+    }
+
+    /// Takes back the container.
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.container
+    }
+}
+
+impl<T> fmt::Display for IncompatibleLayout<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f,
+               "{}: {} (A = {}, {} bytes, align {}; B = {}, {} bytes, align {}",
+               self.name,
+               self.reason,
+               (self.a.name)(),
+               self.a.size,
+               self.a.align,
+               (self.b.name)(),
+               self.b.size,
+               self.b.align)?;
+
+        if let Some(cap) = self.capacity {
+            write!(f, "; capacity {}", cap)?;
+        }
+
+        f.write_str(")")
+    }
+}
+
+impl<T: fmt::Debug> Error for IncompatibleLayout<T> {}
+
+/// The error returned by `map_in_place_catch`: the mapping function panicked.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct MapPanicked<A> {
+    payload: Box<dyn any::Any + Send>,
+    index: usize,
+    rest: Vec<A>,
+}
+
+#[cfg(feature = "std")]
+impl<A> MapPanicked<A> {
+    /// The position of the element the mapping function panicked on. That element was moved
+    /// into the function, and is gone.
+    #[inline]
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// The payload of the panic, as `catch_unwind` would give it. Payloads are always `Send`,
+    /// as `panic_any` requires it.
+    #[inline]
+    pub fn payload(&self) -> &(dyn any::Any + Send) {
+        &*self.payload
+    }
+
+    /// The elements after the one the mapping function panicked on, in a new `Vec`.
+    #[inline]
+    pub fn rest(&self) -> &[A] {
+        &self.rest
+    }
+
+    /// Takes back the payload, index and remaining elements, e.g. to carry on with the panic
+    /// using `resume_unwind`.
+    #[inline]
+    pub fn into_parts(self) -> (Box<dyn any::Any + Send>, usize, Vec<A>) {
+        (self.payload, self.index, self.rest)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<A> fmt::Display for MapPanicked<A> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "map_in_place_catch: f panicked on element {}", self.index)?;
+
+        if let Some(msg) = self.payload.downcast_ref::<&str>() {
+            write!(f, ": {}", msg)
+        } else if let Some(msg) = self.payload.downcast_ref::<String>() {
+            write!(f, ": {}", msg)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<A: fmt::Debug> Error for MapPanicked<A> {}
+
+/// Where `map_in_place` panics, for every implementor, and so does every other operation whose
+/// preconditions on the layouts of `A` and `B` don't hold.
+#[inline]
+fn expect_layout<T, O>(r: Result<O, IncompatibleLayout<T>>) -> O {
+    match r {
+        Ok(o) => o,
+        Err(e) => layout_panic(e),
+    }
+}
+
+#[cold]
+#[inline(never)]
+fn layout_panic<T>(e: IncompatibleLayout<T>) -> ! {
+    panic!("{}", e)
+}
+
+/// `expect_layout` for a precondition on the layouts of `A` and `B` alone, with no container
+/// to hand back.
+#[inline]
+fn check_layout<A, B>(r: Result<(), MapError>, name: &'static str) {
+    expect_layout(r.map_err(|e| IncompatibleLayout::new::<A, B>((), name, e)))
+}
+
+/// Whether `A` and `B` can take each other's place in storage meant for one of them.
+#[inline]
+fn same_layout<A, B>() -> Result<(), MapError> {
+    if mem::size_of::<A>() != mem::size_of::<B>() {
+        return Err(MapError::SizeMismatch);
+    }
+
+    if mem::align_of::<A>() != mem::align_of::<B>() {
+        return Err(MapError::AlignmentMismatch);
+    }
+
+    Ok(())
+}
+
+impl<A, B> MapInPlace<A, B> for Box<A> {
+    type Output = Box<B>;
+
+    /// The allocation is reused when `A` and `B` have the same size and alignment. A smaller
+    /// or differently aligned `B` gets a new allocation, as it couldn't be freed with the
+    /// layout of `A`, and so does any `B` if `A` is zero-sized, as there is no allocation.
+    ///
+    /// Otherwise, a larger `B` is a compile error:
+    ///
+    /// ```compile_fail
+    /// use map_in_place::MapInPlace;
+    ///
+    /// let _ = Box::new(0u32).map_in_place(|x| x as u64);
+    /// ```
+    #[inline]
+    fn map_in_place<F>(self, f: F) -> Self::Output
+        where F: FnMut(A) -> B
+    {
+        let () = NoLarger::<A, B>::OK;
+
+        expect_layout(self.map_in_place_checked(f))
+    }
+
+    /// Fails if `B` is larger than `A`, unless `A` is zero-sized.
+    #[inline]
+    fn map_in_place_checked<F>(self, mut f: F) -> Result<Self::Output, IncompatibleLayout<Self>>
+        where F: FnMut(A) -> B
+    {
+        let a_size = mem::size_of::<A>();
+        let b_size = mem::size_of::<B>();
+
+        if a_size != 0 && a_size < b_size {
+            return Err(IncompatibleLayout::new::<A, B>(self,
+                                                       "map_in_place(Box<A>)",
+                                                       MapError::SizeIncrease));
+        }
+
+        if a_size != b_size || mem::align_of::<A>() != mem::align_of::<B>() {
+            // doesn't preserve address invariant
+            return Ok(Box::new(f(*self)));
+        }
+
+        let ptr = Box::into_raw(self);
+
+        unsafe {
+            let dropper = BoxDropper { ptr };
+            let result = f(ptr::read(ptr));
+            mem::forget(dropper);
+
+            let ptr = ptr as *mut B;
+
+            ptr::write(ptr, result);
+            Ok(Box::from_raw(ptr))
+        }
+    }
+}
+
+/// Frees the allocation of a `Box<A>` whose value has been moved out.
+struct BoxDropper<A> {
+    ptr: *mut A,
+}
+
+impl<A> Drop for BoxDropper<A> {
+    #[inline]
+    fn drop(&mut self) {
+        // panicked; the A was already moved out, so only the allocation is left
+        if mem::size_of::<A>() != 0 {
+            unsafe {
+                dealloc(self.ptr as *mut u8, Layout::new::<A>());
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "allocator-api"))]
+impl<A, B> MapInPlace<A, B> for Vec<A> {
+    type Output = Vec<B>;
+
+    /// `f` is called on the elements in order, from the first to the last.
+    ///
+    /// If `A` and `B` have different alignments, the buffer couldn't be freed as a `Vec<B>`,
+    /// so the results are collected into a new allocation instead.
+    ///
+    /// If `B` is zero-sized, its elements take up no memory at all: the buffer is freed once
+    /// the `A`s have been consumed, and the result never allocates, so unless `A` is also
+    /// zero-sized its pointer differs from that of `self`. That includes uninhabited types such
+    /// as `Infallible`: an empty `self` maps to an empty `Vec<B>` without `f` being called, and
+    /// otherwise `f` can only panic, with the usual cleanup. If `A` is zero-sized and `B` isn't,
+    /// there's no buffer to reuse, so the results are collected into a new allocation.
+    ///
+    /// A `B` larger than `A` is a compile error, unless one of them is zero-sized:
+    ///
+    /// ```compile_fail
+    /// use map_in_place::MapInPlace;
+    ///
+    /// let _ = vec![0u8; 4].map_in_place(|_| 0u64);
+    /// ```
+    ///
+    /// Use `map_in_place_checked` or `map_in_place_or_alloc` where that can't be known.
+    ///
+    /// There's no impl for a partially consumed `vec::IntoIter<A>`, as it doesn't give access
+    /// to the start or the capacity of its buffer to build a `Vec<B>` around. Collecting it into
+    /// a `Vec<A>` first may reuse the buffer, but std doesn't promise to.
+    #[inline]
+    fn map_in_place<F>(self, f: F) -> Self::Output
+        where F: FnMut(A) -> B
+    {
+        let () = NoLarger::<A, B>::OK;
+
+        expect_layout(self.map_in_place_checked(f))
+    }
+
+    /// Fails if `B` is larger than `A` and has the same alignment, or if the buffer is too large
+    /// to be a `Vec<B>` (`MapError::CapacityOverflow` or `CapacityTooLarge`). Otherwise, where
+    /// the buffer can't be reused because one of them is zero-sized or they have different
+    /// alignments, the results are collected into a new allocation, as in `map_in_place`.
+    ///
+    /// With the `small-code` feature, `f` is called through a trait object, as in
+    /// `map_in_place_dyn`.
+    #[inline]
+    fn map_in_place_checked<F>(self, mut f: F) -> Result<Self::Output, IncompatibleLayout<Self>>
+        where F: FnMut(A) -> B
+    {
+        if cfg!(feature = "small-code") {
+            map_vec::<A, B, &mut dyn FnMut(A) -> B>(self, &mut f)
+        } else {
+            map_vec(self, f)
+        }
+    }
+}
+
+impl<A, B> MapInPlaceDyn<A, B> for Vec<A> {
+    type Output = Vec<B>;
+
+    #[inline(never)]
+    fn map_in_place_dyn(self, f: &mut dyn FnMut(A) -> B) -> Self::Output {
+        let () = NoLarger::<A, B>::OK;
+
+        expect_layout(map_vec(self, f))
+    }
+}
+
+#[inline]
+fn map_vec<A, B, F>(v: Vec<A>, f: F) -> Result<Vec<B>, IncompatibleLayout<Vec<A>>>
+    where F: FnMut(A) -> B
+{
+    let len = v.len();
+
+    if needs_new_buffer::<A, B>() {
+        Ok(collect_new(v.into_iter().map(f)))
+    } else {
+        let cap = v.capacity();
+
+        if let Err(e) = checked_capacity::<A, B>(cap) {
+            return Err(IncompatibleLayout::new::<A, B>(v, "map_in_place(Vec<A>)", e));
+        }
+
+        unsafe {
+            let ptr_b = map_elements(v, f);
+            Ok(mapped_vec::<A, B>(ptr_b, len, cap))
+        }
+    }
+}
+
+impl<A, B> MapWithMappedPrefix<A, B> for Vec<A> {
+    type Output = Vec<B>;
+
+    /// The results are borrowed from the front of the buffer of `self` as they're written.
+    ///
+    /// As in `map_in_place`, the results are collected into a new allocation if `A` and `B`
+    /// have different alignments, or if `A` is zero-sized and `B` isn't. Panics if `B` is larger
+    /// than `A`.
+    #[inline]
+    fn map_with_mapped_prefix<F>(self, mut f: F) -> Self::Output
+        where F: FnMut(&[B], A) -> B
+    {
+        if needs_new_buffer::<A, B>() {
+            return collect_new_with(self.into_iter(), |prefix, a| Some(f(prefix, a)));
+        }
+
+        let len = self.len();
+
+        let cap = self.capacity();
+        check_capacity::<A, B>(cap, "map_with_mapped_prefix(Vec<A>)");
+
+        unsafe {
+            let ptr_b = map_elements_at(self, |ptr, i, a| {
+                // the `B`s before `i` have been written, and `B`s are no larger than `A`s, so
+                // they end before the `A` being mapped
+                f(::core::slice::from_raw_parts(ptr as *const B, i), a)
+            });
+
+            mapped_vec::<A, B>(ptr_b, len, cap)
+        }
+    }
+}
+
+impl<A, B> MapWithNextInPlace<A, B> for Vec<A> {
+    type Output = Vec<B>;
+
+    /// The next element is borrowed from the buffer of `self`, where it hasn't been read yet.
+    ///
+    /// As in `map_in_place`, the results are collected into a new allocation if `A` and `B`
+    /// have different alignments, or if `A` is zero-sized and `B` isn't. Panics if `B` is larger
+    /// than `A`.
+    #[inline]
+    fn map_with_next_in_place<F>(self, mut f: F) -> Self::Output
+        where F: FnMut(A, Option<&A>) -> B
+    {
+        if needs_new_buffer::<A, B>() {
+            let mut iter = self.into_iter().peekable();
+
+            return collect_new(::core::iter::from_fn(|| {
+                let e = iter.next()?;
+                Some(f(e, iter.peek()))
+            }));
+        }
+
+        let len = self.len();
+
+        let cap = self.capacity();
+        check_capacity::<A, B>(cap, "map_with_next_in_place(Vec<A>)");
+
+        unsafe {
+            let ptr_b = map_elements_at(self, |ptr, i, a| {
+                // the `B` for element `i` is only written once `f` returns, and `B`s are no
+                // larger than `A`s, so it won't reach element `i + 1` either way
+                let next = if i + 1 < len { Some(&*ptr.add(i + 1)) } else { None };
+                f(a, next)
+            });
+
+            mapped_vec::<A, B>(ptr_b, len, cap)
+        }
+    }
+}
+
+impl<A, B> MapInPlaceRev<A, B> for Vec<A> {
+    type Output = Vec<B>;
+
+    /// `f` is called on the elements in reverse order, from the last to the first.
+    ///
+    /// The buffer is only reused when `A` and `B` have the same size and alignment. Otherwise
+    /// the results are collected into a new allocation, still calling `f` from the back.
+    #[inline]
+    fn map_in_place_rev<F>(self, mut f: F) -> Self::Output
+        where F: FnMut(A) -> B
+    {
+        let len = self.len();
+
+        // a smaller `B` would overwrite elements that haven't been read yet
+        if mem::size_of::<A>() != mem::size_of::<B>() || needs_new_buffer::<A, B>() {
+            let mut v = collect_new(self.into_iter().rev().map(f));
+            v.reverse();
+
+            return v;
+        }
+
+        let cap = self.capacity();
+
+        let mut dropper = RevDropper::<A, B>::new(self);
+
+        let ptr_a = dropper.ptr;
+        let ptr_b = ptr_a as *mut B;
+
+        unsafe {
+            for i in (0..len).rev() {
+                let v = ptr::read(ptr_a.add(i));
+                ptr::write(ptr_b.add(i), f(v));
+
+                dropper.idx += 1;
+            }
+
+            mem::forget(dropper);
+
+            Vec::from_raw_parts(ptr_b, len, cap)
+        }
+    }
+}
+
+impl<A, B> MapInPlaceGrow<A, B> for Vec<A> {
+    type Output = Vec<B>;
+
+    /// `self` is handed back when the spare capacity isn't enough to hold `len` `B`s, or when
+    /// `A` and `B` have different alignments (e.g. `u16` and `u32`), as the buffer couldn't
+    /// then be freed with the layout it was allocated with. A zero-sized `B` is always accepted,
+    /// as in `map_in_place`.
+    ///
+    /// When `B` is larger than `A`, `f` is called on the elements in reverse order, from the
+    /// last to the first, so that no `B` overwrites an `A` that hasn't been read yet. The
+    /// buffer is shrunk to fit a whole number of `B`s if needed.
+    #[inline]
+    fn map_in_place_grow<F>(self, mut f: F) -> Result<Self::Output, Self>
+        where F: FnMut(A) -> B
+    {
+        let a_size = mem::size_of::<A>();
+        let b_size = mem::size_of::<B>();
+        let len = self.len();
+        let cap = self.capacity();
+
+        if b_size == 0 {
+            return Ok(expect_layout(self.map_in_place_checked(f)));
+        }
+
+        if a_size == 0 || mem::align_of::<A>() != mem::align_of::<B>() {
+            return Err(self);
+        }
+
+        match len.checked_mul(b_size) {
+            Some(n_bytes) if n_bytes <= cap * a_size => {}
+            _ => return Err(self),
+        }
+
+        if b_size <= a_size {
+            unsafe {
+                let (ptr_b, cap) = shrink_allocation::<A, B>(map_elements(self, f), cap);
+                return Ok(Vec::from_raw_parts(ptr_b, len, cap));
+            }
+        }
+
+        let mut dropper = RevDropper::<A, B>::new(self);
+
+        let ptr_a = dropper.ptr;
+        let ptr_b = ptr_a as *mut B;
+
+        unsafe {
+            // the `B` at `i` starts at or after the end of the `A` at `i - 1`, so writing it
+            // only overwrites `A`s that have already been read
+            for i in (0..len).rev() {
+                let v = ptr::read(ptr_a.add(i));
+                ptr::write(ptr_b.add(i), f(v));
+
+                dropper.idx += 1;
+            }
+
+            mem::forget(dropper);
+
+            let (ptr_b, cap) = shrink_allocation::<A, B>(ptr_b, cap);
+            Ok(Vec::from_raw_parts(ptr_b, len, cap))
+        }
+    }
+}
+
+impl<A, B> MapInPlaceShrink<A, B> for Vec<A> {
+    type Output = Vec<B>;
+
+    /// The global allocator can't be asked to shrink a block without moving it, and moving it
+    /// is an allocate-and-copy, as in `Vec::shrink_to_fit`. So the buffer of an empty result
+    /// is freed, and otherwise the result keeps the capacity `map_in_place` gives it.
+    #[inline]
+    fn map_in_place_shrink<F>(self, f: F) -> Self::Output
+        where F: FnMut(A) -> B
+    {
+        let v = self.map_in_place(f);
+
+        if v.is_empty() {
+            // freeing the buffer doesn't copy anything
+            return Vec::new();
+        }
+
+        v
+    }
+}
+
+impl<A, B> MapInPlaceOrAlloc<A, B> for Vec<A> {
+    type Output = Vec<B>;
+
+    /// The buffer is reused (and shrunk to fit a whole number of `B`s if needed) when `B` is
+    /// no larger than `A`, has the same alignment, and isn't zero-sized. Otherwise the `B`s are
+    /// collected into a new `Vec`, which can be told apart by its pointer.
+    #[inline]
+    fn map_in_place_or_alloc<F>(self, f: F) -> Self::Output
+        where F: FnMut(A) -> B
+    {
+        let len = self.len();
+        let cap = self.capacity();
+
+        if !can_reuse_buffer::<A, B>() {
+            return self.into_iter().map(f).collect();
+        }
+
+        unsafe {
+            let (ptr_b, cap) = shrink_allocation::<A, B>(map_elements(self, f), cap);
+            Vec::from_raw_parts(ptr_b, len, cap)
+        }
+    }
+}
+
+impl<A, B> CheckedMapInPlace<A, B> for Vec<A> {
+    type Output = Vec<B>;
+
+    /// `map_in_place_checked` with the error split into its parts: `B` is accepted, or not,
+    /// exactly as there.
+    #[inline]
+    fn checked_map_in_place<F>(self, f: F) -> Result<Self::Output, (Self, MapError)>
+        where F: FnMut(A) -> B
+    {
+        self.map_in_place_checked(f).map_err(|e| {
+            let reason = e.reason();
+            (e.into_inner(), reason)
+        })
+    }
+}
+
+impl<A, B> TryMapInPlace<A, B> for Vec<A> {
+    type Output = Vec<B>;
+
+    /// On the first `Err`, the `B`s produced so far and the remaining `A`s are dropped and the
+    /// allocation is freed before the error is returned.
+    #[inline]
+    fn try_map_in_place<F, E>(self, f: F) -> Result<Self::Output, E>
+        where F: FnMut(A) -> Result<B, E>
+    {
+        let len = self.len();
+
+        if mem::size_of::<B>() == 0 {
+            try_collect_new(self.into_iter().map(f))
+        } else {
+            let cap = self.capacity();
+            check_capacity::<A, B>(cap, "try_map_in_place(Vec<A>)");
+
+            unsafe {
+                let ptr_b = try_map_elements(self, f)?;
+                Ok(mapped_vec::<A, B>(ptr_b, len, cap))
+            }
+        }
+    }
+
+    /// One allocation can't back two `Vec`s: on error the mapped prefix keeps the original
+    /// buffer (and its capacity), while the unprocessed suffix is moved into a new one.
+    ///
+    /// If `f` panics, everything is dropped as in `map_in_place`.
+    #[inline]
+    fn try_map_in_place_partial<F, E>(self,
+                                      mut f: F)
+                                      -> Result<Self::Output, (Self::Output, Self, E)>
+        where F: FnMut(A) -> Result<B, E>
+    {
+        let len = self.len();
+
+        if mem::size_of::<B>() == 0 {
+            let mut iter = self.into_iter();
+            let mut error = None;
+
+            let mapped = collect_new(iter.by_ref().map_while(|a| match f(a) {
+                Ok(b) => Some(b),
+                Err(e) => {
+                    error = Some(e);
+                    None
+                }
+            }));
+
+            return match error {
+                Some(e) => Err((mapped, iter.collect(), e)),
+                None => Ok(mapped),
+            };
+        }
+
+        let cap = self.capacity();
+        check_capacity::<A, B>(cap, "try_map_in_place_partial(Vec<A>)");
+
+        let mut dropper = VecDropper::<A, B>::new(self);
+
+        let ptr_a = dropper.ptr;
+        let ptr_b = ptr_a as *mut B;
+
+        unsafe {
+            for i in 0..len {
+                let v = ptr::read(ptr_a.add(i));
+
+                match f(v) {
+                    Ok(b) => ptr::write(ptr_b.add(i), b),
+                    Err(e) => {
+                        let n_rest = len - i - 1;
+                        let mut rest = Vec::with_capacity(n_rest);
+
+                        ptr::copy_nonoverlapping(ptr_a.add(i + 1), rest.as_mut_ptr(), n_rest);
+                        rest.set_len(n_rest);
+
+                        mem::forget(dropper);
+
+                        return Err((mapped_vec::<A, B>(ptr_b, i, cap), rest, e));
+                    }
+                }
+
+                dropper.idx += 1;
+            }
+
+            mem::forget(dropper);
+
+            Ok(mapped_vec::<A, B>(ptr_b, len, cap))
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<A, B> MapInPlaceCatch<A, B> for Vec<A> {
+    type Output = Vec<B>;
+
+    /// Each call to `f` is wrapped in `catch_unwind`, and the buffer is mapped as in
+    /// `try_map_in_place_partial`, so the same rules apply to `A` and `B`. The elements that
+    /// weren't reached are moved into a new `Vec` before the buffer is freed.
+    #[inline]
+    fn map_in_place_catch<F>(self, mut f: F) -> Result<Self::Output, MapPanicked<A>>
+        where F: FnMut(A) -> B
+    {
+        let caught = self.try_map_in_place_partial(|a| {
+            panic::catch_unwind(AssertUnwindSafe(|| f(a)))
+        });
+
+        match caught {
+            Ok(v) => Ok(v),
+            Err((mapped, rest, payload)) => {
+                let index = mapped.len();
+                drop(mapped);
+
+                Err(MapPanicked { payload, index, rest })
+            }
+        }
+    }
+}
+
+impl<A, B> TryMapInPlaceWithRollback<A, B> for Vec<A> {
+    type Output = Vec<B>;
+
+    /// `undo` is called on the mapped elements in reverse order, from the last to the first,
+    /// and `self` is handed back in its original buffer.
+    ///
+    /// If `f` panics, everything is dropped as in `map_in_place`. If `undo` panics, the
+    /// elements that were still mapped and the ones already restored are dropped, and the
+    /// allocation is freed.
+    ///
+    /// Panics unless `A` and `B` have the same size and alignment, as the elements are mapped
+    /// both ways in the same slots.
+    #[inline]
+    fn try_map_in_place_with_rollback<F, G, E>(self,
+                                               mut f: F,
+                                               mut undo: G)
+                                               -> Result<Self::Output, (Self, E)>
+        where F: FnMut(A) -> Result<B, (A, E)>,
+              G: FnMut(B) -> A
+    {
+        check_layout::<A, B>(same_layout::<A, B>(), "try_map_in_place_with_rollback(Vec<A>)");
+
+        let len = self.len();
+        let cap = self.capacity();
+
+        let mut dropper = VecDropper::<A, B>::new(self);
+
+        let ptr_a = dropper.ptr;
+        let ptr_b = ptr_a as *mut B;
+
+        unsafe {
+            for i in 0..len {
+                let v = ptr::read(ptr_a.add(i));
+
+                match f(v) {
+                    Ok(b) => ptr::write(ptr_b.add(i), b),
+                    Err((a, e)) => {
+                        ptr::write(ptr_a.add(i), a);
+                        mem::forget(dropper);
+
+                        // the `B`s before `i` are mapped back from the last to the first,
+                        // which is a reverse pass from `B` to `A` over the first `i` slots
+                        let mut dropper = RevDropper {
+                            idx: len - i,
+                            ptr: ptr_b,
+                            len,
+                            cap,
+                            _marker: PhantomData::<A>,
+                        };
+
+                        for j in (0..i).rev() {
+                            let v = ptr::read(ptr_b.add(j));
+                            ptr::write(ptr_a.add(j), undo(v));
+
+                            dropper.idx += 1;
+                        }
+
+                        mem::forget(dropper);
+
+                        return Err((Vec::from_raw_parts(ptr_a, len, cap), e));
+                    }
+                }
+
+                dropper.idx += 1;
+            }
+
+            mem::forget(dropper);
+
+            Ok(Vec::from_raw_parts(ptr_b, len, cap))
+        }
+    }
+}
+
+impl<A, B> MapInPlaceCollectErrors<A, B> for Vec<A> {
+    type Output = Vec<B>;
+
+    /// Nothing is allocated until the first error. From then on, the `B`s produced so far are
+    /// dropped, and so is every `B` produced after.
+    ///
+    /// If `f` panics, everything is dropped as in `map_in_place`.
+    #[inline]
+    fn map_in_place_collect_errors<F, E>(self, mut f: F) -> Result<Self::Output, Vec<(usize, E)>>
+        where F: FnMut(A) -> Result<B, E>
+    {
+        let len = self.len();
+
+        if mem::size_of::<B>() == 0 {
+            let mut iter = self.into_iter().enumerate();
+            let mut errors = Vec::new();
+
+            let mapped = collect_new(iter.by_ref().map_while(|(i, a)| match f(a) {
+                Ok(b) => Some(b),
+                Err(e) => {
+                    errors.push((i, e));
+                    None
+                }
+            }));
+
+            if errors.is_empty() {
+                return Ok(mapped);
+            }
+
+            drop(mapped);
+
+            for (i, a) in iter {
+                if let Err(e) = f(a) {
+                    errors.push((i, e));
+                }
+            }
+
+            return Err(errors);
+        }
+
+        let cap = self.capacity();
+        check_capacity::<A, B>(cap, "map_in_place_collect_errors(Vec<A>)");
+
+        let mut dropper = VecDropper::<A, B>::new(self);
+
+        let ptr_a = dropper.ptr;
+        let ptr_b = ptr_a as *mut B;
+
+        unsafe {
+            for i in 0..len {
+                let v = ptr::read(ptr_a.add(i));
+
+                match f(v) {
+                    Ok(b) => ptr::write(ptr_b.add(i), b),
+                    Err(e) => {
+                        let mut errors = vec![(i, e)];
+
+                        mem::forget(dropper);
+
+                        // from here on only the `A`s not yet consumed are left to drop
+                        let mut dropper = VecDropper {
+                            idx: i,
+                            ptr: ptr_a,
+                            len,
+                            cap,
+                            _marker: PhantomData::<()>,
+                        };
+
+                        ptr::drop_in_place(ptr::slice_from_raw_parts_mut(ptr_b, i));
+
+                        for j in (i + 1)..len {
+                            dropper.idx = j;
+
+                            if let Err(e) = f(ptr::read(ptr_a.add(j))) {
+                                errors.push((j, e));
+                            }
+                        }
+
+                        // drops nothing more, and frees the allocation
+                        drop(dropper);
+
+                        return Err(errors);
+                    }
+                }
+
+                dropper.idx += 1;
+            }
+
+            mem::forget(dropper);
+
+            Ok(mapped_vec::<A, B>(ptr_b, len, cap))
+        }
+    }
+}
+
+impl<A, B> MapInPlaceUntil<A, B> for Vec<A> {
+    type Output = Vec<B>;
+
+    /// Works like `try_map_in_place_partial`: the mapped prefix keeps the original buffer,
+    /// and the unprocessed suffix is moved into a new one.
+    #[inline]
+    fn map_in_place_until<C, F>(self, mut f: F) -> (Self::Output, Option<(C, Self)>)
+        where F: FnMut(A) -> ControlFlow<C, B>
+    {
+        let mapped = self.try_map_in_place_partial(|a| match f(a) {
+            ControlFlow::Continue(b) => Ok(b),
+            ControlFlow::Break(c) => Err(c),
+        });
+
+        match mapped {
+            Ok(mapped) => (mapped, None),
+            Err((mapped, rest, c)) => (mapped, Some((c, rest))),
+        }
+    }
+}
+
+impl<A> RecycleInPlace<A> for Vec<A> {
+    /// The buffer is kept (and shrunk to fit a whole number of `B`s if needed) when `A` and
+    /// `B` have the same alignment; `B` may be larger than `A`. Otherwise it is freed, and an
+    /// empty `Vec` without an allocation is returned, which can be told apart by its pointer.
+    #[inline]
+    fn recycle_in_place<B>(mut self) -> Vec<B> {
+        self.clear();
+
+        if needs_new_buffer::<A, B>() {
+            return Vec::new();
+        }
+
+        let mut v = mem::ManuallyDrop::new(self);
+
+        unsafe {
+            let (ptr, cap) = shrink_allocation::<A, B>(v.as_mut_ptr() as *mut B, v.capacity());
+
+            Vec::from_raw_parts(ptr, 0, cap)
+        }
+    }
+}
+
+impl<A> ConvertInPlace<A> for Vec<A> {
+    /// The buffer is shrunk to fit a whole number of `B`s if needed.
+    #[inline]
+    fn convert_in_place<B: From<A>>(self) -> Vec<B> {
+        let () = FitsIn::<A, B>::OK;
+
+        let len = self.len();
+        let cap = self.capacity();
+
+        if mem::size_of::<B>() == 0 {
+            return self.map_in_place(B::from);
+        }
+
+        unsafe {
+            let (ptr_b, cap) = shrink_allocation::<A, B>(map_elements(self, B::from), cap);
+            Vec::from_raw_parts(ptr_b, len, cap)
+        }
+    }
+}
+
+impl<A> TryConvertInPlace<A> for Vec<A> {
+    /// On the first error, the `B`s converted so far and the remaining `A`s are dropped and
+    /// the allocation is freed before the error is returned.
+    #[inline]
+    fn try_convert_in_place<B: TryFrom<A>>(self) -> Result<Vec<B>, B::Error> {
+        let () = FitsIn::<A, B>::OK;
+
+        let len = self.len();
+        let cap = self.capacity();
+
+        if mem::size_of::<B>() == 0 {
+            return self.try_map_in_place(B::try_from);
+        }
+
+        unsafe {
+            let ptr_b = try_map_elements(self, B::try_from)?;
+            let (ptr_b, cap) = shrink_allocation::<A, B>(ptr_b, cap);
+            Ok(Vec::from_raw_parts(ptr_b, len, cap))
+        }
+    }
+}
+
+/// Fails to compile (when `OK` is used) unless the buffer of a `Vec<A>` can hold `B`s: `B` is
+/// zero-sized, or no larger than `A` with the same alignment.
+struct FitsIn<A, B>(PhantomData<(A, B)>);
+
+impl<A, B> FitsIn<A, B> {
+    const OK: () = assert!(mem::size_of::<B>() == 0 ||
+                           (mem::size_of::<B>() <= mem::size_of::<A>() &&
+                            mem::align_of::<B>() == mem::align_of::<A>()),
+                           "Size of A must be greater than or equal to size of B, and \
+                            alignment of A must be equal to alignment of B");
+}
+
+/// Fails to compile (when `OK` is used) if `B` is larger than `A` and neither is zero-sized,
+/// in which case `map_in_place` on a `Vec<A>` would always panic.
+///
+/// The message can't name the types, but the compiler notes which instance of the function
+/// using it was being built.
+struct NoLarger<A, B>(PhantomData<(A, B)>);
+
+impl<A, B> NoLarger<A, B> {
+    const OK: () = assert!(mem::size_of::<A>() == 0 || mem::size_of::<B>() == 0 ||
+                           mem::size_of::<B>() <= mem::size_of::<A>(),
+                           "map_in_place: Size of A must be greater than or equal to size of B");
+}
+
+/// Whether the buffer of a `Vec<A>` can hold `B`s, once shrunk with `shrink_allocation`: `B`
+/// is no larger than `A`, has the same alignment, and isn't zero-sized.
+#[inline]
+fn can_reuse_buffer<A, B>() -> bool {
+    let b_size = mem::size_of::<B>();
+
+    b_size != 0 && b_size <= mem::size_of::<A>() && mem::align_of::<A>() == mem::align_of::<B>()
+}
+
+/// Whether the `B`s mapped from a `Vec<A>` go in a new `Vec` rather than in its buffer, see
+/// `collect_new`: `B` takes no buffer, `A` has none, or freeing it as a `Vec<B>` would use the
+/// wrong alignment.
+#[inline]
+fn needs_new_buffer<A, B>() -> bool {
+    mem::size_of::<B>() == 0 || mem::size_of::<A>() == 0 ||
+    mem::align_of::<A>() != mem::align_of::<B>()
+}
+
+/// Checks that the buffer of a `Vec<A>` with capacity `cap` can hold `B`s, once turned into
+/// a `Vec<B>` with `mapped_vec`.
+///
+/// Panics through `expect_layout` if `B` is larger than `A`, or if they have different
+/// alignments. Requires `size_of::<B>()` to be nonzero.
+#[inline]
+fn check_capacity<A, B>(cap: usize, name: &'static str) {
+    expect_layout(checked_capacity::<A, B>(cap)
+        .map_err(|e| IncompatibleLayout::new::<A, B>((), name, e).with_capacity(cap)))
+}
+
+/// Like `check_capacity`, but returns the reason instead of panicking.
+///
+/// All the arithmetic `mapped_vec` does on `cap` is checked here, so it's done before the
+/// elements are touched.
+#[inline]
+fn checked_capacity<A, B>(cap: usize) -> Result<(), MapError> {
+    checked_capacity_within::<A, B>(cap, isize::MAX as usize)
+}
+
+/// `checked_capacity` with `max` as the largest capacity a `Vec<B>` may have, so that the
+/// limit can be tested without allocating that much.
+#[inline]
+fn checked_capacity_within<A, B>(cap: usize, max: usize) -> Result<(), MapError> {
+    let a_size = mem::size_of::<A>();
+    let b_size = mem::size_of::<B>();
+
+    if a_size < b_size {
+        return Err(MapError::SizeIncrease);
+    }
+
+    // the buffer would be freed with the alignment of `B`
+    if mem::align_of::<A>() != mem::align_of::<B>() {
+        return Err(MapError::AlignmentMismatch);
+    }
+
+    let n_bytes = cap.checked_mul(a_size).ok_or(MapError::CapacityOverflow)?;
+
+    // `len` is no larger than the capacity in `B`s, as `B` is no larger than `A`
+    if b_size != 0 && n_bytes / b_size > max {
+        return Err(MapError::CapacityTooLarge);
+    }
+
+    Ok(())
+}
+
+/// Turns the buffer of a `Vec<A>` with capacity `cap`, holding `len` `B`s, into a `Vec<B>`.
+/// When the size of the buffer isn't a multiple of the size of `B`, the remainder is given
+/// back with `shrink_allocation`.
+///
+/// Requires `check_capacity::<A, B>(cap, ..)` to have passed.
+#[inline]
+unsafe fn mapped_vec<A, B>(ptr: *mut B, len: usize, cap: usize) -> Vec<B> {
+    let (ptr, cap) = shrink_allocation::<A, B>(ptr, cap);
+    Vec::from_raw_parts(ptr, len, cap)
+}
+
+impl<A, B> MapInPlace<A, B> for Box<[A]> {
+    type Output = Box<[B]>;
+
+    /// The allocation of a boxed slice must always be exactly `len * size_of::<T>()` bytes,
+    /// so when `B` is smaller than `A` the buffer is shrunk with `realloc` after mapping.
+    /// This is the only case where the allocator may move the data.
+    ///
+    /// A `B` larger than `A` is a compile error, unless one of them is zero-sized:
+    ///
+    /// ```compile_fail
+    /// use map_in_place::MapInPlace;
+    ///
+    /// let _ = vec![0u8; 4].into_boxed_slice().map_in_place(|_| 0u64);
+    /// ```
+    ///
+    /// Panics if `A` and `B` differ in alignment, as `map_in_place_checked` fails.
+    #[inline]
+    fn map_in_place<F>(self, f: F) -> Self::Output
+        where F: FnMut(A) -> B
+    {
+        let () = NoLarger::<A, B>::OK;
+
+        expect_layout(self.map_in_place_checked(f))
+    }
+
+    /// Fails if `B` is larger than `A`, or if `A` and `B` are not zero-sized and differ in
+    /// alignment, since the buffer couldn't then be freed with the layout it was allocated
+    /// with. If one of them is zero-sized there's no buffer to reuse, and the results are
+    /// collected into a new boxed slice.
+    #[inline]
+    fn map_in_place_checked<F>(self, f: F) -> Result<Self::Output, IncompatibleLayout<Self>>
+        where F: FnMut(A) -> B
+    {
+        let a_size = mem::size_of::<A>();
+        let b_size = mem::size_of::<B>();
+        let len = self.len();
+
+        if a_size == 0 || b_size == 0 {
+            Ok(expect_layout(self.into_vec().map_in_place_checked(f)).into_boxed_slice())
+        } else if len == 0 {
+            Ok(Vec::new().into_boxed_slice())
+        } else {
+            let reason = if a_size < b_size {
+                Some(MapError::SizeIncrease)
+            } else if mem::align_of::<A>() != mem::align_of::<B>() {
+                Some(MapError::AlignmentMismatch)
+            } else {
+                None
+            };
+
+            if let Some(reason) = reason {
+                return Err(IncompatibleLayout::new::<A, B>(self, "map_in_place(Box<[A]>)", reason));
+            }
+
+            unsafe {
+                let mut ptr_b = map_elements(self.into_vec(), f);
+
+                if a_size != b_size {
+                    let old_layout = Layout::array::<A>(len).unwrap();
+                    let new_size = len * b_size;
+
+                    ptr_b = realloc(ptr_b as *mut u8, old_layout, new_size) as *mut B;
+
+                    if ptr_b.is_null() {
+                        handle_alloc_error(Layout::array::<B>(len).unwrap());
+                    }
+                }
+
+                Ok(Box::from_raw(ptr::slice_from_raw_parts_mut(ptr_b, len)))
+            }
+        }
+    }
+}
+
+impl<'a, A, B> MapInPlace<A, B> for Cow<'a, [A]>
+    where A: Clone
+{
+    type Output = Mapped<Vec<B>>;
+
+    /// An owned `Vec` is mapped in place exactly like any other `Vec`. Borrowed data is cloned
+    /// element by element into a new `Vec`.
+    #[inline]
+    fn map_in_place_checked<F>(self, f: F) -> Result<Self::Output, IncompatibleLayout<Self>>
+        where F: FnMut(A) -> B
+    {
+        match self {
+            Cow::Owned(v) => {
+                v.map_in_place_checked(f).map(Mapped::InPlace).map_err(|e| e.map(Cow::Owned))
+            }
+            Cow::Borrowed(s) => Ok(Mapped::Allocated(s.iter().cloned().map(f).collect())),
+        }
+    }
+}
+
+/// Collects the results of `f` on the items of `iter` into a new `Vec`, leaving out the
+/// `None`s. For when the buffer the items come from can't be reused: `B` is zero-sized, or `A`
+/// is, or the buffer couldn't be freed with the alignment of `B`. Unless `A` and `B` are both
+/// zero-sized, the result then isn't where the elements were.
+///
+/// `f` is given the results so far. Zero-sized `B`s are only counted, so that the result never
+/// allocates.
+///
+/// On the first `Err`, or if `f` panics, the results so far are dropped before `iter`, and
+/// with it the items it hasn't yielded, as when mapping in place.
+#[inline]
+fn try_collect_new_with<I, B, E, F>(mut iter: I, mut f: F) -> Result<Vec<B>, E>
+    where I: Iterator,
+          F: FnMut(&[B], I::Item) -> Result<Option<B>, E>
+{
+    let zero_sized = mem::size_of::<B>() == 0;
+    let mut out = Vec::with_capacity(if zero_sized { 0 } else { iter.size_hint().0 });
+
+    for item in iter.by_ref() {
+        if let Some(b) = f(&out, item)? {
+            if zero_sized {
+                // a zero-sized `B` is whole once it's counted
+                mem::forget(b);
+
+                unsafe {
+                    out.set_len(out.len() + 1);
+                }
+            } else {
+                out.push(b);
+            }
+        }
+    }
+
+    Ok(out)
+}
+
+/// `try_collect_new_with` for an iterator of results.
+#[inline]
+fn try_collect_new<I, B, E>(iter: I) -> Result<Vec<B>, E>
+    where I: Iterator<Item = Result<B, E>>
+{
+    try_collect_new_with(iter, |_, r| r.map(Some))
+}
+
+/// `try_collect_new_with` for an `f` that can't fail.
+#[inline]
+fn collect_new_with<I, B, F>(iter: I, mut f: F) -> Vec<B>
+    where I: Iterator,
+          F: FnMut(&[B], I::Item) -> Option<B>
+{
+    match try_collect_new_with(iter, |out, item| Ok::<_, Infallible>(f(out, item))) {
+        Ok(v) => v,
+        Err(e) => match e {},
+    }
+}
+
+/// `try_collect_new_with` for an iterator of the results themselves.
+#[inline]
+fn collect_new<I: Iterator>(iter: I) -> Vec<I::Item> {
+    collect_new_with(iter, |_, b| Some(b))
+}
+
+/// Maps every element of `v` into the slot it occupies, returning the buffer retyped as `B`.
+/// The caller takes ownership of the `B`s and of the allocation, and is responsible for
+/// reconstituting an owner for them with a layout matching that of the original `Vec<A>`.
+///
+/// If `f` panics, the `B`s produced so far and the `A`s not yet consumed are dropped and the
+/// allocation is freed.
+///
+/// Requires `size_of::<B>()` to be nonzero and no greater than `size_of::<A>()`.
+///
+/// With `panic = "abort"`, `f` can't unwind, so there's nothing to clean up and no dropper is
+/// needed.
+#[inline]
+unsafe fn map_elements<A, B, F>(v: Vec<A>, mut f: F) -> *mut B
+    where F: FnMut(A) -> B
+{
+    if cfg!(panic = "abort") {
+        return map_elements_unguarded(v, f);
+    }
+
+    match try_map_elements(v, |a| Ok::<B, Infallible>(f(a))) {
+        Ok(ptr_b) => ptr_b,
+        Err(e) => match e {},
+    }
+}
+
+/// Like `map_elements`, but `f` is also given the buffer and the index of the element it's
+/// mapping, for borrowing the `B`s before it or the `A`s after it. The pointer is the one the
+/// dropper holds, so every access to the buffer derives from it.
+#[inline]
+unsafe fn map_elements_at<A, B, F>(v: Vec<A>, mut f: F) -> *mut B
+    where F: FnMut(*mut A, usize, A) -> B
+{
+    let len = v.len();
+    let mut dropper = VecDropper::<A, B>::new(v);
+
+    let ptr_a = dropper.ptr;
+    let ptr_b = ptr_a as *mut B;
+
+    for i in 0..len {
+        let a = ptr::read(ptr_a.add(i));
+        poison_slot(ptr_a.add(i));
+        ptr::write(ptr_b.add(i), f(ptr_a, i, a));
+
+        dropper.idx += 1;
+    }
+
+    mem::forget(dropper);
+    poison_tail::<A, B>(ptr_b, len);
+
+    ptr_b
+}
+
+/// `map_elements` without any cleanup should `f` panic, which would leak the elements and the
+/// allocation and leave them half mapped.
+#[inline]
+unsafe fn map_elements_unguarded<A, B, F>(v: Vec<A>, mut f: F) -> *mut B
+    where F: FnMut(A) -> B
+{
+    let mut v = mem::ManuallyDrop::new(v);
+    let ptr_a = v.as_mut_ptr();
+    let ptr_b = ptr_a as *mut B;
+
+    for i in 0..v.len() {
+        let a = ptr::read(ptr_a.add(i));
+        poison_slot(ptr_a.add(i));
+        ptr::write(ptr_b.add(i), f(a));
+    }
+
+    poison_tail::<A, B>(ptr_b, v.len());
+
+    ptr_b
+}
+
+/// Like `map_elements`, but stops at the first `Err` returned by `f`, cleaning up as if `f`
+/// had panicked there before returning the error.
+#[inline]
+unsafe fn try_map_elements<A, B, E, F>(v: Vec<A>, mut f: F) -> Result<*mut B, E>
+    where F: FnMut(A) -> Result<B, E>
+{
+    let len = v.len();
+
+    if !mem::needs_drop::<A>() && !mem::needs_drop::<B>() {
+        let mut v = v;
+        let ptr_a = v.as_mut_ptr();
+        let ptr_b = ptr_a as *mut B;
+
+        // if `f` panics or fails, freeing the allocation is the only cleanup needed, and `v`
+        // does that
+        for i in 0..len {
+            let v = ptr::read(ptr_a.add(i));
+            poison_slot(ptr_a.add(i));
+            ptr::write(ptr_b.add(i), f(v)?);
+        }
+
+        mem::forget(v);
+        poison_tail::<A, B>(ptr_b, len);
+
+        return Ok(ptr_b);
+    }
+
+    let mut dropper = VecDropper::<A, B>::new(v);
+
+    // every access to the buffer derives from the pointer the dropper holds
+    let ptr_a = dropper.ptr;
+    let ptr_b = ptr_a as *mut B;
+
+    for i in 0..len {
+        let v = ptr::read(ptr_a.add(i));
+        poison_slot(ptr_a.add(i));
+        ptr::write(ptr_b.add(i), f(v)?);
+
+        dropper.idx += 1;
+    }
+
+    mem::forget(dropper);
+    poison_tail::<A, B>(ptr_b, len);
+
+    Ok(ptr_b)
+}
+
+/// The byte the `debug-poison` feature fills vacated slots with.
+const POISON: u8 = 0xDE;
+
+/// With the `debug-poison` feature, fills the `A` at `ptr`, which has just been moved out of,
+/// with `POISON`, so that reading it again stands out. Does nothing otherwise.
+#[inline]
+unsafe fn poison_slot<A>(ptr: *mut A) {
+    if cfg!(feature = "debug-poison") {
+        ptr::write_bytes(ptr as *mut u8, POISON, mem::size_of::<A>());
+    }
+}
+
+/// With the `debug-poison` feature, fills the bytes after the `len` `B`s at `ptr` that the
+/// `len` `A`s they were mapped from took up with `POISON`. Does nothing otherwise.
+#[inline]
+unsafe fn poison_tail<A, B>(ptr: *mut B, len: usize) {
+    let a_size = mem::size_of::<A>();
+    let b_size = mem::size_of::<B>();
+
+    if cfg!(feature = "debug-poison") && b_size < a_size {
+        ptr::write_bytes(ptr.add(len) as *mut u8, POISON, len * (a_size - b_size));
+    }
+}
+
+/// Returns the capacity of the buffer of a `Vec<A>` with capacity `cap`, holding `B`s at `ptr`
+/// after `map_elements`, along with the buffer's possibly new address.
+///
+/// When the buffer's size in bytes isn't a multiple of `size_of::<B>()`, it is shrunk to the
+/// largest multiple that fits with `realloc`, which may move it (and the `B`s it holds). If not
+/// even one `B` fits, it is freed instead.
+///
+/// Requires `size_of::<B>()` to be nonzero, and `A` and `B` to have the same alignment.
+#[inline]
+unsafe fn shrink_allocation<A, B>(ptr: *mut B, cap: usize) -> (*mut B, usize) {
+    let a_size = mem::size_of::<A>();
+    let b_size = mem::size_of::<B>();
+
+    if a_size == b_size || cap == 0 {
+        return (ptr, cap);
+    }
+
+    let n_bytes = cap * a_size;
+    let new_cap = n_bytes / b_size;
+
+    if n_bytes.is_multiple_of(b_size) {
+        return (ptr, new_cap);
+    }
+
+    let old_layout = Layout::array::<A>(cap).unwrap();
+
+    if new_cap == 0 {
+        dealloc(ptr as *mut u8, old_layout);
+
+        return (ptr::NonNull::dangling().as_ptr(), 0);
+    }
+
+    let ptr = realloc(ptr as *mut u8, old_layout, new_cap * b_size) as *mut B;
+
+    if ptr.is_null() {
+        handle_alloc_error(Layout::array::<B>(new_cap).unwrap());
+    }
+
+    (ptr, new_cap)
+}
+
+/// `idx` is the number of elements that have been mapped; if `f` panics, the element at
+/// `idx` has already been moved out.
+///
+/// The buffer is held as raw parts rather than as a `Vec<A>`, since it holds `B`s as well
+/// once mapping has started. It's only freed if the dropper is dropped, which is only meant to
+/// happen when `f` panics or fails, and otherwise the dropper is forgotten.
+///
+/// A destructor that panics during the cleanup doesn't keep the other elements from being
+/// dropped or the buffer from being freed, and the panic carries on afterwards. If `f` had
+/// panicked, that's a panic while unwinding, so the process aborts instead.
+struct VecDropper<A, B> {
+    idx: usize,
+    ptr: *mut A,
+    len: usize,
+    cap: usize,
+    _marker: PhantomData<B>,
+}
+
+impl<A, B> VecDropper<A, B> {
+    /// Takes over the elements and the buffer of `v`.
+    #[inline]
+    fn new(v: Vec<A>) -> Self {
+        let mut v = mem::ManuallyDrop::new(v);
+
+        VecDropper {
+            idx: 0,
+            ptr: v.as_mut_ptr(),
+            len: v.len(),
+            cap: v.capacity(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<A, B> Drop for VecDropper<A, B> {
+    #[inline]
+    fn drop(&mut self) {
+        // panicked; manual cleanup needed
+        let idx = self.idx;
+        let ptr_a = self.ptr;
+        let ptr_b = ptr_a as *mut B;
+
+        unsafe {
+            // frees the buffer once the elements are gone
+            let _owned = Vec::from_raw_parts(ptr_a, 0, self.cap);
+
+            let _rest = DropSlice(ptr::slice_from_raw_parts_mut(ptr_a.add(idx + 1),
+                                                                self.len - idx - 1));
+
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(ptr_b, idx));
+        }
+    }
+}
+
+/// `idx` is the number of elements that have been mapped, starting from the back; if `f`
+/// panics, the element `idx` places from the back has already been moved out. The `B`s may be
+/// larger than the `A`s, as long as the buffer holds `len` of them.
+///
+/// The buffer is held as raw parts, and panicking destructors are handled, as in `VecDropper`.
+struct RevDropper<A, B> {
+    idx: usize,
+    ptr: *mut A,
+    len: usize,
+    cap: usize,
+    _marker: PhantomData<B>,
+}
+
+impl<A, B> RevDropper<A, B> {
+    /// Takes over the elements and the buffer of `v`.
+    #[inline]
+    fn new(v: Vec<A>) -> Self {
+        let mut v = mem::ManuallyDrop::new(v);
+
+        RevDropper {
+            idx: 0,
+            ptr: v.as_mut_ptr(),
+            len: v.len(),
+            cap: v.capacity(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<A, B> Drop for RevDropper<A, B> {
+    #[inline]
+    fn drop(&mut self) {
+        // panicked; manual cleanup needed
+        let len = self.len;
+        let consumed = len - 1 - self.idx;
+        let ptr_a = self.ptr;
+        let ptr_b = ptr_a as *mut B;
+
+        unsafe {
+            // frees the buffer once the elements are gone
+            let _owned = Vec::from_raw_parts(ptr_a, 0, self.cap);
+
+            let _rest = DropSlice(ptr::slice_from_raw_parts_mut(ptr_b.add(consumed + 1),
+                                                                len - consumed - 1));
+
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(ptr_a, consumed));
+        }
+    }
+}
+
+/// Drops the elements of a slice when dropped.
+///
+/// The drop glue of a slice carries on past an element whose destructor panics, so the
+/// droppers only need this for what comes after the first slice they drop.
+struct DropSlice<T>(*mut [T]);
+
+impl<T> Drop for DropSlice<T> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe {
+            ptr::drop_in_place(self.0);
+        }
+    }
+}
+
+/// Maps every element of the slice at `ptr` into the slot it occupies.
+///
+/// If `f` panics, the `B`s produced so far and the `A`s not yet consumed are dropped, then
+/// `free` is called to release the allocation.
+///
+/// Requires `A` and `B` to have the same size, and `ptr` to be aligned for both.
+#[inline]
+unsafe fn map_slice<A, B, F>(ptr: *mut [A], mut f: F, free: unsafe fn(*mut [A])) -> *mut [B]
+    where F: FnMut(A) -> B
+{
+    let len = ptr.len();
+    let ptr_a = ptr as *mut A;
+    let ptr_b = ptr_a as *mut B;
+
+    if cfg!(panic = "abort") {
+        // as in `map_elements`
+        for i in 0..len {
+            let a = ptr::read(ptr_a.add(i));
+            poison_slot(ptr_a.add(i));
+            ptr::write(ptr_b.add(i), f(a));
+        }
+
+        return ptr as *mut [B];
+    }
+
+    let mut dropper = SliceDropper::<A, B> {
+        idx: 0,
+        ptr,
+        free,
+        _marker: PhantomData,
+    };
+
+    for i in 0..len {
+        let v = ptr::read(ptr_a.add(i));
+        poison_slot(ptr_a.add(i));
+        ptr::write(ptr_b.add(i), f(v));
+
+        dropper.idx += 1;
+    }
+
+    mem::forget(dropper);
+
+    ptr as *mut [B]
+}
+
+/// `idx` is the number of elements that have been mapped; if `f` panics, the element at
+/// `idx` has already been moved out.
+///
+/// Panicking destructors are handled as in `VecDropper`, with `free` still called.
+struct SliceDropper<A, B> {
+    idx: usize,
+    ptr: *mut [A],
+    free: unsafe fn(*mut [A]),
+    _marker: PhantomData<B>,
+}
+
+impl<A, B> Drop for SliceDropper<A, B> {
+    #[inline]
+    fn drop(&mut self) {
+        // panicked; manual cleanup needed
+        unsafe {
+            let len = self.ptr.len();
+            let ptr_a = self.ptr as *mut A;
+            let ptr_b = ptr_a as *mut B;
+
+            let _free = FreeSlice(self.ptr, self.free);
+            let _rest = DropSlice(ptr::slice_from_raw_parts_mut(ptr_a.add(self.idx + 1),
+                                                                len - self.idx - 1));
+
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(ptr_b, self.idx));
+        }
+    }
+}
+
+/// Calls the function with the slice when dropped, like `SliceDropper` does with `free`.
+struct FreeSlice<A>(*mut [A], unsafe fn(*mut [A]));
+
+impl<A> Drop for FreeSlice<A> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe {
+            (self.1)(self.0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CheckedMapInPlace, ConvertInPlace, MapError, MapInPlace, MapInPlaceCollectErrors,
+                MapInPlaceDyn, MapInPlaceGrow, MapInPlaceOrAlloc, MapInPlaceRev, MapInPlaceShrink,
+                MapInPlaceUntil, MapWithMappedPrefix, MapWithNextInPlace, Mapped, RecycleInPlace,
+                TryConvertInPlace, TryMapInPlace, TryMapInPlaceWithRollback};
+    #[cfg(feature = "std")]
+    use super::MapInPlaceCatch;
+    use testing::{DropTracker, Tracked};
+
+    use std::borrow::Cow;
+    use std::error::Error;
+    use std::convert::{Infallible, TryFrom};
+    use std::num::NonZeroI64;
+    use std::ops::ControlFlow;
+
+    use std::env;
+    use std::mem;
+    use std::process::Command;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    macro_rules! box_drop_test {
+        ($name:ident, $xtype:ty, $ytype:ty, $same_addr:expr) => {
+            #[test]
+            fn $name() {
+                static DROPS: DropTracker = DropTracker::new();
+
+                let b = Box::new(DROPS.track(7 as $xtype));
+
+                let bp = Box::into_raw(b);
+                let b = unsafe { Box::from_raw(bp) };
+                let bp = bp as *const ();
+
+                let b = b.map_in_place(|x| DROPS.track(-(x.0 as $ytype)));
+
+                DROPS.assert_drops(&["7"]);
+
+                let ap = Box::into_raw(b);
+                let b = unsafe { Box::from_raw(ap) };
+                let ap = ap as *const ();
+
+                if $same_addr {
+                    assert_eq!(bp, ap);
+                }
+
+                mem::drop(b);
+                DROPS.assert_drops(&["7", "-7"]);
+            }
+        }
+    }
+
+    macro_rules! box_panic_drop_test {
+        ($name:ident, $xtype:ty, $ytype:ty) => {
+            #[test]
+            fn $name() {
+                static DROPS: DropTracker = DropTracker::new();
+
+                let b = Box::new(DROPS.track(7 as $xtype));
+
+                let bp = Box::into_raw(b);
+                let b = unsafe { Box::from_raw(bp) };
+
+                match catch_unwind(|| b.map_in_place(|_| -> Tracked<$ytype> {
+                    panic!();
+                })) {
+                    Ok(_) => unreachable!(),
+                    Err(_) => DROPS.assert_drops(&["7"]),
+                }
+            }
+        }
+    }
+
+    box_drop_test!(box_drop_same_sizes, u64, i64, true);
+    // a `Tracked<u128>` is larger than a `Tracked<i32>`
+    box_drop_test!(box_drop_diff_sizes, u128, i32, false);
+    box_panic_drop_test!(box_panic_drop_same_sizes, u64, i64);
+    box_panic_drop_test!(box_panic_drop_diff_sizes, u128, i32);
+
+    #[test]
+    fn box_larger_b() {
+        // a compile error with `map_in_place`
+        let e = Box::new(7u32).map_in_place_checked(|x| x as u64).unwrap_err();
+
+        assert_eq!(e.reason(), MapError::SizeIncrease);
+        assert_eq!(*e.into_inner(), 7);
+    }
+
+    #[test]
+    fn box_zst() {
+        let b = Box::new(7u64).map_in_place(|_| ());
+        assert_eq!(*b, ());
+
+        let b = Box::new(()).map_in_place(|()| ());
+        assert_eq!(*b, ());
+    }
+
+    #[test]
+    fn vec_elements_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (1..5).map(|x| DROPS.track(x as usize)).collect();
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.map_in_place(|x| DROPS.track(-(x.0 as isize)));
+
+        DROPS.assert_drops(&["1", "2", "3", "4"]);
+
+        let ap = v.as_ptr() as *const ();
+        let expected: Vec<_> = (1..5).map(|x| DROPS.track(-x)).collect();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, expected);
+
+        mem::drop(v);
+        DROPS.assert_drops(&["1", "2", "3", "4", "-1", "-2", "-3", "-4"]);
+
+        mem::drop(expected);
+    }
+
+    macro_rules! vec_panic_drop_test {
+        ($name:ident, $xs:expr, $to_y:expr, $expected:expr) => {
+            #[test]
+            fn $name() {
+                static DROPS: DropTracker = DropTracker::new();
+
+                let v: Vec<_> = $xs.iter().map(|&x| DROPS.track(x)).collect();
+
+                match catch_unwind(|| {
+                    v.map_in_place(|x| {
+                        if x.0 == $xs[2] {
+                            panic!();
+                        } else {
+                            DROPS.track($to_y(x.0))
+                        }
+                    })
+                }) {
+                    Ok(_) => unreachable!(),
+                    Err(_) => DROPS.assert_drops(&$expected),
+                }
+            }
+        }
+    }
+
+    vec_panic_drop_test!(vec_same_size_panic_drop,
+                         ['a', 'b', 'c', 'd', 'e'],
+                         |c| c as u32,
+                         [// consume Xs
+                          "'a'",
+                          "'b'",
+                          "'c'",
+                          // panic here
+                          // drop generated Ys
+                          "97",
+                          "98",
+                          // drop remaining unprocessed Xs
+                          "'d'",
+                          "'e'"]);
+    // a `Tracked<&str>` is larger than a `Tracked<u16>`
+    vec_panic_drop_test!(vec_diff_size_panic_drop,
+                         ["a", "b", "c", "d", "e"],
+                         |s: &str| s.as_bytes()[0] as u16,
+                         [// consume Xs
+                          "\"a\"",
+                          "\"b\"",
+                          "\"c\"",
+                          // panic here
+                          // drop generated Ys
+                          "97",
+                          "98",
+                          // drop remaining unprocessed Xs
+                          "\"d\"",
+                          "\"e\""]);
+
+
+    #[test]
+    fn same_size_vec() {
+        let v = vec![0, 1, 2, 3];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.map_in_place(|x: u32| (x * x) as i32);
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![0, 1, 4, 9]);
+    }
+
+    // the buffer must always be freed with the alignment it was allocated with; these are
+    // only caught by running the tests under Miri
+
+    #[test]
+    fn same_size_vec_align_increase() {
+        let v: Vec<[u8; 4]> = vec![[1, 0, 0, 0], [0, 1, 0, 0]];
+        let v = v.map_in_place(u32::from_le_bytes);
+
+        assert_eq!(v, vec![1, 256]);
+    }
+
+    #[test]
+    fn same_size_vec_align_decrease() {
+        let v: Vec<u64> = vec![1, 256];
+        let v = v.map_in_place(u64::to_le_bytes);
+
+        assert_eq!(v, vec![[1, 0, 0, 0, 0, 0, 0, 0], [0, 1, 0, 0, 0, 0, 0, 0]]);
+    }
+
+    #[test]
+    fn same_size_vec_over_aligned() {
+        #[derive(Debug, PartialEq)]
+        #[repr(align(64))]
+        struct Line([u8; 64]);
+
+        let v: Vec<[u8; 64]> = vec![[7; 64], [9; 64]];
+        let v = v.map_in_place(Line);
+
+        assert_eq!(v, vec![Line([7; 64]), Line([9; 64])]);
+        assert!((v.as_ptr() as usize).is_multiple_of(64));
+
+        let v = v.map_in_place(|Line(bytes)| bytes);
+        assert_eq!(v, vec![[7; 64], [9; 64]]);
+    }
+
+    #[test]
+    fn same_size_vec_align_mismatch_rev() {
+        let mut order = vec![];
+
+        let v: Vec<[u8; 4]> = vec![[1, 0, 0, 0], [0, 1, 0, 0]];
+        let v = v.map_in_place_rev(|b| {
+            order.push(b[0]);
+            u32::from_le_bytes(b)
+        });
+
+        assert_eq!(v, vec![1, 256]);
+        assert_eq!(order, vec![0, 1]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn same_size_vec_align_mismatch_try() {
+        let _ = vec![[0u8; 4]].try_map_in_place(|b| Ok::<_, ()>(u32::from_le_bytes(b)));
+    }
+
+    #[test]
+    fn different_sizes_vec() {
+        let v = vec![(0, 0), (1, 1), (2, 2), (3, 3)];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.map_in_place(|(x, y): (u32, u32)| (x * y) as i32);
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![0, 1, 4, 9]);
+    }
+
+    #[test]
+    fn different_sizes_vec_align_decrease() {
+        let v = vec![0, 1, 2, 3];
+        let v = v.map_in_place(|x: u32| (x * x) as i16);
+
+        assert_eq!(v, vec![0, 1, 4, 9]);
+    }
+
+    #[test]
+    fn different_sizes_vec_align_increase() {
+        let v: Vec<[u8; 8]> = vec![[1, 0, 0, 0, 0, 0, 0, 0], [0, 1, 0, 0, 0, 0, 0, 0]];
+        let v = v.map_in_place(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+
+        assert_eq!(v, vec![1, 256]);
+    }
+
+    #[test]
+    fn different_sizes_vec_over_aligned() {
+        #[derive(Debug, PartialEq)]
+        #[repr(align(64))]
+        struct Line([u8; 64]);
+
+        let v: Vec<[u8; 128]> = vec![[7; 128], [9; 128]];
+        let v = v.map_in_place(|b| Line([b[0]; 64]));
+
+        assert_eq!(v, vec![Line([7; 64]), Line([9; 64])]);
+        assert!((v.as_ptr() as usize).is_multiple_of(64));
+    }
+
+    #[test]
+    fn dyn_vec() {
+        let v = vec![(0, 0), (1, 1), (2, 2), (3, 3)];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.map_in_place_dyn(&mut |(x, y): (u32, u32)| (x * y) as i32);
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![0, 1, 4, 9]);
+
+        let v = v.map_in_place_dyn(&mut |_| ());
+        assert_eq!(v.len(), 4);
+    }
+
+    #[test]
+    fn dyn_vec_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = "abcd".chars().map(|c| DROPS.track(c)).collect();
+
+        assert!(catch_unwind(|| {
+            v.map_in_place_dyn(&mut |x: Tracked<char>| {
+                if x.0 == 'c' {
+                    panic!();
+                }
+
+                DROPS.track(x.0.to_ascii_uppercase())
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&[// consume Xs
+                             "'a'",
+                             "'b'",
+                             "'c'",
+                             // panic here
+                             // drop generated Xs
+                             "'A'",
+                             "'B'",
+                             // drop remaining unprocessed Xs
+                             "'d'"]);
+    }
+
+    #[test]
+    fn both_zst_vec() {
+        #[derive(Debug)]
+        struct Zst;
+
+        let v = vec![(), (), (), ()];
+
+        let bp = v.as_ptr();
+        let v = v.map_in_place(|_| Zst);
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+    }
+
+    #[test]
+    fn nzst_to_zst_vec() {
+        let v = vec![0, 1, 2, 3];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.map_in_place(|_: u32| ());
+        let ap = v.as_ptr();
+
+        assert!(bp != ap); // -- NOT -- still at same memory addr
+    }
+
+    #[test]
+    fn nzst_to_zst_vec_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        struct Z;
+
+        impl Drop for Z {
+            fn drop(&mut self) {
+                DROPS.record("Z");
+            }
+        }
+
+        let mut calls = vec![];
+        let v = vec![1u32, 2, 3].map_in_place(|x| {
+            calls.push(x);
+            Z
+        });
+
+        assert_eq!(v.len(), 3);
+        assert_eq!(calls, vec![1, 2, 3]);
+        DROPS.assert_count(0);
+
+        drop(v);
+        DROPS.assert_drops(&["Z", "Z", "Z"]);
+    }
+
+    #[test]
+    fn nzst_to_zst_vec_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        struct Z;
+
+        impl Drop for Z {
+            fn drop(&mut self) {
+                DROPS.record("Z");
+            }
+        }
+
+        let v: Vec<_> = (0..4).map(|x| DROPS.track(x)).collect();
+
+        assert!(catch_unwind(AssertUnwindSafe(|| {
+            v.map_in_place(|x| {
+                if x.0 == 2 {
+                    panic!();
+                }
+
+                Z
+            })
+        })).is_err());
+
+        DROPS.assert_drops(&[// consume Xs
+                             "0",
+                             "1",
+                             "2",
+                             // panic here
+                             // drop generated Zs
+                             "Z",
+                             "Z",
+                             // drop remaining unprocessed Xs
+                             "3"]);
+    }
+
+    #[test]
+    fn uninhabited_vec_empty() {
+        let v: Vec<String> = Vec::with_capacity(4);
+
+        let v: Vec<Infallible> = v.map_in_place(|_| unreachable!());
+        assert!(v.is_empty());
+
+        let v: Vec<Infallible> = v.map_in_place(|e| match e {});
+        assert!(v.is_empty());
+
+        let b: Box<[String]> = Box::new([]);
+        let b: Box<[Infallible]> = b.map_in_place(|_| unreachable!());
+        assert!(b.is_empty());
+    }
+
+    #[test]
+    fn uninhabited_vec_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (0..3).map(|x| DROPS.track(x)).collect();
+
+        assert!(catch_unwind(|| {
+            let _: Vec<Infallible> = v.map_in_place(|_| panic!());
+        }).is_err());
+
+        DROPS.assert_drops(&[// consume Xs
+                             "0",
+                             // panic here
+                             // drop remaining unprocessed Xs
+                             "1",
+                             "2"]);
+    }
+
+    #[test]
+    fn zst_to_nzst_vec() {
+        let mut calls = vec![];
+        let v = vec![(), (), (), ()];
+
+        // there's no buffer to reuse, so the results are collected into a new one
+        let v = v.map_in_place_enumerated(|i, ()| {
+            calls.push(i);
+            i as u8
+        });
+
+        assert_eq!(v, vec![0, 1, 2, 3]);
+        assert_eq!(calls, vec![0, 1, 2, 3]);
+
+        let v = vec![(), ()].map_in_place_checked(|_| 7usize).unwrap();
+        assert_eq!(v, vec![7, 7]);
+
+        let b = Box::new(()).map_in_place(|()| 7u64);
+        assert_eq!(*b, 7);
+    }
+
+    #[test]
+    fn zst_to_nzst_vec_generic() {
+        // no special case needed for zero-sized `T`s
+        fn positions<T>(v: Vec<T>) -> Vec<usize> {
+            v.map_in_place_enumerated(|i, _| i)
+        }
+
+        assert_eq!(positions(vec![(), ()]), vec![0, 1]);
+        assert_eq!(positions(vec![1u64, 2]), vec![0, 1]);
+    }
+
+    // none of these are compile errors, even though `B` is larger than `A`
+    #[test]
+    fn larger_b_checked() {
+        let v: Vec<u32> = vec![1, 2];
+        let v = v.map_in_place_checked(|x| [x; 2]).unwrap_err().into_inner();
+        let (v, _) = v.checked_map_in_place(|x| [x; 2]).unwrap_err();
+        let v = v.map_in_place_grow(|x| [x; 2]).unwrap_err();
+        let v = v.map_in_place_or_alloc(|x| [x; 2]);
+        assert_eq!(v, vec![[1; 2], [2; 2]]);
+
+        let e = Box::new(1u32).map_in_place_checked(|x| [x; 2]).unwrap_err();
+        assert_eq!(*e.into_inner(), 1);
+    }
+
+    #[test]
+    fn vec_panic_on_last_element_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (1..4).map(|x| DROPS.track(x as u32)).collect();
+
+        assert!(catch_unwind(|| {
+            v.map_in_place(|x| {
+                if x.0 == 3 {
+                    panic!();
+                } else {
+                    DROPS.track(-(x.0 as i32))
+                }
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&["1", "2", "3", "-1", "-2"]);
+    }
+
+    // A destructor panicking during the cleanup after an error from `f`. After a panic from
+    // `f`, or a second destructor panicking, it would be a panic while unwinding, which aborts.
+    macro_rules! drop_panic_test {
+        ($name:ident, $panicking:expr, $expected:expr) => {
+            #[test]
+            fn $name() {
+                static DROPS: DropTracker = DropTracker::new();
+
+                let expected: Vec<&str> = $expected;
+                DROPS.panic_on_drop(expected.iter().position(|&d| d == $panicking).unwrap());
+
+                let v: Vec<_> = (1..7).map(|x| DROPS.track(x as u32)).collect();
+
+                let e = catch_unwind(|| {
+                        v.try_map_in_place(|x| {
+                            if x.0 == 4 { Err(()) } else { Ok(DROPS.track(-(x.0 as i32))) }
+                        })
+                    })
+                    .unwrap_err();
+
+                DROPS.assert_drops(&expected);
+                assert_eq!(e.downcast_ref::<String>().map(|s| &**s), Some($panicking));
+            }
+        }
+    }
+
+    drop_panic_test!(vec_drop_panic_mapped_prefix,
+                     "-2",
+                     vec![// consume Xs
+                          "1",
+                          "2",
+                          "3",
+                          "4",
+                          // fail here
+                          // drop generated Ys, -2 panics
+                          "-1",
+                          "-2",
+                          "-3",
+                          // drop remaining unprocessed Xs
+                          "5",
+                          "6"]);
+
+    drop_panic_test!(vec_drop_panic_unprocessed_suffix,
+                     "5",
+                     vec![// consume Xs
+                          "1",
+                          "2",
+                          "3",
+                          "4",
+                          // fail here
+                          // drop generated Ys
+                          "-1",
+                          "-2",
+                          "-3",
+                          // drop remaining unprocessed Xs, 5 panics
+                          "5",
+                          "6"]);
+
+    // After a panic from `f`, a destructor panicking during the cleanup is a panic while
+    // unwinding, which aborts. That takes the whole process down, so it runs in a child.
+    #[test]
+    fn vec_drop_panic_while_unwinding_aborts() {
+        const CHILD: &str = "MAP_IN_PLACE_ABORT_CHILD";
+
+        if env::var_os(CHILD).is_some() {
+            static DROPS: DropTracker = DropTracker::new();
+
+            // the second of the generated Ys panics
+            DROPS.panic_on_drop(1);
+
+            // as large as a `Tracked<u32>`
+            let v: Vec<[u32; 4]> = (0..4).map(|x| [x; 4]).collect();
+
+            let _ = catch_unwind(|| {
+                v.map_in_place(|[x, ..]| if x == 3 { panic!() } else { DROPS.track(x) })
+            });
+
+            unreachable!();
+        }
+
+        let out = Command::new(env::current_exe().unwrap())
+            .args(["--exact", "tests::vec_drop_panic_while_unwinding_aborts", "--test-threads=1"])
+            .env(CHILD, "1")
+            .output()
+            .unwrap();
+
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert!(!out.status.success());
+        assert!(stderr.contains("panic in a destructor during cleanup"));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn catch_vec() {
+        let v: Vec<u32> = vec![1, 2, 3];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.map_in_place_catch(|x| x as i32 * 2).unwrap();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![2, 4, 6]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn catch_vec_first_and_last() {
+        let v = vec![String::from("a"), "b".into(), "c".into()];
+        let e = v.map_in_place_catch(|s| -> Box<str> { panic!("no {}", s) }).unwrap_err();
+
+        assert_eq!(e.index(), 0);
+        assert_eq!(e.rest(), &["b", "c"]);
+        assert_eq!(e.to_string(), "map_in_place_catch: f panicked on element 0: no a");
+
+        let v = vec![String::from("a"), "b".into(), "c".into()];
+        let e = v.map_in_place_catch(|s| -> Box<str> {
+                if s == "c" {
+                    panic!("last");
+                }
+
+                s.into_boxed_str()
+            })
+            .unwrap_err();
+
+        assert_eq!(e.index(), 2);
+        assert!(e.rest().is_empty());
+        assert_eq!(e.payload().downcast_ref::<&str>(), Some(&"last"));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn catch_vec_payload() {
+        // payloads are always `Send`, so whatever `f` panics with comes back as it was
+        #[derive(Debug, PartialEq)]
+        struct Payload(u32);
+
+        let panics = |x: u32| -> i32 { ::std::panic::panic_any(Payload(x)) };
+        let e = vec![1u32, 2].map_in_place_catch(panics).unwrap_err();
+
+        assert_eq!(e.to_string(), "map_in_place_catch: f panicked on element 0");
+
+        let (payload, index, rest) = e.into_parts();
+        assert_eq!(payload.downcast_ref::<Payload>(), Some(&Payload(1)));
+        assert_eq!(index, 0);
+        assert_eq!(rest, vec![2]);
+
+        let e: Box<dyn Error> = Box::new(vec![1u32].map_in_place_catch(|_| -> u32 { panic!() })
+                                                   .unwrap_err());
+        assert!(e.to_string().starts_with("map_in_place_catch"));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn catch_vec_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (1..5).map(|x| DROPS.track(x as u32)).collect();
+        let e = v.map_in_place_catch(|x| {
+                if x.0 == 3 {
+                    panic!();
+                }
+
+                DROPS.track(-(x.0 as i32))
+            })
+            .unwrap_err();
+
+        DROPS.assert_drops(&[// consume Xs
+                             "1",
+                             "2",
+                             "3",
+                             // panic here
+                             // drop generated Ys
+                             "-1",
+                             "-2"]);
+
+        // the remaining unprocessed Xs are handed back
+        assert_eq!(e.index(), 2);
+        assert_eq!(e.rest().len(), 1);
+        assert_eq!(e.rest()[0].0, 4);
+    }
+
+    #[test]
+    fn enumerated_vec() {
+        let v: Vec<u64> = vec![10, 20, 30];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.map_in_place_enumerated(|i, x| (i * 100) as i64 + x as i64);
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![10, 120, 230]);
+    }
+
+    #[test]
+    fn enumerated_vec_zst() {
+        let mut seen = vec![];
+        let v = vec![1u8, 2, 3].map_in_place_enumerated(|i, _| seen.push(i));
+
+        assert_eq!(v.len(), 3);
+        assert_eq!(seen, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn enumerated_boxed_slice() {
+        let b: Box<[u32]> = vec![5, 5, 5].into_boxed_slice();
+        let b = b.map_in_place_enumerated(|i, x| x + i as u32);
+
+        assert_eq!(&*b, &[5, 6, 7]);
+    }
+
+    #[test]
+    fn with_state_vec() {
+        fn offset(base: &mut u64, x: u64) -> u64 {
+            *base += 1;
+            x + *base
+        }
+
+        let mut base = 0;
+
+        let v: Vec<u64> = vec![10, 20, 30];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.map_in_place_with(&mut base, offset);
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![11, 22, 33]);
+
+        let v = vec![10u64].map_in_place_with(&mut base, offset);
+        assert_eq!(v, vec![14]);
+        assert_eq!(base, 4);
+    }
+
+    #[test]
+    fn with_state_vec_zst() {
+        let mut seen = vec![];
+        let v = vec![1u8, 2, 3].map_in_place_with(&mut seen, |seen, x| seen.push(x));
+
+        assert_eq!(v.len(), 3);
+        assert_eq!(seen, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn with_state_vec_panic() {
+        let mut count = 0;
+
+        assert!(catch_unwind(AssertUnwindSafe(|| {
+            vec![1u32, 2, 3].map_in_place_with(&mut count, |count, x| {
+                if x == 3 {
+                    panic!();
+                }
+
+                *count += 1;
+                x
+            })
+        })).is_err());
+
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn scan_vec() {
+        let v: Vec<u64> = vec![100, 103, 110, 111];
+
+        let bp = v.as_ptr() as *const ();
+        let (v, last) = v.scan_in_place(0, |prev, x| {
+            let delta = x - *prev;
+            *prev = x;
+            delta
+        });
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![100, 3, 7, 1]);
+        assert_eq!(last, 111);
+    }
+
+    #[test]
+    fn scan_vec_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (0..3).map(|x| DROPS.track(x as u32)).collect();
+
+        assert!(catch_unwind(|| {
+            v.scan_in_place(DROPS.track(10), |s, x| {
+                if x.0 == 1 {
+                    panic!();
+                } else {
+                    s.0 += 1;
+                    x.0
+                }
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&["0", "1", "2", "11"]);
+    }
+
+    #[test]
+    fn with_prev_vec() {
+        let v: Vec<u64> = vec![100, 103, 110, 111];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.map_with_prev_in_place(|prev, x| x - prev.unwrap_or(&0));
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![100, 3, 7, 1]);
+    }
+
+    #[test]
+    fn with_prev_vec_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (0..4).map(|x| DROPS.track(x as u32)).collect();
+
+        assert!(catch_unwind(|| {
+            v.map_with_prev_in_place(|prev, x| {
+                if x.0 == 2 {
+                    panic!();
+                }
+
+                prev.map_or(0, |p| p.0) + x.0
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&[// replace the element kept aside
+                             "0",
+                             // panic here
+                             "2",
+                             // drop remaining unprocessed elements
+                             "3",
+                             // drop the element kept aside
+                             "1"]);
+    }
+
+    #[test]
+    fn mapped_prefix_vec() {
+        let v: Vec<u64> = vec![1, 2, 3, 4];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.map_with_mapped_prefix(|prefix, x| prefix.last().unwrap_or(&0) + x);
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![1, 3, 6, 10]);
+    }
+
+    #[test]
+    fn mapped_prefix_vec_shrinking() {
+        // back-references to earlier outputs
+        let v: Vec<(u32, u32)> = vec![(0, 7), (0, 8), (0, 0), (1, 1)];
+        let mut seen = vec![];
+
+        let v: Vec<u32> = v.map_with_mapped_prefix(|prefix, (is_ref, x)| {
+            seen.push(prefix.to_vec());
+
+            if is_ref == 1 { prefix[x as usize] } else { x }
+        });
+
+        assert_eq!(v, vec![7, 8, 0, 8]);
+        assert_eq!(seen, vec![vec![], vec![7], vec![7, 8], vec![7, 8, 0]]);
+    }
+
+    #[test]
+    fn mapped_prefix_vec_zst() {
+        let mut lens = vec![];
+        let v = vec![1u32, 2, 3].map_with_mapped_prefix(|prefix: &[()], _| lens.push(prefix.len()));
+
+        assert_eq!(v.len(), 3);
+        assert_eq!(lens, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn mapped_prefix_vec_alignment_mismatch() {
+        // the results go in a new allocation, which the prefix is borrowed from
+        let v = vec![1u32, 2, 3].map_with_mapped_prefix(|prefix: &[[u16; 2]], x| {
+            [x as u16, prefix.len() as u16]
+        });
+
+        assert_eq!(v, vec![[1, 0], [2, 1], [3, 2]]);
+    }
+
+    #[test]
+    fn with_next_vec() {
+        let v: Vec<i64> = vec![1, 4, 9, 16];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.map_with_next_in_place(|x, next| next.map_or(0, |n| n - x));
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![3, 5, 7, 0]);
+    }
+
+    #[test]
+    fn with_next_vec_shrinking() {
+        let v: Vec<String> = vec!["a".into(), "bb".into(), "ccc".into(), "dddd".into()];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.map_with_next_in_place(|s, next| (s.len(), next.map_or(0, String::len)));
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![(1, 2), (2, 3), (3, 4), (4, 0)]);
+    }
+
+    #[test]
+    fn with_next_vec_alignment_mismatch() {
+        let v = vec![1u32, 2, 3].map_with_next_in_place(|x, next| {
+            [x as u16, next.map_or(0, |&n| n as u16)]
+        });
+
+        assert_eq!(v, vec![[1, 2], [2, 3], [3, 0]]);
+    }
+
+    #[test]
+    fn with_next_vec_zst() {
+        let mut seen = vec![];
+        let v = vec![1u32, 2, 3].map_with_next_in_place(|x, next| seen.push((x, next.cloned())));
+
+        assert_eq!(v.len(), 3);
+        assert_eq!(seen, vec![(1, Some(2)), (2, Some(3)), (3, None)]);
+    }
+
+    #[test]
+    fn with_next_vec_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (0..4).map(|x| DROPS.track(x as u32)).collect();
+
+        assert!(catch_unwind(|| {
+            v.map_with_next_in_place(|x, next| {
+                if next.map(|n| n.0) == Some(2) {
+                    panic!();
+                }
+
+                DROPS.track(x.0 + 10)
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&[// consume Xs
+                             "0",
+                             "1",
+                             // panic here
+                             // drop generated Xs
+                             "10",
+                             // drop remaining unprocessed Xs
+                             "2",
+                             "3"]);
+    }
+
+    #[test]
+    fn rev_vec() {
+        let v: Vec<u32> = vec![1, 2, 3];
+        let mut order = vec![];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.map_in_place_rev(|x| {
+            order.push(x);
+            x as f32
+        });
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![1.0, 2.0, 3.0]);
+        assert_eq!(order, vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn rev_vec_zst() {
+        let mut order = vec![];
+        let v = vec![1u32, 2, 3].map_in_place_rev(|x| order.push(x));
+
+        assert_eq!(v.len(), 3);
+        assert_eq!(order, vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn rev_vec_different_sizes() {
+        let mut order = vec![];
+
+        // the results go in a new allocation, in the order of the elements they're mapped from
+        let v = vec![1u64, 2].map_in_place_rev(|x| {
+            order.push(x);
+            x as u32
+        });
+        assert_eq!(v, vec![1, 2]);
+
+        let v = v.map_in_place_rev(|x| {
+            order.push(x as u64);
+            x as u64
+        });
+        assert_eq!(v, vec![1, 2]);
+        assert_eq!(order, vec![2, 1, 2, 1]);
+    }
+
+    #[test]
+    fn rev_vec_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (1..6).map(|x| DROPS.track(x as u32)).collect();
+
+        assert!(catch_unwind(|| {
+            v.map_in_place_rev(|x| {
+                if x.0 == 3 {
+                    panic!();
+                } else {
+                    DROPS.track(-(x.0 as i32))
+                }
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&[// consume Xs from the back
+                             "5",
+                             "4",
+                             "3",
+                             // panic here
+                             // drop remaining unprocessed elements
+                             "1",
+                             "2",
+                             // drop mapped elements
+                             "-4",
+                             "-5"]);
+    }
+
+    #[test]
+    fn map_fold_vec() {
+        let v: Vec<u32> = vec![3, 1, 4, 1, 5];
+
+        let bp = v.as_ptr() as *const ();
+        let (v, (min, max)) = v.map_fold_in_place((u32::MAX, 0), |(min, max), x| {
+            ((min.min(x), max.max(x)), x as i32 - 2)
+        });
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![1, -1, 2, -1, 3]);
+        assert_eq!((min, max), (1, 5));
+    }
+
+    #[test]
+    fn map_fold_vec_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (0..3).map(|x| DROPS.track(x as u32)).collect();
+
+        assert!(catch_unwind(|| {
+            v.map_fold_in_place(DROPS.track(10), |acc, x| {
+                if x.0 == 1 {
+                    panic!();
+                } else {
+                    (DROPS.track(acc.0 + 1), x.0)
+                }
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&["0",
+                             // the first accumulator, replaced
+                             "10",
+                             "1",
+                             // panic here, dropping the accumulator given to `f`
+                             "11",
+                             // drop remaining unprocessed elements
+                             "2"]);
+    }
+
+    #[test]
+    fn try_map_vec() {
+        let v: Vec<&str> = vec!["1", "2", "3"];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.try_map_in_place(|s| s.parse::<usize>()).unwrap();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn try_map_vec_err() {
+        let v: Vec<&str> = vec!["1", "x", "3"];
+        let r = v.try_map_in_place(|s| s.parse::<usize>());
+
+        assert!(r.is_err());
+    }
+
+    #[test]
+    fn try_map_vec_zst() {
+        let v = vec![1u32, 2, 3];
+        assert_eq!(v.try_map_in_place(|_| Ok::<(), ()>(())), Ok(vec![(), (), ()]));
+
+        let v = vec![1u32, 2, 3];
+        assert_eq!(v.try_map_in_place(|x| if x == 2 { Err(x) } else { Ok(()) }), Err(2));
+    }
+
+    #[test]
+    fn try_map_vec_err_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (1..5).map(|x| DROPS.track(x as u32)).collect();
+
+        let r = v.try_map_in_place(|x| {
+            if x.0 == 3 {
+                Err(x.0)
+            } else {
+                Ok(DROPS.track(-(x.0 as i32)))
+            }
+        });
+
+        assert_eq!(r.unwrap_err(), 3);
+
+        DROPS.assert_drops(&[// consume Xs
+                             "1",
+                             "2",
+                             "3",
+                             // error here
+                             // drop mapped elements
+                             "-1",
+                             "-2",
+                             // drop remaining unprocessed elements
+                             "4"]);
+    }
+
+    #[test]
+    fn try_map_vec_partial() {
+        let v: Vec<&str> = vec!["1", "2", "x", "4", "5"];
+
+        let bp = v.as_ptr() as *const ();
+        let (mapped, rest, _) = v.try_map_in_place_partial(|s| s.parse::<usize>()).unwrap_err();
+        let ap = mapped.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // prefix still at same memory addr
+        assert_eq!(mapped, vec![1, 2]);
+        assert_eq!(rest, vec!["4", "5"]);
+
+        let v: Vec<&str> = vec!["1", "2"];
+        assert_eq!(v.try_map_in_place_partial(|s| s.parse::<usize>()), Ok(vec![1, 2]));
+    }
+
+    #[test]
+    fn try_map_vec_partial_zst() {
+        let v = vec![1u32, 2, 3, 4];
+        let r = v.try_map_in_place_partial(|x| if x == 2 { Err(x) } else { Ok(()) });
+
+        assert_eq!(r, Err((vec![()], vec![3, 4], 2)));
+    }
+
+    #[test]
+    fn try_map_vec_partial_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (1..6).map(|x| DROPS.track(x as u32)).collect();
+
+        let (mapped, rest, e) = v.try_map_in_place_partial(|x| {
+                if x.0 == 3 {
+                    Err(x.0)
+                } else {
+                    Ok(DROPS.track(-(x.0 as i32)))
+                }
+            })
+            .unwrap_err();
+
+        assert_eq!(e, 3);
+        DROPS.assert_drops(&["1", "2", "3"]);
+
+        drop(mapped);
+        drop(rest);
+
+        DROPS.assert_drops(&["1", "2", "3", "-1", "-2", "4", "5"]);
+    }
+
+    #[test]
+    fn try_map_vec_partial_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (1..5).map(|x| DROPS.track(x as u32)).collect();
+
+        assert!(catch_unwind(|| {
+            v.try_map_in_place_partial(|x| {
+                if x.0 == 2 {
+                    panic!();
+                } else {
+                    Ok::<_, ()>(DROPS.track(-(x.0 as i32)))
+                }
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&["1", "2", "-1", "3", "4"]);
+    }
+
+    #[test]
+    fn rollback_vec() {
+        let v: Vec<u32> = (0..100).collect();
+
+        let bp = v.as_ptr() as *const ();
+        let encrypt = |x| if x == 90 { Err((x, "fail")) } else { Ok(x as i32 ^ 0x5555) };
+        let decrypt = |y| (y ^ 0x5555) as u32;
+        let (v, e) = v.try_map_in_place_with_rollback(encrypt, decrypt).unwrap_err();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, (0..100).collect::<Vec<u32>>());
+        assert_eq!(e, "fail");
+
+        let v = v.try_map_in_place_with_rollback(|x| Ok::<_, (u32, ())>(x as i32), |y| y as u32)
+            .unwrap();
+        assert_eq!(v, (0..100).collect::<Vec<i32>>());
+    }
+
+    #[test]
+    fn rollback_vec_panic_message() {
+        let e = catch_unwind(|| {
+                vec![1u32].try_map_in_place_with_rollback(|x| Ok::<_, (u32, ())>(x as u16),
+                                                          |y| y as u32)
+            })
+            .unwrap_err();
+
+        assert_eq!(e.downcast_ref::<String>().map(|s| &**s),
+                   Some("try_map_in_place_with_rollback(Vec<A>): Size of A must be equal to size \
+                         of B (A = u32, 4 bytes, align 4; B = u16, 2 bytes, align 2)"));
+    }
+
+    #[test]
+    fn rollback_vec_undo_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (1..6).map(|x| DROPS.track(x as u32)).collect();
+
+        assert!(catch_unwind(|| {
+            let f = |x: Tracked<u32>| {
+                if x.0 == 4 {
+                    return Err((x, ()));
+                }
+
+                let y = DROPS.track(-(x.0 as i32));
+                mem::forget(x);
+                Ok(y)
+            };
+            let undo = |y: Tracked<i32>| {
+                if y.0 == -2 {
+                    panic!();
+                }
+
+                let x = DROPS.track(-y.0 as u32);
+                mem::forget(y);
+                x
+            };
+
+            v.try_map_in_place_with_rollback(f, undo)
+        }).is_err());
+
+        DROPS.assert_drops(&[// panic while undoing here
+                             "-2",
+                             // drop elements that are still mapped
+                             "-1",
+                             // drop restored and unprocessed elements
+                             "3",
+                             "4",
+                             "5"]);
+    }
+
+    #[test]
+    fn collect_errors_vec() {
+        let v: Vec<&str> = vec!["1", "2", "3"];
+
+        let bp = v.as_ptr() as *const ();
+        let v: Vec<u64> = v.map_in_place_collect_errors(|s| s.parse()).unwrap();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn collect_errors_vec_indices() {
+        let v: Vec<&str> = vec!["1", "x", "3", "", "5", "y"];
+        let errors = v.map_in_place_collect_errors(|s| s.parse::<u64>().map_err(|_| s))
+            .unwrap_err();
+
+        assert_eq!(errors, vec![(1, "x"), (3, ""), (5, "y")]);
+
+        let v: Vec<&str> = vec!["1", "2", "x"];
+        let errors = v.map_in_place_collect_errors(|s| s.parse::<u64>().map_err(|_| s))
+            .unwrap_err();
+
+        assert_eq!(errors, vec![(2, "x")]);
+    }
+
+    #[test]
+    fn collect_errors_vec_zst() {
+        let v = vec![1u32, 2, 3, 4];
+        let errors = v.map_in_place_collect_errors(|x| if x % 2 == 0 { Err(x) } else { Ok(()) })
+            .unwrap_err();
+
+        assert_eq!(errors, vec![(1, 2), (3, 4)]);
+    }
+
+    #[test]
+    fn collect_errors_vec_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (1..6).map(|x| DROPS.track(x as u32)).collect();
+        let errors = v.map_in_place_collect_errors(|x| {
+                if x.0 % 2 == 0 { Err(x.0) } else { Ok(DROPS.track(-(x.0 as i32))) }
+            })
+            .unwrap_err();
+
+        assert_eq!(errors, vec![(1, 2), (3, 4)]);
+
+        DROPS.assert_drops(&["1",
+                             // first error here
+                             "2",
+                             // drop mapped elements
+                             "-1",
+                             // map the rest
+                             "3",
+                             "-3",
+                             "4",
+                             "5",
+                             "-5"]);
+    }
+
+    #[test]
+    fn collect_errors_vec_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (0..5).map(|x| DROPS.track(x as u32)).collect();
+
+        assert!(catch_unwind(|| {
+            v.map_in_place_collect_errors(|x| match x.0 {
+                1 => Err(()),
+                3 => panic!(),
+                v => Ok(v),
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&[// consume Xs
+                             "0",
+                             "1",
+                             "2",
+                             // panic here
+                             "3",
+                             // drop remaining unprocessed elements
+                             "4"]);
+    }
+
+    #[test]
+    fn until_vec() {
+        let v: Vec<u32> = vec![1, 2, 0xff, 4, 5];
+
+        let bp = v.as_ptr() as *const ();
+        let (mapped, rest) = v.map_in_place_until(|x| {
+            if x == 0xff {
+                ControlFlow::Break("v2")
+            } else {
+                ControlFlow::Continue(x as f32)
+            }
+        });
+        let ap = mapped.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // prefix still at same memory addr
+        assert_eq!(mapped, vec![1.0, 2.0]);
+        assert_eq!(rest, Some(("v2", vec![4, 5])));
+    }
+
+    #[test]
+    fn until_vec_no_break() {
+        let v: Vec<u32> = vec![1, 2];
+        let (mapped, rest) = v.map_in_place_until(|x| ControlFlow::<(), u32>::Continue(x * 2));
+
+        assert_eq!(mapped, vec![2, 4]);
+        assert!(rest.is_none());
+    }
+
+    #[test]
+    fn until_vec_panic_while_breaking() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (1..5).map(|x| DROPS.track(x as u32)).collect();
+
+        assert!(catch_unwind(|| {
+            v.map_in_place_until(|x| {
+                if x.0 == 3 {
+                    let c = DROPS.track(x.0 + 10);
+
+                    if c.0 == 13 {
+                        panic!();
+                    }
+
+                    ControlFlow::Break(c)
+                } else {
+                    ControlFlow::Continue(DROPS.track(-(x.0 as i32)))
+                }
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&["1",
+                             "2",
+                             // panic here, dropping the break value and the consumed element
+                             "13",
+                             "3",
+                             // drop mapped elements
+                             "-1",
+                             "-2",
+                             // drop remaining unprocessed elements
+                             "4"]);
+    }
+
+    #[test]
+    fn recycle_vec() {
+        let v: Vec<String> = vec!["a".into(), "b".into()];
+
+        let bp = v.as_ptr() as *const ();
+        let cap = v.capacity();
+        let v: Vec<[usize; 2]> = v.recycle_in_place();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert!(v.is_empty());
+        assert_eq!(v.capacity(), cap * 3 / 2);
+    }
+
+    #[test]
+    fn recycle_vec_larger_b() {
+        let v: Vec<u32> = Vec::with_capacity(8);
+
+        let bp = v.as_ptr() as *const ();
+        let v: Vec<[u32; 3]> = v.recycle_in_place();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v.capacity(), 2);
+    }
+
+    #[test]
+    fn recycle_vec_too_small() {
+        let v: Vec<u32> = Vec::with_capacity(1);
+        let v: Vec<[u32; 2]> = v.recycle_in_place();
+
+        assert_eq!(v.capacity(), 0);
+    }
+
+    #[test]
+    fn recycle_vec_different_align() {
+        let v: Vec<u64> = vec![1, 2];
+
+        let bp = v.as_ptr() as *const ();
+        let v: Vec<u8> = v.recycle_in_place();
+        let ap = v.as_ptr() as *const ();
+
+        assert_ne!(bp, ap); // freed
+        assert_eq!(v.capacity(), 0);
+    }
+
+    #[test]
+    fn recycle_vec_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v = vec![DROPS.track(0u32), DROPS.track(1)];
+        let _: Vec<u32> = v.recycle_in_place();
+
+        DROPS.assert_drops(&["0", "1"]);
+    }
+
+    #[test]
+    fn convert_vec() {
+        #[derive(Debug, PartialEq)]
+        struct UserId(u32);
+
+        impl From<u32> for UserId {
+            fn from(x: u32) -> Self {
+                UserId(x)
+            }
+        }
+
+        let v: Vec<u32> = vec![1, 2, 3];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.convert_in_place::<UserId>();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![UserId(1), UserId(2), UserId(3)]);
+    }
+
+    #[test]
+    fn convert_vec_shrinking() {
+        #[derive(Debug, PartialEq)]
+        struct First(u16);
+
+        impl From<[u16; 3]> for First {
+            fn from(x: [u16; 3]) -> Self {
+                First(x[0])
+            }
+        }
+
+        let mut v: Vec<[u16; 3]> = Vec::with_capacity(3);
+        v.extend([[1, 2, 3], [4, 5, 6]]);
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.convert_in_place::<First>();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![First(1), First(4)]);
+        assert_eq!(v.capacity(), 9);
+    }
+
+    #[test]
+    fn convert_vec_zst() {
+        struct Unit;
+
+        impl From<u32> for Unit {
+            fn from(_: u32) -> Self {
+                Unit
+            }
+        }
+
+        let v = vec![1u32, 2].convert_in_place::<Unit>();
+
+        assert_eq!(v.len(), 2);
+    }
+
+    #[test]
+    fn try_convert_vec() {
+        let v: Vec<i64> = vec![1, 2, 3];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.try_convert_in_place::<NonZeroI64>().unwrap();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v.iter().map(|x| x.get()).collect::<Vec<_>>(), vec![1, 2, 3]);
+
+        assert!(vec![1i64, 0, 3].try_convert_in_place::<NonZeroI64>().is_err());
+    }
+
+    #[test]
+    fn try_convert_vec_error_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        // no `Debug` bound on the error
+        struct Odd;
+
+        #[allow(dead_code)]
+        struct Even(Tracked<i32>);
+
+        impl TryFrom<Tracked<u32>> for Even {
+            type Error = Odd;
+
+            fn try_from(x: Tracked<u32>) -> Result<Even, Odd> {
+                if x.0 % 2 == 1 {
+                    Err(Odd)
+                } else {
+                    Ok(Even(DROPS.track(-(x.0 as i32))))
+                }
+            }
+        }
+
+        let v: Vec<_> = [2, 4, 5, 6].iter().map(|&x| DROPS.track(x as u32)).collect();
+
+        assert!(v.try_convert_in_place::<Even>().is_err());
+
+        DROPS.assert_drops(&[// consume Xs
+                             "2",
+                             "4",
+                             // fail here
+                             "5",
+                             // drop converted elements
+                             "-2",
+                             "-4",
+                             // drop remaining unprocessed elements
+                             "6"]);
+    }
+
+    #[test]
+    fn checked_vec() {
+        let v: Vec<[u32; 2]> = vec![[1, 2], [3, 4]];
+
+        let bp = v.as_ptr() as *const ();
+        let v: Vec<u32> = v.checked_map_in_place(|[a, b]| a + b).unwrap();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![3, 7]);
+    }
+
+    #[test]
+    fn checked_vec_errors() {
+        let mut called = false;
+
+        let v: Vec<u32> = vec![1, 2, 3];
+        let bp = v.as_ptr() as *const ();
+        let (v, e) = v.checked_map_in_place(|x| {
+                called = true;
+                [x; 2]
+            })
+            .unwrap_err();
+        assert_eq!(bp, v.as_ptr() as *const ());
+        assert_eq!(v, vec![1, 2, 3]);
+        assert_eq!(e, MapError::SizeIncrease);
+
+        assert!(!called);
 
-    #[inline]
-    fn map_in_place<F>(self, mut f: F) -> Self::Output
-        where F: FnMut(A) -> B
-    {
-        let ptr = Box::into_raw(self);
+        // a differently aligned `B` gets a new allocation, as in `map_in_place_checked`
+        let v: Vec<[u8; 4]> = vec![[1, 0, 0, 0]];
+        let v = v.checked_map_in_place(u32::from_le_bytes).unwrap();
+        assert_eq!(v, vec![1]);
 
-        unsafe {
-            let result = f(ptr::read(ptr));
-            let ptr = ptr as *mut B;
+        // and so does any `B` if `A` is zero-sized
+        let v = vec![(); 3].checked_map_in_place(|()| 1u32).unwrap();
+        assert_eq!(v, vec![1, 1, 1]);
+    }
 
-            ptr::write(ptr, result);
-            Box::from_raw(ptr)
-        }
+    #[test]
+    fn checked_vec_awkward_capacity() {
+        let mut v: Vec<[u32; 3]> = Vec::with_capacity(3);
+        v.push([1, 2, 3]);
+
+        let v = v.checked_map_in_place(|[x, y, _]| [x, y]).unwrap();
+        assert_eq!(v, vec![[1, 2]]);
+        assert_eq!(v.capacity(), 4);
     }
-}
 
-impl<A, B> MapInPlace<A, B> for Vec<A> {
-    type Output = Vec<B>;
+    #[test]
+    fn map_in_place_checked_vec() {
+        let v: Vec<u32> = vec![1, 2, 3];
 
-    #[inline]
-    fn map_in_place<F>(self, mut f: F) -> Self::Output
-        where F: FnMut(A) -> B
-    {
-        let a_size = mem::size_of::<A>();
-        let b_size = mem::size_of::<B>();
-        let ptr_a = self.as_ptr();
-        let ptr_b = ptr_a as *mut B;
-        let len = self.len();
+        let bp = v.as_ptr() as *const ();
+        let v = v.map_in_place_checked(|x| x as i32 - 1).unwrap();
+        let ap = v.as_ptr() as *const ();
 
-        if b_size == 0 {
-            // doesn't preserve address invariant if a_size != 0
-            let mut v = Vec::with_capacity(0);
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![0, 1, 2]);
+    }
 
-            for e in self.into_iter() {
-                v.push(f(e));
-            }
+    #[test]
+    fn map_in_place_checked_errors() {
+        let v: Box<[u32]> = vec![1, 2, 3].into_boxed_slice();
+        let bp = v.as_ptr() as *const ();
 
-            v
-        } else {
-            let cap = if a_size == b_size {
-                self.capacity()
-            } else if a_size > b_size {
-                // nA * bytes/A = nbytes
-                // nbytes / bytes/B = nbytes * B/bytes = nB
-                // (assuming bytes/B divides evenly into nbytes)
-                let n_bytes = self.capacity().checked_mul(a_size).unwrap();
-                // TODO: don't require the divisibility constraint
-                assert_eq!(n_bytes % b_size, 0);
-                n_bytes / b_size
-            } else {
-                panic!("map_in_place(Vec<A>): Size of A must be greater than or equal to size of B")
-            };
+        let e = v.map_in_place_checked(|x| x as u64).unwrap_err();
+        assert_eq!(e.reason(), MapError::SizeIncrease);
+        assert_eq!(e.to_string(),
+                   "map_in_place(Box<[A]>): Size of A must be greater than or equal to size of B \
+                    (A = u32, 4 bytes, align 4; B = u64, 8 bytes, align 8)");
 
-            let mut dropper = VecDropper {
-                idx: 0,
-                owned: self,
-                _marker: PhantomData::<B>,
-            };
+        let v = e.into_inner();
+        assert_eq!(bp, v.as_ptr() as *const ());
+        assert_eq!(&*v, &[1, 2, 3]);
 
-            unsafe {
-                for i in 0..len {
-                    let ptr_a = ptr_a.offset(i as isize);
-                    let ptr_b = ptr_b.offset(i as isize);
+        let v: Vec<u32> = vec![1];
+        let e: Box<dyn Error> = Box::new(v.map_in_place_checked(|x| [x; 2]).unwrap_err());
+        assert!(e.to_string().starts_with("map_in_place(Vec<A>): Size of A must be greater"));
+    }
 
-                    let v = ptr::read(ptr_a);
-                    dropper.idx += 1;
+    #[test]
+    fn map_in_place_panic_message() {
+        // a larger `B` doesn't compile, a differently aligned one panics
+        let e = catch_unwind(|| {
+                let v: Box<[u32]> = vec![1].into_boxed_slice();
+                v.map_in_place(|x| [x as u16; 2])
+            })
+            .unwrap_err();
 
-                    ptr::write(ptr_b, f(v));
-                }
+        assert_eq!(e.downcast_ref::<String>().map(|s| &**s),
+                   Some("map_in_place(Box<[A]>): Alignment of A must be equal to alignment of B \
+                         (A = u32, 4 bytes, align 4; B = [u16; 2], 4 bytes, align 2)"));
+    }
 
-                Vec::from_raw_parts(ptr_b, len, cap)
-            }
+    // the buffer is shrunk to the largest multiple of the size of `B` that fits, so the
+    // capacity always describes the allocation that will be freed
+
+    #[test]
+    fn different_sizes_vec_awkward_capacity() {
+        for &cap in &[1, 2, 3, 5, 7, 11, 13, 97] {
+            let mut v: Vec<String> = Vec::with_capacity(cap);
+            v.push("a".into());
+
+            let v: Vec<Box<str>> = v.map_in_place(String::into_boxed_str);
+
+            assert_eq!(v, vec!["a".into()]);
+            assert_eq!(v.capacity(), cap * 24 / 16);
         }
     }
-}
 
-struct VecDropper<A, B> {
-    idx: usize,
-    owned: Vec<A>,
-    _marker: PhantomData<B>,
-}
+    #[test]
+    fn different_sizes_vec_capacity_much_larger_than_len() {
+        let mut v: Vec<[u64; 3]> = Vec::with_capacity(1001);
+        v.extend(&[[1, 2, 3], [4, 5, 6]]);
 
-impl<A, B> Drop for VecDropper<A, B> {
-    #[inline]
-    fn drop(&mut self) {
-        let owned = &mut self.owned;
-        let idx = self.idx;
-        let len = owned.len();
-        let ptr_a = owned.as_mut_ptr();
-        let ptr_b = ptr_a as *mut B;
+        let v = v.map_in_place(|[x, y, z]| [x + y + z, 0]);
 
-        unsafe {
-            owned.set_len(0);
+        assert_eq!(v, vec![[6, 0], [15, 0]]);
+        assert_eq!(v.capacity(), 1501);
+    }
 
-            if idx != len {
-                // panicked; manual cleanup needed
-                for i in 0..(idx - 1) {
-                    ptr::drop_in_place(ptr_b.offset(i as isize));
-                }
+    #[test]
+    fn different_sizes_vec_awkward_capacity_panic_drop() {
+        let mut v: Vec<[u64; 3]> = Vec::with_capacity(7);
+        v.extend(&[[1, 2, 3], [4, 5, 6]]);
 
-                for i in idx..len {
-                    ptr::drop_in_place(ptr_a.offset(i as isize));
-                }
-            } else {
-                // everything went well, no cleanup required
-                mem::forget(mem::replace(owned, Vec::with_capacity(0)));
-            }
-        }
+        assert!(catch_unwind(|| v.map_in_place(|[x, _, _]| -> [u64; 2] { panic!("{}", x) }))
+            .is_err());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::MapInPlace;
+    #[test]
+    fn checked_vec_zst() {
+        let v = vec![1u32, 2, 3];
+        let v = v.checked_map_in_place(|_| ()).unwrap();
 
-    use std::mem;
-    use std::sync::Mutex;
-    use std::panic::catch_unwind;
+        assert_eq!(v.len(), 3);
+    }
 
-    macro_rules! box_drop_test {
-        ($name:ident, $xtype:ty, $ytype:ty) => {
-            #[test]
-            fn $name() {
-                lazy_static! {
-                    static ref DROPS: Mutex<Vec<String>> = Mutex::new(vec![]);
-                }
-                
-                #[derive(Debug, PartialEq, Clone)]
-                struct X($xtype);
-                
-                impl Drop for X {
-                    fn drop(&mut self) {
-                        DROPS.lock().unwrap().push(format!("X({})", self.0));
-                    }
-                }
-                
-                #[derive(Debug, PartialEq, Clone)]
-                struct Y($ytype);
-                
-                impl Drop for Y {
-                    fn drop(&mut self) {
-                        DROPS.lock().unwrap().push(format!("Y({})", self.0));
-                    }
-                }
-                
-                let b = Box::new(X(7));
-                
-                let bp = Box::into_raw(b);
-                let b = unsafe { Box::from_raw(bp) };
-                let bp = bp as *const ();
-                
-                let b = b.map_in_place(|X(v)| Y(v as $ytype));
-                
-                {
-                    let drops = DROPS.lock().unwrap().clone();
-                    assert_eq!(drops, vec!["X(7)"]);
-                }
-                
-                let ap = Box::into_raw(b);
-                let b = unsafe { Box::from_raw(ap) };
-                let ap = ap as *const ();
-                
-                assert_eq!(bp, ap);
-                
-                mem::drop(b);
-                
-                {
-                    let drops = DROPS.lock().unwrap().clone();
-                    assert_eq!(drops, vec!["X(7)", "Y(7)"]);
-                }
-            }
-        }
+    #[test]
+    fn checked_capacity_overflow() {
+        assert_eq!(super::checked_capacity::<[u8; 4], u8>(usize::MAX / 2),
+                   Err(MapError::CapacityOverflow));
     }
 
-    macro_rules! box_panic_drop_test {
-        ($name:ident, $xtype:ty, $ytype:ty) => {
-            #[test]
-            fn $name() {
-                lazy_static! {
-                    static ref DROPS: Mutex<Vec<String>> = Mutex::new(vec![]);
-                }
-                
-                #[derive(Debug, PartialEq, Clone)]
-                struct X($xtype);
-                
-                impl Drop for X {
-                    fn drop(&mut self) {
-                        DROPS.lock().unwrap().push(format!("X({})", self.0));
-                    }
-                }
-                
-                #[derive(Debug, PartialEq, Clone)]
-                struct Y($ytype);
-                
-                impl Drop for Y {
-                    fn drop(&mut self) {
-                        DROPS.lock().unwrap().push(format!("Y({})", self.0));
-                    }
-                }
-                
-                let b = Box::new(X(7));
-                
-                let bp = Box::into_raw(b);
-                let b = unsafe { Box::from_raw(bp) };
-                
-                match catch_unwind(|| b.map_in_place(|X(_)| {
-                    panic!();
-                })) {
-                    Ok(_) => unreachable!(),
-                    Err(_) => {
-                        let drops = DROPS.lock().unwrap().clone();
-                        assert_eq!(drops, vec!["X(7)"]);
-                    }
-                }
-            }
-        }
+    #[test]
+    fn checked_capacity_overflow_4096() {
+        // reachable for real `Vec`s on 32-bit targets
+        let cap = usize::MAX / 4096 + 1;
+
+        assert_eq!(super::checked_capacity::<[u8; 4096], [u8; 1024]>(cap),
+                   Err(MapError::CapacityOverflow));
+        assert_eq!(super::checked_capacity::<[u8; 4096], [u8; 1024]>(cap - 1), Ok(()));
     }
 
-    box_drop_test!(box_drop_same_sizes, u64, i64);
-    box_drop_test!(box_drop_diff_sizes, u64, i32);
-    box_panic_drop_test!(box_panic_drop_same_sizes, u64, i64);
-    box_panic_drop_test!(box_panic_drop_diff_sizes, u64, i32);
+    // the path taken with `panic = "abort"`, which the tests can't be built with
+    #[test]
+    fn map_elements_unguarded() {
+        let v = vec![String::from("a"), "bc".into()];
+        let cap = v.capacity();
+
+        let bp = v.as_ptr() as *const ();
+        let v = unsafe {
+            let ptr_b = super::map_elements_unguarded(v, String::into_boxed_str);
+            super::mapped_vec::<String, Box<str>>(ptr_b, 2, cap)
+        };
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec!["a".into(), "bc".into()]);
+    }
 
     #[test]
-    fn vec_elements_drop() {
-        lazy_static! {
-            static ref DROPS: Mutex<Vec<String>> = Mutex::new(vec![]);
-        }
+    fn checked_capacity_too_large() {
+        // a `Vec` can't hold more than `isize::MAX` elements; that limit is stood in for by
+        // the length of the `B`s the buffer below could hold
+        let v: Vec<[u8; 2]> = Vec::with_capacity(1000);
+        let cap = v.capacity();
 
-        #[derive(Debug, PartialEq, Clone)]
-        struct X(usize);
+        assert_eq!(super::checked_capacity_within::<[u8; 2], u8>(cap, cap * 2 - 1),
+                   Err(MapError::CapacityTooLarge));
+        assert_eq!(super::checked_capacity_within::<[u8; 2], u8>(cap, cap * 2), Ok(()));
+        assert_eq!(super::checked_capacity_within::<[u8; 2], [u8; 2]>(cap, cap), Ok(()));
+    }
 
-        impl Drop for X {
-            fn drop(&mut self) {
-                DROPS.lock().unwrap().push(format!("X({})", self.0));
-            }
-        }
+    #[test]
+    fn check_capacity_panic_message() {
+        let e = catch_unwind(|| {
+                super::check_capacity::<[u8; 4096], [u8; 1024]>(usize::MAX, "map_in_place(Vec<A>)")
+            })
+            .unwrap_err();
 
-        #[derive(Debug, PartialEq, Clone)]
-        struct Y(usize);
+        let msg = e.downcast_ref::<String>().unwrap();
+        assert_eq!(*msg,
+                   format!("map_in_place(Vec<A>): Size of the buffer overflows usize (A = [u8; \
+                            4096], 4096 bytes, align 1; B = [u8; 1024], 1024 bytes, align 1; \
+                            capacity {})",
+                           usize::MAX));
 
-        impl Drop for Y {
-            fn drop(&mut self) {
-                DROPS.lock().unwrap().push(format!("Y({})", self.0));
-            }
-        }
+        let e = catch_unwind(|| super::check_capacity::<u16, u32>(3, "map_in_place(Vec<A>)"))
+            .unwrap_err();
 
-        assert_eq!(mem::size_of::<X>(), mem::size_of::<Y>());
+        let msg = e.downcast_ref::<String>().unwrap();
+        assert_eq!(*msg,
+                   "map_in_place(Vec<A>): Size of A must be greater than or equal to size of B \
+                    (A = u16, 2 bytes, align 2; B = u32, 4 bytes, align 4; capacity 3)");
+    }
 
-        let v = vec![X(0), X(1), X(2), X(3)];
+    #[test]
+    fn grow_vec() {
+        let mut v: Vec<u16> = Vec::with_capacity(8);
+        v.extend([1, 2, 3]);
 
         let bp = v.as_ptr() as *const ();
-        let v = v.map_in_place(|X(v)| Y(v));
+        let v: Vec<[u16; 2]> = v.map_in_place_grow(|x| [x, x * 2]).unwrap();
+        let ap = v.as_ptr() as *const ();
 
-        {
-            let drops = DROPS.lock().unwrap().clone();
-            assert_eq!(drops, vec!["X(0)", "X(1)", "X(2)", "X(3)"]);
-        }
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![[1, 2], [2, 4], [3, 6]]);
+        assert_eq!(v.capacity(), 4);
+    }
+
+    #[test]
+    fn grow_vec_shrinking_capacity() {
+        let mut v: Vec<[u16; 2]> = Vec::with_capacity(5);
+        v.extend([[1, 2], [3, 4], [5, 6]]);
 
+        let bp = v.as_ptr() as *const ();
+        let v: Vec<[u16; 3]> = v.map_in_place_grow(|[a, b]| [a, b, a + b]).unwrap();
         let ap = v.as_ptr() as *const ();
-        let expected = vec![Y(0), Y(1), Y(2), Y(3)];
 
         assert_eq!(bp, ap); // still at same memory addr
-        assert_eq!(v, expected);
+        assert_eq!(v, vec![[1, 2, 3], [3, 4, 7], [5, 6, 11]]);
+        assert_eq!(v.capacity(), 3);
+    }
 
-        mem::drop(v);
+    #[test]
+    fn grow_vec_full() {
+        let mut v: Vec<u16> = Vec::with_capacity(4);
+        v.extend([1, 2, 3]);
 
-        {
-            let drops = DROPS.lock().unwrap().clone();
-            assert_eq!(drops,
-                       vec!["X(0)", "X(1)", "X(2)", "X(3)", "Y(0)", "Y(1)", "Y(2)", "Y(3)"]);
-        }
+        let v = v.map_in_place_grow(|x| -> [u16; 2] { [x; 2] }).unwrap_err();
 
-        mem::drop(expected);
+        assert_eq!(v, vec![1, 2, 3]);
+
+        let v: Vec<[u8; 2]> = vec![[1, 2]];
+        let v = v.map_in_place_grow(|_| -> u16 { unreachable!() }).unwrap_err();
+
+        assert_eq!(v, vec![[1, 2]]);
     }
 
-    macro_rules! vec_panic_drop_test {
-        ($name:ident, $ytype:ty) => {
-            #[test]
-            fn $name() {
-                lazy_static! {
-                    static ref DROPS: Mutex<Vec<String>> = Mutex::new(vec![]);
-                }
-                
-                #[derive(Debug, PartialEq, Clone)]
-                struct X(char);
-                
-                impl Drop for X {
-                    fn drop(&mut self) {
-                        DROPS.lock().unwrap().push(format!("X({})", self.0));
-                    }
-                }
-                
-                #[derive(Debug, PartialEq, Clone)]
-                struct Y($ytype);
-                
-                impl Drop for Y {
-                    fn drop(&mut self) {
-                        DROPS.lock().unwrap().push(format!("Y({})", self.0));
-                    }
-                }
-                
-                let v = vec![X('a'), X('b'), X('c'), X('d'), X('e')];
-                
-                match catch_unwind(|| {
-                    v.map_in_place(|X(v)| {
-                        if v == 'c' {
-                            panic!();
-                        } else {
-                            Y(v as $ytype)
-                        }
-                    })
-                }) {
-                    Ok(_) => unreachable!(),
-                    Err(_) => {
-                        let drops = DROPS.lock().unwrap().clone();
-                        assert_eq!(drops,
-                                   vec![// consume Xs
-                                       "X(a)",
-                                       "X(b)",
-                                       "X(c)",
-                                       // panic here
-                                       // drop generated Ys
-                                       "Y(97)",
-                                       "Y(98)",
-                                       // drop remaining unprocessed Xs
-                                       "X(d)",
-                                       "X(e)"]);
-                    }
+    #[test]
+    fn grow_vec_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let mut v = Vec::with_capacity(8);
+        v.extend((1..5).map(|x| DROPS.track(x as u32)));
+
+        assert!(catch_unwind(|| {
+            // a `Tracked<(i32, u64)>` is larger than a `Tracked<u32>`
+            v.map_in_place_grow(|x| {
+                if x.0 == 2 {
+                    panic!();
                 }
-            }
-        }
-    }
 
-    vec_panic_drop_test!(vec_same_size_panic_drop, u32);
-    vec_panic_drop_test!(vec_diff_size_panic_drop, u16);
+                DROPS.track((-(x.0 as i32), 0u64))
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&[// consume Xs
+                             "4",
+                             "3",
+                             // panic here
+                             "2",
+                             // drop remaining unprocessed elements
+                             "1",
+                             // drop mapped elements
+                             "(-3, 0)",
+                             "(-4, 0)"]);
+    }
 
     #[test]
-    fn same_size_vec() {
-        let v = vec![0, 1, 2, 3];
+    fn shrink_vec() {
+        let mut v: Vec<[u64; 8]> = Vec::with_capacity(10);
+        v.extend([[1; 8], [2; 8], [3; 8]]);
 
         let bp = v.as_ptr() as *const ();
-        let v = v.map_in_place(|x: u32| (x * x) as i32);
+        let v: Vec<u64> = v.map_in_place_shrink(|x| x.iter().sum());
         let ap = v.as_ptr() as *const ();
 
+        // the block can't be shrunk without risking a copy, so it's kept whole
         assert_eq!(bp, ap); // still at same memory addr
-        assert_eq!(v, vec![0, 1, 4, 9]);
+        assert_eq!(v, vec![8, 16, 24]);
+        assert_eq!(v.capacity(), 80);
     }
 
     #[test]
-    fn different_sizes_vec() {
-        let v = vec![0, 1, 2, 3];
+    fn shrink_vec_empty() {
+        let v: Vec<[u64; 2]> = Vec::with_capacity(10);
+        let v: Vec<u64> = v.map_in_place_shrink(|x| x[0]);
+
+        assert_eq!(v.capacity(), 0);
+    }
+
+    #[test]
+    fn or_alloc_vec_in_place() {
+        let v: Vec<[u32; 3]> = vec![[1, 2, 3], [4, 5, 6], [7, 8, 9]];
 
         let bp = v.as_ptr() as *const ();
-        let v = v.map_in_place(|x: u32| (x * x) as i16);
+        let v: Vec<[u32; 2]> = v.map_in_place_or_alloc(|[a, b, c]| [a + b, c]);
         let ap = v.as_ptr() as *const ();
 
         assert_eq!(bp, ap); // still at same memory addr
-        assert_eq!(v, vec![0, 1, 4, 9]);
+        assert_eq!(v, vec![[3, 3], [9, 6], [15, 9]]);
+        assert_eq!(v.capacity(), 4);
     }
 
     #[test]
-    fn both_zst_vec() {
-        #[derive(Debug)]
-        struct Zst;
-
-        let v = vec![(), (), (), ()];
+    fn or_alloc_vec_larger_b() {
+        let v: Vec<u32> = vec![1, 2, 3];
 
         let bp = v.as_ptr() as *const ();
-        let v = v.map_in_place(|_| Zst);
+        let v: Vec<[u32; 2]> = v.map_in_place_or_alloc(|x| [x, x * 2]);
         let ap = v.as_ptr() as *const ();
 
-        assert_eq!(bp, ap); // still at same memory addr
+        assert_ne!(bp, ap); // allocated
+        assert_eq!(v, vec![[1, 2], [2, 4], [3, 6]]);
     }
 
     #[test]
-    fn nzst_to_zst_vec() {
-        let v = vec![0, 1, 2, 3];
+    fn or_alloc_vec_different_align() {
+        let v: Vec<[u8; 4]> = vec![[1, 0, 0, 0], [2, 0, 0, 0]];
 
         let bp = v.as_ptr() as *const ();
-        let v = v.map_in_place(|_: u32| ());
+        let v: Vec<u32> = v.map_in_place_or_alloc(u32::from_le_bytes);
         let ap = v.as_ptr() as *const ();
 
-        assert!(bp != ap); // -- NOT -- still at same memory addr
+        assert_ne!(bp, ap); // allocated
+        assert_eq!(v, vec![1, 2]);
+    }
+
+    #[test]
+    fn or_alloc_vec_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (1..4).map(|x| DROPS.track(x as u32)).collect();
+
+        assert!(catch_unwind(|| {
+            v.map_in_place_or_alloc(|x| {
+                if x.0 == 2 {
+                    panic!();
+                }
+
+                DROPS.track(-(x.0 as i32))
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&[// consume Xs
+                             "1",
+                             // panic here
+                             "2",
+                             // drop mapped elements
+                             "-1",
+                             // drop remaining unprocessed elements
+                             "3"]);
+    }
+
+    #[test]
+    fn same_size_boxed_slice() {
+        let b: Box<[u32]> = vec![0, 1, 2, 3].into_boxed_slice();
+
+        let bp = b.as_ptr() as *const ();
+        let b = b.map_in_place(|x| (x * x) as i32);
+        let ap = b.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(&*b, &[0, 1, 4, 9]);
+    }
+
+    #[test]
+    fn different_sizes_boxed_slice() {
+        let b: Box<[(u32, u32)]> = vec![(0, 1), (2, 3), (4, 5)].into_boxed_slice();
+
+        let b = b.map_in_place(|(x, y)| x + y);
+
+        assert_eq!(&*b, &[1, 5, 9]);
+    }
+
+    #[test]
+    fn empty_boxed_slice() {
+        let b: Box<[u64]> = Vec::new().into_boxed_slice();
+        let b = b.map_in_place(|x| x as u32);
+
+        assert!(b.is_empty());
+    }
+
+    #[test]
+    fn zst_boxed_slice() {
+        let b: Box<[()]> = vec![(); 3].into_boxed_slice();
+        let b = b.map_in_place_checked(|()| 7u32).unwrap();
+
+        assert_eq!(&*b, &[7, 7, 7]);
     }
 
     #[test]
     #[should_panic]
-    fn zst_to_nzst_vec() {
-        let v = vec![(), (), (), ()];
+    fn different_align_boxed_slice() {
+        let b: Box<[[u8; 4]]> = vec![[0; 4], [1; 4]].into_boxed_slice();
+        let _ = b.map_in_place(u32::from_ne_bytes);
+    }
+
+    #[test]
+    fn boxed_slice_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let b: Box<[_]> = (1..5).map(|x| DROPS.track(x as u64)).collect();
+
+        assert!(catch_unwind(|| {
+            b.map_in_place(|x| {
+                if x.0 == 2 {
+                    panic!();
+                } else {
+                    DROPS.track(-(x.0 as i64))
+                }
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&["1", "2", "-1", "3", "4"]);
+    }
 
+    #[test]
+    fn owned_cow() {
+        let v = vec![0u32, 1, 2, 3];
         let bp = v.as_ptr() as *const ();
-        let v = v.map_in_place(|_| 0usize);
+
+        let c: Cow<[u32]> = Cow::Owned(v);
+        let m = c.map_in_place(|x| (x * x) as i32);
+
+        assert!(m.is_in_place());
+
+        let v = m.into_inner();
         let ap = v.as_ptr() as *const ();
 
         assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![0, 1, 4, 9]);
+    }
+
+    #[test]
+    fn borrowed_cow() {
+        let s = [0u32, 1, 2, 3];
+
+        let c: Cow<[u32]> = Cow::Borrowed(&s);
+        let m = c.map_in_place(|x| (x * x) as i32);
+
+        assert_eq!(m, Mapped::Allocated(vec![0, 1, 4, 9]));
+        assert_eq!(s, [0, 1, 2, 3]);
+    }
+
+    #[test]
+    #[cfg(feature = "debug-poison")]
+    fn debug_poison_vec() {
+        let v = vec![[1u32, 1], [2, 2], [3, 3], [4, 4]];
+        let p = v.as_ptr() as *const u8;
+        let bytes = |start: usize, len: usize| unsafe {
+            ::std::slice::from_raw_parts(p.add(start), len).to_vec()
+        };
+
+        let mut i = 0;
+        let v = v.map_in_place(|[x, _]| {
+            // moved out of, and its `B` not written yet
+            assert_eq!(bytes(i * 8, 8), [0xDE; 8]);
+            i += 1;
+            x
+        });
+
+        assert_eq!(v, [1, 2, 3, 4]);
+        assert_eq!(v.as_ptr() as *const u8, p);
+        // where the `A`s were, past the `B`s
+        assert_eq!(bytes(16, 16), [0xDE; 16]);
+    }
+
+    #[test]
+    #[cfg(feature = "debug-poison")]
+    fn debug_poison_vec_drop() {
+        // 48 bytes, so that the buffer holds exactly 3 `Box<str>`s and isn't reallocated
+        let mut v = Vec::with_capacity(2);
+        v.extend([String::from("a"), String::from("b")]);
+        let p = v.as_ptr() as *const u8;
+        let bytes = |start: usize, len: usize| unsafe {
+            ::std::slice::from_raw_parts(p.add(start), len).to_vec()
+        };
+
+        let mut i = 0;
+        let v = v.map_in_place(|s| {
+            assert_eq!(bytes(i * 24, 24), [0xDE; 24]);
+            i += 1;
+            s.into_boxed_str()
+        });
+
+        assert_eq!(v, ["a".into(), "b".into()]);
+        assert_eq!(v.as_ptr() as *const u8, p);
+        assert_eq!(bytes(32, 16), [0xDE; 16]);
     }
 }
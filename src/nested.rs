@@ -0,0 +1,330 @@
+//! Impls for containers of containers, reusing the allocations at both levels.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::mem;
+
+use {expect_layout, map_elements, shrink_allocation};
+use {MapBoxedInPlace, MapInPlace, MapNestedInPlace, ShrinkWrapInPlace};
+
+impl<A, B> MapNestedInPlace<A, B> for Vec<Vec<A>> {
+    type Output = Vec<Vec<B>>;
+
+    /// Each inner `Vec` is mapped in place following the usual rules, and then stored in the
+    /// slot of the outer `Vec` it came from (`Vec<A>` and `Vec<B>` are always the same size).
+    ///
+    /// If `f` panics, the inner `Vec` being mapped is cleaned up as usual, the ones already
+    /// mapped are dropped as `Vec<B>`s and the rest as `Vec<A>`s.
+    #[inline]
+    fn map_nested_in_place<F>(self, mut f: F) -> Self::Output
+        where F: FnMut(A) -> B
+    {
+        self.map_in_place(|inner| inner.map_in_place(&mut f))
+    }
+}
+
+/// Maps the value in `b`, reusing its allocation if `A` and `B` have the same layout, or
+/// boxing the result anew otherwise.
+#[inline]
+fn map_box<A, B, F>(b: Box<A>, f: &mut F) -> Box<B>
+    where F: FnMut(A) -> B
+{
+    if mem::size_of::<A>() == mem::size_of::<B>() && mem::align_of::<A>() == mem::align_of::<B>() {
+        expect_layout(b.map_in_place_checked(f))
+    } else {
+        Box::new(f(*b))
+    }
+}
+
+impl<A, B> MapBoxedInPlace<A, B> for Vec<Box<A>> {
+    type Output = Vec<Box<B>>;
+
+    /// The outer `Vec` is always reused, since all boxes are the same size. Each box is reused
+    /// if `A` and `B` have the same size and alignment; otherwise (whether `B` is smaller,
+    /// larger or differently aligned) the value is moved out, its box freed, and the result
+    /// put in a new box, so this never panics because of the layout of `A` and `B`.
+    ///
+    /// If `f` panics, the allocation of the box being mapped is freed, the boxes already
+    /// mapped are dropped as `Box<B>`s and the rest as `Box<A>`s.
+    #[inline]
+    fn map_boxed_in_place<F>(self, mut f: F) -> Self::Output
+        where F: FnMut(A) -> B
+    {
+        self.map_in_place(|b| map_box(b, &mut f))
+    }
+}
+
+impl<A, B> MapBoxedInPlace<A, B> for Vec<Option<Box<A>>> {
+    type Output = Vec<Option<Box<B>>>;
+
+    /// Like for `Vec<Box<A>>`, with `None`s left as they are (`Option<Box<_>>` is always
+    /// pointer-sized). `f` is only called on the `Some`s.
+    #[inline]
+    fn map_boxed_in_place<F>(self, mut f: F) -> Self::Output
+        where F: FnMut(A) -> B
+    {
+        self.map_in_place(|o| o.map(|b| map_box(b, &mut f)))
+    }
+}
+
+impl<A> ShrinkWrapInPlace for Vec<Vec<A>> {
+    type Output = Vec<Box<[A]>>;
+
+    /// Each inner `Vec` is converted with `into_boxed_slice`, which leaves its buffer alone if
+    /// its capacity is equal to its length, and otherwise shrinks it with `realloc`. Only then
+    /// can the allocator move and copy the elements elsewhere, which for shrinking requests
+    /// most don't do.
+    ///
+    /// The outer `Vec` is reused, its capacity adjusted to the number of (smaller) boxed
+    /// slices it can hold. If its size in bytes doesn't divide evenly, it is also shrunk with
+    /// `realloc`, by less than the size of one boxed slice.
+    #[inline]
+    fn shrink_wrap_in_place(self) -> Self::Output {
+        shrink_wrap(self, Vec::into_boxed_slice)
+    }
+}
+
+impl ShrinkWrapInPlace for Vec<String> {
+    type Output = Vec<Box<str>>;
+
+    /// Each `String` is converted with `into_boxed_str`, and the outer `Vec` reused, in the
+    /// same way as for `Vec<Vec<A>>`.
+    ///
+    /// The reverse conversion can't be done in place, as a `String` is larger than a
+    /// `Box<str>`.
+    #[inline]
+    fn shrink_wrap_in_place(self) -> Self::Output {
+        shrink_wrap(self, String::into_boxed_str)
+    }
+}
+
+/// Maps `v` with `f` from owned buffers to their boxed equivalents, which are smaller but
+/// aligned the same, adjusting the capacity of `v` to match.
+#[inline]
+fn shrink_wrap<A, B>(v: Vec<A>, f: fn(A) -> B) -> Vec<B> {
+    assert!(mem::size_of::<B>() <= mem::size_of::<A>());
+    assert_eq!(mem::align_of::<B>(), mem::align_of::<A>());
+
+    let len = v.len();
+    let cap = v.capacity();
+
+    unsafe {
+        let ptr = map_elements(v, f);
+        let (ptr, cap) = shrink_allocation::<A, B>(ptr, cap);
+
+        Vec::from_raw_parts(ptr, len, cap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {MapBoxedInPlace, MapNestedInPlace, ShrinkWrapInPlace};
+    use testing::DropTracker;
+
+    use std::panic::catch_unwind;
+
+    #[test]
+    fn nested_vec() {
+        let v = vec![vec![0u32, 1], vec![], vec![2, 3, 4]];
+
+        let bp = v.as_ptr() as *const ();
+        let inner_bp: Vec<*const ()> = v.iter().map(|i| i.as_ptr() as *const ()).collect();
+
+        let v = v.map_nested_in_place(|x| (x * x) as i32);
+
+        let ap = v.as_ptr() as *const ();
+        let inner_ap: Vec<*const ()> = v.iter().map(|i| i.as_ptr() as *const ()).collect();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(inner_bp, inner_ap); // all of them
+        assert_eq!(v, vec![vec![0, 1], vec![], vec![4, 9, 16]]);
+    }
+
+    #[test]
+    fn nested_vec_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<Vec<_>> = vec![(1..3).map(|x| DROPS.track(x as u32)).collect(),
+                                  (3..6).map(|x| DROPS.track(x as u32)).collect(),
+                                  vec![DROPS.track(6)]];
+
+        assert!(catch_unwind(|| {
+            v.map_nested_in_place(|x| {
+                if x.0 == 4 {
+                    panic!();
+                } else {
+                    DROPS.track(-(x.0 as i32))
+                }
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&[// consume Xs
+                             "1",
+                             "2",
+                             "3",
+                             "4",
+                             // panic here
+                             // clean up the inner vec being mapped
+                             "-3",
+                             "5",
+                             // drop the mapped inner vecs
+                             "-1",
+                             "-2",
+                             // drop the remaining unprocessed inner vecs
+                             "6"]);
+    }
+
+    #[test]
+    fn boxed_vec() {
+        let v: Vec<Box<u64>> = (0..4).map(Box::new).collect();
+
+        let bp = v.as_ptr() as *const ();
+        let inner_bp: Vec<*const ()> = v.iter().map(|b| &**b as *const u64 as *const ()).collect();
+
+        let v = v.map_boxed_in_place(|x| x as f64);
+
+        let ap = v.as_ptr() as *const ();
+        let inner_ap: Vec<*const ()> = v.iter().map(|b| &**b as *const f64 as *const ()).collect();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(inner_bp, inner_ap); // all of them
+        assert_eq!(v, vec![Box::new(0.0), Box::new(1.0), Box::new(2.0), Box::new(3.0)]);
+    }
+
+    #[test]
+    fn different_sizes_boxed_vec() {
+        let v: Vec<Box<u32>> = (0..4).map(Box::new).collect();
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.map_boxed_in_place(|x| (x as u64) << 32);
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // the boxes are new, but still at same memory addr
+        assert_eq!(v, (0..4).map(|x| Box::new(x << 32)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn boxed_vec_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (1..5).map(|x| Box::new(DROPS.track(x as u32))).collect();
+
+        assert!(catch_unwind(|| {
+            v.map_boxed_in_place(|x| {
+                if x.0 == 3 {
+                    panic!();
+                } else {
+                    DROPS.track(-(x.0 as i32))
+                }
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&["1", "2", "3", "-1", "-2", "4"]);
+    }
+
+    #[test]
+    fn sparse_boxed_vec() {
+        let v: Vec<Option<Box<u64>>> = vec![Some(Box::new(1)), None, None, Some(Box::new(2))];
+
+        let bp = v.as_ptr() as *const ();
+        let inner_bp: Vec<Option<*const ()>> =
+            v.iter().map(|o| o.as_ref().map(|b| &**b as *const u64 as *const ())).collect();
+
+        let mut calls = 0;
+        let v = v.map_boxed_in_place(|x| {
+            calls += 1;
+            -(x as i64)
+        });
+
+        let ap = v.as_ptr() as *const ();
+        let inner_ap: Vec<Option<*const ()>> =
+            v.iter().map(|o| o.as_ref().map(|b| &**b as *const i64 as *const ())).collect();
+
+        assert_eq!(calls, 2);
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(inner_bp, inner_ap); // all of them
+        assert_eq!(v, vec![Some(Box::new(-1)), None, None, Some(Box::new(-2))]);
+    }
+
+    #[test]
+    fn sparse_boxed_vec_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v = vec![None,
+                     Some(Box::new(DROPS.track(1u32))),
+                     None,
+                     Some(Box::new(DROPS.track(3))),
+                     None,
+                     Some(Box::new(DROPS.track(5)))];
+
+        assert!(catch_unwind(|| {
+            v.map_boxed_in_place(|x| {
+                if x.0 == 3 {
+                    panic!();
+                } else {
+                    DROPS.track(-(x.0 as i32))
+                }
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&["1", "3", "-1", "5"]);
+    }
+
+    #[test]
+    fn shrink_wrap_vec() {
+        let mut inner = Vec::with_capacity(16);
+        inner.extend_from_slice(&[3u32, 4, 5]);
+
+        let mut v = Vec::with_capacity(7);
+        v.push(vec![0u32, 1]);
+        v.push(vec![]);
+        v.push(inner);
+
+        let bp = v.as_ptr() as *const ();
+        let first_bp = v[0].as_ptr();
+
+        let v = v.shrink_wrap_in_place();
+
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(first_bp, v[0].as_ptr()); // already tightly sized
+        assert!(v.capacity() >= 7);
+        assert_eq!(v, vec![vec![0, 1].into_boxed_slice(),
+                           vec![].into_boxed_slice(),
+                           vec![3, 4, 5].into_boxed_slice()]);
+    }
+
+    #[test]
+    fn shrink_wrap_vec_awkward_capacities() {
+        for &cap in &[1, 2, 3, 7, 11, 13, 64, 97] {
+            let mut v = Vec::with_capacity(cap);
+            v.push(vec![String::from("a")]);
+
+            let mut v = v.shrink_wrap_in_place();
+
+            assert_eq!(v.len(), 1);
+            assert_eq!(&*v[0], &[String::from("a")]);
+
+            // the capacity must describe the allocation for it to be grown and freed correctly
+            v.extend((0..100).map(|_| vec![String::from("b")].into_boxed_slice()));
+            assert_eq!(v.len(), 101);
+        }
+    }
+
+    #[test]
+    fn shrink_wrap_strings() {
+        let mut v = Vec::with_capacity(7);
+        v.push(String::from("abc"));
+        v.push(String::with_capacity(10));
+        v.push(String::from("def"));
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.shrink_wrap_in_place();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec!["abc".into(), "".into(), "def".into()] as Vec<Box<str>>);
+    }
+}
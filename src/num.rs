@@ -0,0 +1,171 @@
+//! `as` casts between primitive numeric types of the same size.
+
+use alloc::vec::Vec;
+
+use {CastNumInPlace, MapInPlace, NumCast, SaturatingNumCast};
+
+/// Keeps `NumCast` and `SaturatingNumCast` from being implemented outside of this crate.
+pub trait Sealed<T> {
+    fn cast(self) -> T;
+}
+
+macro_rules! num_cast {
+    ($($a:ty => $($b:ty),+;)+) => {
+        $($(
+            impl Sealed<$b> for $a {
+                #[inline]
+                fn cast(self) -> $b {
+                    self as $b
+                }
+            }
+
+            impl NumCast<$b> for $a {}
+        )+)+
+    }
+}
+
+macro_rules! saturating_num_cast {
+    ($($a:ty => $($b:ty),+;)+) => {
+        $($(
+            impl Sealed<$b> for $a {
+                #[inline]
+                fn cast(self) -> $b {
+                    self as $b
+                }
+            }
+
+            impl SaturatingNumCast<$b> for $a {}
+        )+)+
+    }
+}
+
+num_cast! {
+    u8 => u8, i8;
+    i8 => u8, i8;
+    u16 => u16, i16;
+    i16 => u16, i16;
+    u32 => u32, i32, f32;
+    i32 => u32, i32, f32;
+    f32 => f32;
+    u64 => u64, i64, f64;
+    i64 => u64, i64, f64;
+    f64 => f64;
+    u128 => u128, i128;
+    i128 => u128, i128;
+    usize => usize, isize;
+    isize => usize, isize;
+}
+
+saturating_num_cast! {
+    f32 => u32, i32;
+    f64 => u64, i64;
+}
+
+// the size of `usize` and `isize` depends on the target
+
+#[cfg(target_pointer_width = "16")]
+num_cast! {
+    usize => u16, i16;
+    isize => u16, i16;
+    u16 => usize, isize;
+    i16 => usize, isize;
+}
+
+#[cfg(target_pointer_width = "32")]
+num_cast! {
+    usize => u32, i32, f32;
+    isize => u32, i32, f32;
+    u32 => usize, isize;
+    i32 => usize, isize;
+}
+
+#[cfg(target_pointer_width = "32")]
+saturating_num_cast! {
+    f32 => usize, isize;
+}
+
+#[cfg(target_pointer_width = "64")]
+num_cast! {
+    usize => u64, i64, f64;
+    isize => u64, i64, f64;
+    u64 => usize, isize;
+    i64 => usize, isize;
+}
+
+#[cfg(target_pointer_width = "64")]
+saturating_num_cast! {
+    f64 => usize, isize;
+}
+
+impl<A> CastNumInPlace<A> for Vec<A> {
+    #[inline]
+    fn cast_num_in_place<T>(self) -> Vec<T>
+        where A: NumCast<T>
+    {
+        self.map_in_place(Sealed::cast)
+    }
+
+    #[inline]
+    fn saturating_cast_num_in_place<T>(self) -> Vec<T>
+        where A: SaturatingNumCast<T>
+    {
+        self.map_in_place(Sealed::cast)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use CastNumInPlace;
+
+    #[test]
+    fn cast_ints() {
+        let v: Vec<u32> = vec![0, 1, u32::MAX];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.cast_num_in_place::<i32>();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![0, 1, -1]);
+
+        let v = v.cast_num_in_place::<u32>();
+        assert_eq!(v, vec![0, 1, u32::MAX]);
+    }
+
+    #[test]
+    fn cast_int_to_float() {
+        let v: Vec<u64> = vec![0, 3, 1 << 60];
+        let v = v.cast_num_in_place::<f64>();
+
+        assert_eq!(v, vec![0.0, 3.0, (1u64 << 60) as f64]);
+    }
+
+    #[test]
+    fn cast_pointer_sized() {
+        let v: Vec<isize> = vec![-1, 2];
+        let v = v.cast_num_in_place::<usize>();
+
+        assert_eq!(v, vec![usize::MAX, 2]);
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn cast_pointer_sized_to_fixed() {
+        let v: Vec<usize> = vec![1, usize::MAX];
+        let v = v.cast_num_in_place::<i64>();
+
+        assert_eq!(v, vec![1, -1]);
+    }
+
+    #[test]
+    fn saturating_cast_float_to_int() {
+        let v: Vec<f32> = vec![1.5, -1.0, 1e20, f32::NAN];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.saturating_cast_num_in_place::<u32>();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![1, 0, u32::MAX, 0]);
+    }
+}
@@ -0,0 +1,309 @@
+//! Mapping the payloads of `Result`s and `Option`s in a `Vec`.
+//!
+//! Unless noted otherwise, every slot is rewritten as a whole, as the discriminants of the two
+//! element types may be encoded differently, e.g. in a niche of one payload type but not of
+//! the other. The payloads that aren't mapped are moved, but never dropped or cloned.
+
+use alloc::vec::Vec;
+use core::mem;
+use core::ptr;
+
+use {can_reuse_buffer, poison_slot, poison_tail, same_layout, shrink_allocation,
+     try_map_elements, CollectResultsInPlace, MapErrInPlace, MapInPlaceOrAlloc, MapOkInPlace,
+     MapSomeInPlace, Mapped, VecDropper};
+
+impl<A, B, E> MapOkInPlace<A, B, E> for Vec<Result<A, E>> {
+    type Output = Mapped<Vec<Result<B, E>>>;
+
+    /// The buffer is reused when `Result<A, E>` and `Result<B, E>` encode their discriminants
+    /// the same way, see `same_encoding`. Only the `Ok` slots are rewritten then; the `Err`s
+    /// are left where they are, without being read. Otherwise the results are collected into
+    /// a new `Vec`.
+    ///
+    /// If `f` panics, the results before the one it was given (mapped `Ok`s and untouched
+    /// `Err`s alike) are dropped, then the ones that weren't reached; the `A` `f` was given is
+    /// dropped by `f`.
+    #[inline]
+    fn map_ok_in_place<F>(self, mut f: F) -> Self::Output
+        where F: FnMut(A) -> B
+    {
+        if !same_encoding::<A, B, E>() {
+            return Mapped::Allocated(self.into_iter().map(|r| r.map(&mut f)).collect());
+        }
+
+        let len = self.len();
+        let cap = self.capacity();
+        let mut dropper = VecDropper::<Result<A, E>, Result<B, E>>::new(self);
+
+        let ptr_a = dropper.ptr;
+        let ptr_b = ptr_a as *mut Result<B, E>;
+
+        unsafe {
+            for i in 0..len {
+                // an `Err` reads the same as a `Result<B, E>`, so it counts as mapped as it is
+                if let Ok(ref mut a) = *ptr_a.add(i) {
+                    let a = ptr::read(a);
+                    poison_slot(ptr_a.add(i));
+                    ptr::write(ptr_b.add(i), Ok(f(a)));
+                }
+
+                dropper.idx += 1;
+            }
+
+            mem::forget(dropper);
+            poison_tail::<Result<A, E>, Result<B, E>>(ptr_b, len);
+
+            Mapped::InPlace(Vec::from_raw_parts(ptr_b, len, cap))
+        }
+    }
+}
+
+/// Whether an `Err` stored as a `Result<A, E>` is also one as a `Result<B, E>`, with its
+/// payload at the same place. The layout of `Result` isn't specified, but the compiler only
+/// picks it from the layouts of the payloads: when `A` and `B` have the same size and
+/// alignment and neither has a niche to store the discriminant in, they're interchangeable.
+#[inline]
+fn same_encoding<A, B, E>() -> bool {
+    fn has_niche<T>() -> bool {
+        mem::size_of::<Option<T>>() == mem::size_of::<T>()
+    }
+
+    same_layout::<A, B>().is_ok() && !has_niche::<A>() && !has_niche::<B>() &&
+    same_layout::<Result<A, E>, Result<B, E>>().is_ok()
+}
+
+impl<A, E, E2> MapErrInPlace<A, E, E2> for Vec<Result<A, E>> {
+    type Output = Mapped<Vec<Result<A, E2>>>;
+
+    /// The buffer is reused under the same conditions as for `map_ok_in_place`, and panics
+    /// are handled the same way.
+    #[inline]
+    fn map_err_in_place<F>(self, mut f: F) -> Self::Output
+        where F: FnMut(E) -> E2
+    {
+        let in_place = can_reuse_buffer::<Result<A, E>, Result<A, E2>>();
+        let v = self.map_in_place_or_alloc(|r| r.map_err(&mut f));
+
+        if in_place { Mapped::InPlace(v) } else { Mapped::Allocated(v) }
+    }
+}
+
+impl<A, B> MapSomeInPlace<A, B> for Vec<Option<A>> {
+    type Output = Mapped<Vec<Option<B>>>;
+
+    /// The buffer is reused when `Option<B>` is no larger than `Option<A>` and has the same
+    /// alignment, as in `map_in_place_or_alloc`. Otherwise the options are collected into a
+    /// new `Vec`, which `Mapped::Allocated` reports, e.g. when only `B` has a niche for `None`.
+    ///
+    /// Panics are handled the same way as in `map_ok_in_place`.
+    #[inline]
+    fn map_some_in_place<F>(self, mut f: F) -> Self::Output
+        where F: FnMut(A) -> B
+    {
+        let in_place = can_reuse_buffer::<Option<A>, Option<B>>();
+        let v = self.map_in_place_or_alloc(|o| o.map(&mut f));
+
+        if in_place { Mapped::InPlace(v) } else { Mapped::Allocated(v) }
+    }
+}
+
+impl<A, E> CollectResultsInPlace<A, E> for Vec<Result<A, E>> {
+    type Output = Vec<A>;
+
+    /// The buffer is kept (and shrunk to fit a whole number of `A`s if needed) when `A` has the
+    /// same alignment as `Result<A, E>` and isn't zero-sized. Otherwise the values are collected
+    /// into a new `Vec`.
+    ///
+    /// On the first `Err`, the values unwrapped so far and the remaining results are dropped
+    /// and the allocation is freed before the error is returned.
+    #[inline]
+    fn collect_results_in_place(self) -> Result<Self::Output, E> {
+        let len = self.len();
+        let cap = self.capacity();
+
+        if !can_reuse_buffer::<Result<A, E>, A>() {
+            return self.into_iter().collect();
+        }
+
+        unsafe {
+            let ptr = try_map_elements(self, |r| r)?;
+            let (ptr, cap) = shrink_allocation::<Result<A, E>, A>(ptr, cap);
+            Ok(Vec::from_raw_parts(ptr, len, cap))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {CollectResultsInPlace, MapErrInPlace, MapOkInPlace, MapSomeInPlace, Mapped};
+    use testing::DropTracker;
+
+    use std::fmt;
+
+    use std::num::NonZeroU32;
+    use std::panic::catch_unwind;
+
+    #[test]
+    fn map_ok() {
+        let v: Vec<Result<u32, String>> = vec![Ok(1), Err("e".into()), Ok(3)];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.map_ok_in_place(|x| -(x as i32));
+        assert!(v.is_in_place());
+
+        let v = v.into_inner();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![Ok(-1), Err("e".into()), Ok(-3)]);
+    }
+
+    #[test]
+    fn map_ok_different_niche() {
+        // `Result<NonZeroU32, ()>` uses the niche of `NonZeroU32`, so it's smaller
+        let v: Vec<Result<NonZeroU32, ()>> = vec![Ok(NonZeroU32::new(1).unwrap()), Err(())];
+        let v = v.map_ok_in_place(|x| x.get());
+
+        assert_eq!(v, Mapped::Allocated(vec![Ok(1), Err(())]));
+
+        // same size, but only `NonZeroU32` has a niche, so an `Err` may be encoded differently
+        let v: Vec<Result<u32, ()>> = vec![Ok(1), Err(())];
+        let v = v.map_ok_in_place(|x| NonZeroU32::new(x).unwrap());
+
+        assert_eq!(v, Mapped::Allocated(vec![Ok(NonZeroU32::new(1).unwrap()), Err(())]));
+    }
+
+    #[test]
+    fn map_ok_err_untouched() {
+        let v: Vec<Result<u64, String>> = vec![Err("a".into()), Ok(2), Err("b".into())];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.map_ok_in_place(|x| x as f64 / 2.0);
+        assert!(v.is_in_place());
+
+        let v = v.into_inner();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![Err("a".into()), Ok(1.0), Err("b".into())]);
+    }
+
+    #[test]
+    fn map_ok_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v = vec![Ok(DROPS.track(1u32)),
+                     Err(DROPS.track('a')),
+                     Ok(DROPS.track(3)),
+                     Err(DROPS.track('b'))];
+
+        assert!(catch_unwind(|| {
+            v.map_ok_in_place(|x| {
+                if x.0 == 3 {
+                    panic!();
+                }
+
+                DROPS.track(-(x.0 as i32))
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&[// consume Xs
+                             "1",
+                             // panic here
+                             "3",
+                             // drop mapped elements
+                             "-1",
+                             "'a'",
+                             // drop remaining unprocessed elements
+                             "'b'"]);
+    }
+
+    #[test]
+    fn map_err() {
+        #[derive(Debug, PartialEq)]
+        struct Context(&'static str, fmt::Error);
+
+        let v: Vec<Result<u64, fmt::Error>> = vec![Ok(1), Err(fmt::Error), Ok(3)];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.map_err_in_place(|e| Context("writing", e));
+        assert!(v.is_in_place());
+
+        let v = v.into_inner();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![Ok(1), Err(Context("writing", fmt::Error)), Ok(3)]);
+    }
+
+    #[test]
+    fn map_err_larger() {
+        let v: Vec<Result<u8, ()>> = vec![Ok(1), Err(())];
+        let v = v.map_err_in_place(|()| String::from("e"));
+
+        assert_eq!(v, Mapped::Allocated(vec![Ok(1), Err("e".into())]));
+    }
+
+    #[test]
+    fn map_some() {
+        let v: Vec<Option<String>> = vec![Some("a".into()), None, Some("bc".into())];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.map_some_in_place(|s| s.len());
+        assert!(v.is_in_place());
+
+        let v = v.into_inner();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![Some(1), None, Some(2)]);
+    }
+
+    #[test]
+    fn map_some_different_niche() {
+        let v: Vec<Option<NonZeroU32>> = vec![NonZeroU32::new(1), None];
+        let v = v.map_some_in_place(|x| x.get());
+
+        assert_eq!(v, Mapped::Allocated(vec![Some(1), None]));
+    }
+
+    #[test]
+    fn collect_results() {
+        let mut v: Vec<Result<u32, u32>> = Vec::with_capacity(5);
+        v.extend([Ok(1), Ok(2), Ok(3)]);
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.collect_results_in_place().unwrap();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![1, 2, 3]);
+        assert_eq!(v.capacity(), 10);
+
+        let v: Vec<Result<u32, u32>> = vec![Ok(1), Err(2), Err(3)];
+        assert_eq!(v.collect_results_in_place(), Err(2));
+    }
+
+    #[test]
+    fn collect_results_error_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v = vec![Ok(DROPS.track(0u32)),
+                     Ok(DROPS.track(1)),
+                     Err(DROPS.track('a')),
+                     Ok(DROPS.track(3)),
+                     Err(DROPS.track('b'))];
+
+        let e = v.collect_results_in_place().unwrap_err();
+
+        DROPS.assert_drops(&[// drop unwrapped elements
+                             "0",
+                             "1",
+                             // drop remaining unprocessed elements
+                             "3",
+                             "'b'"]);
+
+        drop(e);
+        assert_eq!(DROPS.drops().last().unwrap(), "'a'");
+    }
+}
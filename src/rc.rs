@@ -0,0 +1,187 @@
+//! Impls for reference-counted slices.
+//!
+//! The allocation of an `Rc<[A]>` or `Arc<[A]>` starts with the reference counts, so the
+//! payload can only be retyped in place when `[A]` and `[B]` have the exact same layout, and
+//! the allocation is always freed by handing it back to `Rc` or `Arc` itself.
+
+use alloc::rc::Rc;
+use alloc::sync::Arc;
+use core::mem::{self, ManuallyDrop};
+
+use {map_slice, MapInPlaceUnique};
+
+/// Releases an `Rc<[A]>` obtained from `Rc::into_raw` without dropping its elements.
+unsafe fn free_rc<A>(ptr: *mut [A]) {
+    mem::drop(Rc::from_raw(ptr as *const [ManuallyDrop<A>]));
+}
+
+/// Releases an `Arc<[A]>` obtained from `Arc::into_raw` without dropping its elements.
+unsafe fn free_arc<A>(ptr: *mut [A]) {
+    mem::drop(Arc::from_raw(ptr as *const [ManuallyDrop<A>]));
+}
+
+#[inline]
+fn same_layout<A, B>() -> bool {
+    mem::size_of::<A>() == mem::size_of::<B>() && mem::align_of::<A>() == mem::align_of::<B>()
+}
+
+impl<A, B> MapInPlaceUnique<A, B> for Rc<[A]> {
+    type Output = Rc<[B]>;
+
+    /// Succeeds when there are no other `Rc` or `Weak` pointers to the allocation, and `A` and
+    /// `B` have the same size and alignment.
+    #[inline]
+    fn map_in_place_unique<F>(mut self, f: F) -> Result<Self::Output, Self>
+        where F: FnMut(A) -> B
+    {
+        if !same_layout::<A, B>() || Rc::get_mut(&mut self).is_none() {
+            return Err(self);
+        }
+
+        unsafe {
+            let ptr = Rc::into_raw(self) as *mut [A];
+            Ok(Rc::from_raw(map_slice(ptr, f, free_rc::<A>)))
+        }
+    }
+}
+
+impl<A, B> MapInPlaceUnique<A, B> for Arc<[A]> {
+    type Output = Arc<[B]>;
+
+    /// Succeeds when there are no other `Arc` or `Weak` pointers to the allocation, and `A`
+    /// and `B` have the same size and alignment.
+    ///
+    /// Uniqueness is checked with `Arc::get_mut`, so it synchronizes with the release of any
+    /// other reference. Holding the only `Arc` and no `Weak`, no new reference can be created
+    /// by another thread while the elements are mapped.
+    #[inline]
+    fn map_in_place_unique<F>(mut self, f: F) -> Result<Self::Output, Self>
+        where F: FnMut(A) -> B
+    {
+        if !same_layout::<A, B>() || Arc::get_mut(&mut self).is_none() {
+            return Err(self);
+        }
+
+        unsafe {
+            let ptr = Arc::into_raw(self) as *mut [A];
+            Ok(Arc::from_raw(map_slice(ptr, f, free_arc::<A>)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use MapInPlaceUnique;
+    use testing::DropTracker;
+
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use std::thread;
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    #[test]
+    fn unique_rc_slice() {
+        let r: Rc<[u32]> = Rc::from(vec![0, 1, 2, 3]);
+
+        let bp = r.as_ptr() as *const ();
+        let r = r.map_in_place_unique(|x| (x * x) as i32).unwrap();
+        let ap = r.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(&*r, &[0, 1, 4, 9]);
+    }
+
+    #[test]
+    fn shared_rc_slice() {
+        let r: Rc<[u32]> = Rc::from(vec![0, 1, 2, 3]);
+        let other = r.clone();
+
+        let r = r.map_in_place_unique(|x| x as i32).unwrap_err();
+        assert!(Rc::ptr_eq(&r, &other));
+        assert_eq!(&*r, &[0, 1, 2, 3]);
+
+        drop(other);
+
+        let weak = Rc::downgrade(&r);
+        let r = r.map_in_place_unique(|x| x as i32).unwrap_err();
+        assert_eq!(&*r, &[0, 1, 2, 3]);
+
+        drop(weak);
+        assert!(r.map_in_place_unique(|x| x as i32).is_ok());
+    }
+
+    #[test]
+    fn different_sizes_rc_slice() {
+        let r: Rc<[u32]> = Rc::from(vec![0, 1, 2, 3]);
+        assert!(r.map_in_place_unique(|x| x as u16).is_err());
+    }
+
+    #[test]
+    fn rc_slice_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let r: Rc<[_]> = (1..5).map(|x| DROPS.track(x as u32)).collect();
+
+        assert!(catch_unwind(AssertUnwindSafe(|| {
+            r.map_in_place_unique(|x| {
+                if x.0 == 3 {
+                    panic!();
+                } else {
+                    DROPS.track(-(x.0 as i32))
+                }
+            })
+        })).is_err());
+
+        DROPS.assert_drops(&["1", "2", "3", "-1", "-2", "4"]);
+    }
+
+    #[test]
+    fn unique_arc_slice() {
+        let a: Arc<[u32]> = Arc::from(vec![0, 1, 2, 3]);
+
+        let bp = a.as_ptr() as *const ();
+        let a = a.map_in_place_unique(|x| (x * x) as i32).unwrap();
+        let ap = a.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(&*a, &[0, 1, 4, 9]);
+    }
+
+    #[test]
+    fn shared_arc_slice() {
+        let a: Arc<[u32]> = Arc::from(vec![0, 1, 2, 3]);
+        let other = a.clone();
+
+        let a = a.map_in_place_unique(|x| x as i32).unwrap_err();
+        assert!(Arc::ptr_eq(&a, &other));
+
+        // released by another thread
+        thread::spawn(move || drop(other)).join().unwrap();
+
+        let weak = Arc::downgrade(&a);
+        let a = a.map_in_place_unique(|x| x as i32).unwrap_err();
+        assert_eq!(&*a, &[0, 1, 2, 3]);
+
+        drop(weak);
+        assert!(a.map_in_place_unique(|x| x as i32).is_ok());
+    }
+
+    #[test]
+    fn arc_slice_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let a: Arc<[_]> = (1..5).map(|x| DROPS.track(x as u32)).collect();
+
+        assert!(catch_unwind(AssertUnwindSafe(|| {
+            a.map_in_place_unique(|x| {
+                if x.0 == 1 {
+                    panic!();
+                } else {
+                    DROPS.track(-(x.0 as i32))
+                }
+            })
+        })).is_err());
+
+        DROPS.assert_drops(&["1", "2", "3", "4"]);
+    }
+}
@@ -0,0 +1,114 @@
+//! Reinterpreting a `Vec<A>` as a `Vec<B>` of a layout-identical type.
+
+use alloc::vec::Vec;
+use core::cmp::Reverse;
+use core::marker::PhantomData;
+use core::mem::{self, ManuallyDrop};
+use core::num::{NonZeroI128, NonZeroI16, NonZeroI32, NonZeroI64, NonZeroI8, NonZeroIsize,
+               NonZeroU128, NonZeroU16, NonZeroU32, NonZeroU64, NonZeroU8, NonZeroUsize, Wrapping};
+
+use {RetypeInPlace, TransmutableFrom};
+
+/// Fails to compile (when `OK` is used) unless `A` and `B` have the same size and alignment.
+struct SameLayout<A, B>(PhantomData<(A, B)>);
+
+impl<A, B> SameLayout<A, B> {
+    const OK: () = assert!(mem::size_of::<A>() == mem::size_of::<B>() &&
+                           mem::align_of::<A>() == mem::align_of::<B>(),
+                           "retype_in_place: Size and alignment of A must be equal to those of B");
+}
+
+impl<A> RetypeInPlace<A> for Vec<A> {
+    #[inline]
+    fn retype_in_place<B: TransmutableFrom<A>>(self) -> Vec<B> {
+        let () = SameLayout::<A, B>::OK;
+
+        let mut v = ManuallyDrop::new(self);
+
+        unsafe { Vec::from_raw_parts(v.as_mut_ptr() as *mut B, v.len(), v.capacity()) }
+    }
+}
+
+unsafe impl<T> TransmutableFrom<T> for Wrapping<T> {}
+unsafe impl<T> TransmutableFrom<Wrapping<T>> for T {}
+unsafe impl<T> TransmutableFrom<T> for Reverse<T> {}
+unsafe impl<T> TransmutableFrom<Reverse<T>> for T {}
+
+macro_rules! from_non_zero {
+    ($($nz:ty => $t:ty),+) => {
+        $(
+            unsafe impl TransmutableFrom<$nz> for $t {}
+        )+
+    }
+}
+
+from_non_zero!(NonZeroU8 => u8,
+               NonZeroU16 => u16,
+               NonZeroU32 => u32,
+               NonZeroU64 => u64,
+               NonZeroU128 => u128,
+               NonZeroUsize => usize,
+               NonZeroI8 => i8,
+               NonZeroI16 => i16,
+               NonZeroI32 => i32,
+               NonZeroI64 => i64,
+               NonZeroI128 => i128,
+               NonZeroIsize => isize);
+
+#[cfg(test)]
+mod tests {
+    use {RetypeInPlace, TransmutableFrom};
+
+    use std::cmp::Reverse;
+    use std::num::{NonZeroU32, Wrapping};
+
+    #[test]
+    fn retype_wrapping() {
+        let v: Vec<u32> = vec![1, 2, u32::MAX];
+
+        let bp = v.as_ptr() as *const ();
+        let cap = v.capacity();
+        let v = v.retype_in_place::<Wrapping<u32>>();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v.capacity(), cap);
+        assert_eq!(v.iter().map(|x| (x + Wrapping(1)).0).collect::<Vec<_>>(), vec![2, 3, 0]);
+
+        let v = v.retype_in_place::<u32>();
+        assert_eq!(v, vec![1, 2, u32::MAX]);
+    }
+
+    #[test]
+    fn retype_reverse() {
+        let mut v = vec![1, 3, 2].retype_in_place::<Reverse<i32>>();
+        v.sort();
+
+        assert_eq!(v.retype_in_place::<i32>(), vec![3, 2, 1]);
+    }
+
+    #[test]
+    fn retype_non_zero() {
+        let v = vec![NonZeroU32::new(4).unwrap(), NonZeroU32::new(5).unwrap()];
+
+        assert_eq!(v.retype_in_place::<u32>(), vec![4, 5]);
+    }
+
+    #[test]
+    fn retype_user_type() {
+        #[derive(Debug, PartialEq)]
+        #[repr(transparent)]
+        struct Name(String);
+
+        unsafe impl TransmutableFrom<String> for Name {}
+
+        let v: Vec<String> = vec!["a".into(), "b".into()];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.retype_in_place::<Name>();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![Name("a".into()), Name("b".into())]);
+    }
+}
@@ -0,0 +1,229 @@
+//! Replacing the elements of a slice or `Vec` by value, through a borrow.
+
+use alloc::vec::Vec;
+use core::mem;
+use core::ptr;
+
+use {MapInPlaceSame, ReplaceEach};
+
+impl<A> ReplaceEach<A> for [A] {
+    #[inline]
+    fn replace_each<F>(&mut self, f: F)
+        where F: FnMut(A) -> A
+    {
+        replace_elements::<A, F, fn() -> A>(self, f, None);
+    }
+
+    #[inline]
+    fn replace_each_or_else<F, R>(&mut self, f: F, recover: R)
+        where F: FnMut(A) -> A,
+              R: FnOnce() -> A
+    {
+        replace_elements(self, f, Some(recover));
+    }
+}
+
+#[inline]
+fn replace_elements<A, F, R>(slice: &mut [A], mut f: F, recover: Option<R>)
+    where F: FnMut(A) -> A,
+          R: FnOnce() -> A
+{
+    let mut guard = HoleGuard {
+        hole: ptr::null_mut(),
+        recover,
+    };
+
+    for x in slice.iter_mut() {
+        let x: *mut A = x;
+        guard.hole = x;
+
+        unsafe {
+            ptr::write(x, f(ptr::read(x)));
+        }
+    }
+
+    guard.hole = ptr::null_mut();
+}
+
+impl<A> MapInPlaceSame<A> for Vec<A> {
+    #[inline]
+    fn map_in_place_same<F>(&mut self, mut f: F)
+        where F: FnMut(A) -> A
+    {
+        let len = self.len();
+
+        unsafe {
+            // the guard owns the elements until they're all replaced
+            self.set_len(0);
+        }
+
+        let mut guard = TruncateGuard {
+            vec: self,
+            idx: 0,
+            len,
+        };
+
+        while guard.idx < len {
+            unsafe {
+                let x = guard.vec.as_mut_ptr().add(guard.idx);
+                ptr::write(x, f(ptr::read(x)));
+            }
+
+            guard.idx += 1;
+        }
+    }
+}
+
+/// Elements `0..idx` of `vec` have been replaced, and element `idx` has been moved out if
+/// `idx < len`. Dropping the rest and keeping the replaced prefix leaves `vec` valid.
+struct TruncateGuard<'a, A: 'a> {
+    vec: &'a mut Vec<A>,
+    idx: usize,
+    len: usize,
+}
+
+impl<'a, A> Drop for TruncateGuard<'a, A> {
+    #[inline]
+    fn drop(&mut self) {
+        unsafe {
+            self.vec.set_len(self.idx);
+
+            if self.idx < self.len {
+                // panicked; the element that was being replaced is already gone
+                let rest = self.vec.as_mut_ptr().add(self.idx + 1);
+                let rest = ::core::slice::from_raw_parts_mut(rest, self.len - self.idx - 1);
+                ptr::drop_in_place(rest);
+            }
+        }
+    }
+}
+
+/// `hole` points to the element that has been moved out, if any. If `f` panics, it is filled
+/// with the result of `recover`, or the process is aborted when there is none.
+struct HoleGuard<A, R: FnOnce() -> A> {
+    hole: *mut A,
+    recover: Option<R>,
+}
+
+impl<A, R: FnOnce() -> A> Drop for HoleGuard<A, R> {
+    #[inline]
+    fn drop(&mut self) {
+        if self.hole.is_null() {
+            return;
+        }
+
+        // panicked; the hole must be filled before the slice can be used again
+        match self.recover.take() {
+            Some(recover) => {
+                let abort = AbortOnDrop;
+
+                unsafe {
+                    ptr::write(self.hole, recover());
+                }
+
+                mem::forget(abort);
+            }
+            None => abort(),
+        }
+    }
+}
+
+/// Aborts the process if dropped, i.e. if `recover` panics.
+struct AbortOnDrop;
+
+impl Drop for AbortOnDrop {
+    #[inline]
+    fn drop(&mut self) {
+        abort();
+    }
+}
+
+/// Only called while unwinding, where without `std` panicking again aborts just the same.
+#[cold]
+fn abort() -> ! {
+    #[cfg(feature = "std")]
+    ::std::process::abort();
+
+    #[cfg(not(feature = "std"))]
+    panic!("panicked again while the slice had a hole in it");
+}
+
+#[cfg(test)]
+mod tests {
+    use {MapInPlaceSame, ReplaceEach};
+
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+
+    #[derive(Debug, PartialEq)]
+    struct Secret(String);
+
+    #[test]
+    fn replace_each() {
+        let mut v = vec![Secret("a".into()), Secret("b".into())];
+
+        let bp = v.as_ptr() as *const ();
+        v.replace_each(|Secret(s)| Secret(s + "'"));
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![Secret("a'".into()), Secret("b'".into())]);
+    }
+
+    #[test]
+    fn replace_each_or_else() {
+        let mut v = vec![Secret("a".into()), Secret("b".into()), Secret("c".into())];
+
+        assert!(catch_unwind(AssertUnwindSafe(|| {
+            v[..].replace_each_or_else(|Secret(s)| {
+                                          if s == "b" {
+                                              panic!();
+                                          }
+
+                                          Secret(s.to_uppercase())
+                                      },
+                                      || Secret("revoked".into()))
+        })).is_err());
+
+        assert_eq!(v,
+                   vec![Secret("A".into()), Secret("revoked".into()), Secret("c".into())]);
+    }
+
+    #[test]
+    fn map_in_place_same() {
+        struct Holder {
+            secrets: Vec<Secret>,
+        }
+
+        let mut h = Holder { secrets: vec![Secret("a".into()), Secret("b".into())] };
+
+        let bp = h.secrets.as_ptr() as *const ();
+        h.secrets.map_in_place_same(|Secret(s)| Secret(s + "'"));
+        let ap = h.secrets.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(h.secrets, vec![Secret("a'".into()), Secret("b'".into())]);
+    }
+
+    #[test]
+    fn map_in_place_same_panic_truncates() {
+        use std::rc::Rc;
+
+        let rc = Rc::new(());
+        let mut v: Vec<(u32, Rc<()>)> = (0..5).map(|i| (i, rc.clone())).collect();
+
+        assert!(catch_unwind(AssertUnwindSafe(|| {
+            v.map_in_place_same(|(i, r)| {
+                                    if i == 2 {
+                                        panic!();
+                                    }
+
+                                    (i * 10, r)
+                                })
+        })).is_err());
+
+        // everything past the replaced prefix was dropped
+        assert_eq!(Rc::strong_count(&rc), 3);
+        let v: Vec<u32> = v.into_iter().map(|(i, _)| i).collect();
+        assert_eq!(v, vec![0, 10]);
+    }
+}
@@ -0,0 +1,131 @@
+//! Byte-wise mapping of owned strings.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use {MapAsciiInPlace, MapBytesInPlace, MapInPlace};
+
+impl MapAsciiInPlace for String {
+    /// Panics if `f` maps an ASCII byte to a non-ASCII one. The bytes mapped up to that point
+    /// are dropped along with the rest of the string.
+    #[inline]
+    fn map_ascii_in_place<F>(self, mut f: F) -> Self
+        where F: FnMut(u8) -> u8
+    {
+        let mut bytes = self.into_bytes();
+
+        for b in bytes.iter_mut().filter(|b| b.is_ascii()) {
+            let mapped = f(*b);
+
+            if !mapped.is_ascii() {
+                panic!("map_ascii_in_place(String): {:#04x} was mapped to non-ASCII byte {:#04x}",
+                       *b,
+                       mapped)
+            }
+
+            *b = mapped;
+        }
+
+        // only ASCII bytes were replaced, and only with ASCII bytes
+        unsafe { String::from_utf8_unchecked(bytes) }
+    }
+}
+
+impl MapBytesInPlace for String {
+    type Output = Vec<u8>;
+
+    /// Keeps the buffer, capacity included, as the returned `Vec<u8>`. If `f` panics, the
+    /// buffer is freed and nothing else needs cleaning up.
+    #[inline]
+    fn map_bytes_in_place<F>(self, f: F) -> Self::Output
+        where F: FnMut(u8) -> u8
+    {
+        self.into_bytes().map_in_place(f)
+    }
+}
+
+impl MapBytesInPlace for Box<str> {
+    type Output = Box<[u8]>;
+
+    /// Keeps the allocation as the returned `Box<[u8]>`, which has the exact same layout.
+    #[inline]
+    fn map_bytes_in_place<F>(self, f: F) -> Self::Output
+        where F: FnMut(u8) -> u8
+    {
+        self.into_boxed_bytes().map_in_place(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {MapAsciiInPlace, MapBytesInPlace};
+
+    use std::panic::catch_unwind;
+
+    #[test]
+    fn ascii_string() {
+        let s = String::from("Hello+Wörld+ÆØÅ");
+
+        let bp = s.as_ptr();
+        let cap = s.capacity();
+        let s = s.map_ascii_in_place(|b| if b == b'+' { b'-' } else { b.to_ascii_lowercase() });
+
+        assert_eq!(bp, s.as_ptr()); // still at same memory addr
+        assert_eq!(cap, s.capacity());
+        assert_eq!(s, "hello-wörld-ÆØÅ");
+    }
+
+    #[test]
+    fn ascii_string_skips_multibyte() {
+        let s = String::from("aé€😀b");
+        let mut seen = vec![];
+
+        let s = s.map_ascii_in_place(|b| {
+            seen.push(b);
+            b
+        });
+
+        assert_eq!(seen, b"ab");
+        assert_eq!(s, "aé€😀b");
+    }
+
+    #[test]
+    #[should_panic]
+    fn ascii_string_non_ascii_result() {
+        let _ = String::from("abc").map_ascii_in_place(|b| b | 0x80);
+    }
+
+    #[test]
+    fn bytes_string() {
+        let mut s = String::with_capacity(32);
+        s.push_str("secret");
+
+        let bp = s.as_ptr();
+        let v = s.map_bytes_in_place(|b| b ^ 0xff);
+
+        assert_eq!(bp, v.as_ptr()); // still at same memory addr
+        assert_eq!(v.capacity(), 32);
+        assert_eq!(v, b"secret".iter().map(|b| b ^ 0xff).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn bytes_string_panic() {
+        let s = String::from("secret");
+
+        assert!(catch_unwind(|| {
+            s.map_bytes_in_place(|b| if b == b'c' { panic!() } else { b ^ 0xff })
+        }).is_err());
+    }
+
+    #[test]
+    fn bytes_boxed_str() {
+        let s: Box<str> = "Hello, World".into();
+
+        let bp = s.as_ptr();
+        let b = s.map_bytes_in_place(|b| b.to_ascii_lowercase());
+
+        assert_eq!(bp, b.as_ptr()); // still at same memory addr
+        assert_eq!(&*b, b"hello, world");
+    }
+}
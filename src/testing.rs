@@ -0,0 +1,219 @@
+//! Drop tracking, for testing code built on this crate: that every element is dropped exactly
+//! once and in the expected order, also when the mapping function or a destructor panics.
+//!
+//! Enabled by the `testing` feature, and semi-stable: it won't break in patch releases, but
+//! may change more freely than the rest of the crate between minor ones.
+//!
+//! ```
+//! use map_in_place::testing::DropTracker;
+//! use map_in_place::MapInPlace;
+//!
+//! static DROPS: DropTracker = DropTracker::new();
+//!
+//! let v = vec![DROPS.track('a'), DROPS.track('b')];
+//! let v = v.map_in_place(|a| DROPS.track(a.0 as u32));
+//! drop(v);
+//!
+//! // each `Tracked` records its value when dropped, and `a.0` copied it out
+//! DROPS.assert_drops(&["'a'", "'b'", "97", "98"]);
+//! ```
+
+use std::fmt;
+use std::panic;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Records drops, in the order they happen. Can be a `static`, and shared between threads.
+#[derive(Debug)]
+pub struct DropTracker {
+    drops: Mutex<Vec<String>>,
+    /// The index of the drop to panic on, or `usize::MAX` for none.
+    panic_on: AtomicUsize,
+}
+
+impl DropTracker {
+    #[inline]
+    pub const fn new() -> Self {
+        DropTracker {
+            drops: Mutex::new(Vec::new()),
+            panic_on: AtomicUsize::new(usize::MAX),
+        }
+    }
+
+    /// The handle `Tracked` values record their drops with.
+    #[inline]
+    pub fn id(&'static self) -> TrackerId {
+        TrackerId(self)
+    }
+
+    /// Wraps `value` so that dropping it records it, as formatted with `Debug`.
+    #[inline]
+    pub fn track<T: fmt::Debug>(&'static self, value: T) -> Tracked<T> {
+        Tracked(value, self.id())
+    }
+
+    /// Records a drop. Panics with `drop` as the payload if it's the one `panic_on_drop` was
+    /// set to, after recording it.
+    pub fn record<S: Into<String>>(&self, drop: S) {
+        let drop = drop.into();
+        let n = {
+            let mut drops = self.lock();
+            drops.push(drop.clone());
+            drops.len() - 1
+        };
+
+        if n == self.panic_on.load(Ordering::SeqCst) {
+            panic::panic_any(drop);
+        }
+    }
+
+    /// Makes the `n`th drop recorded, counting from 0 since the last `clear`, panic. For
+    /// exercising the cleanup after a destructor panics.
+    #[inline]
+    pub fn panic_on_drop(&self, n: usize) {
+        self.panic_on.store(n, Ordering::SeqCst);
+    }
+
+    /// The drops recorded so far.
+    #[inline]
+    pub fn drops(&self) -> Vec<String> {
+        self.lock().clone()
+    }
+
+    /// The number of drops recorded so far.
+    #[inline]
+    pub fn count(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// Forgets the drops recorded so far, and which one to panic on.
+    #[inline]
+    pub fn clear(&self) {
+        self.lock().clear();
+        self.panic_on.store(usize::MAX, Ordering::SeqCst);
+    }
+
+    /// Panics unless exactly the drops in `expected` have been recorded, in that order.
+    #[track_caller]
+    pub fn assert_drops(&self, expected: &[&str]) {
+        assert_eq!(*self.lock(), expected);
+    }
+
+    /// Panics unless exactly `n` drops have been recorded.
+    #[track_caller]
+    pub fn assert_count(&self, n: usize) {
+        assert_eq!(self.count(), n, "{:?}", self.drops());
+    }
+
+    /// A panic while recording a drop doesn't leave anything half done, so poisoning can be
+    /// ignored.
+    #[inline]
+    fn lock(&self) -> MutexGuard<'_, Vec<String>> {
+        self.drops.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Default for DropTracker {
+    #[inline]
+    fn default() -> Self {
+        DropTracker::new()
+    }
+}
+
+/// Refers to a `DropTracker`, see `DropTracker::id`.
+#[derive(Debug, Clone, Copy)]
+pub struct TrackerId(&'static DropTracker);
+
+impl TrackerId {
+    #[inline]
+    pub fn tracker(self) -> &'static DropTracker {
+        self.0
+    }
+}
+
+/// A value that records itself with its tracker when dropped.
+#[derive(Debug)]
+pub struct Tracked<T: fmt::Debug>(pub T, pub TrackerId);
+
+impl<T: fmt::Debug> Drop for Tracked<T> {
+    fn drop(&mut self) {
+        self.1.tracker().record(format!("{:?}", self.0));
+    }
+}
+
+/// Compares the values, whichever trackers they record with.
+impl<T: fmt::Debug + PartialEq> PartialEq for Tracked<T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DropTracker;
+    use MapInPlace;
+
+    use std::panic::catch_unwind;
+    use std::thread;
+
+    #[test]
+    fn tracked_vec() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v = vec![DROPS.track(1u32), DROPS.track(2), DROPS.track(3)];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.map_in_place(|x| DROPS.track(-(x.0 as i32)));
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        DROPS.assert_drops(&["1", "2", "3"]);
+
+        drop(v);
+        DROPS.assert_drops(&["1", "2", "3", "-1", "-2", "-3"]);
+    }
+
+    #[test]
+    fn panic_on_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        DROPS.panic_on_drop(1);
+
+        let v = vec![DROPS.track('a'), DROPS.track('b'), DROPS.track('c')];
+        let e = catch_unwind(|| drop(v)).unwrap_err();
+
+        // the drop glue of a `Vec` carries on past a panicking destructor
+        assert_eq!(e.downcast_ref::<String>().map(|s| &**s), Some("'b'"));
+        DROPS.assert_drops(&["'a'", "'b'", "'c'"]);
+
+        DROPS.clear();
+        drop(DROPS.track('d'));
+        DROPS.assert_drops(&["'d'"]);
+    }
+
+    #[test]
+    fn threads() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let handles: Vec<_> = (0..4u32)
+            .map(|i| {
+                thread::spawn(move || {
+                    let v: Vec<_> = (0..100).map(|j| DROPS.track(i * 100 + j)).collect();
+                    v.map_in_place(|x| DROPS.track(x.0 as i32))
+                })
+            })
+            .collect();
+
+        for h in handles {
+            h.join().unwrap();
+        }
+
+        DROPS.assert_count(800);
+
+        let mut drops = DROPS.drops();
+        drops.sort();
+        drops.dedup();
+        assert_eq!(drops.len(), 400);
+    }
+}
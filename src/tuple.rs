@@ -0,0 +1,532 @@
+//! Impls for `Vec`s of tuples that only touch some of the fields.
+
+use alloc::vec::Vec;
+use core::mem;
+use core::ptr;
+
+use {check_layout, map_elements, same_layout, shrink_allocation, DropSlice, MapError,
+     MapFirstInPlace, MapTuplesInPlace, MapValuesInPlace, UnzipInPlace};
+
+/// Panics unless `(K, A)` and `(K, B)` have the same layout, with the keys at the same offset.
+///
+/// Tuples are `repr(Rust)`, so the position of each field has to be checked as well.
+#[inline]
+fn assert_same_pair_layout<K, A, B>(name: &'static str) {
+    check_layout::<(K, A), (K, B)>(same_layout::<(K, A), (K, B)>(), name);
+
+    if mem::offset_of!((K, A), 0) != mem::offset_of!((K, B), 0) ||
+       mem::offset_of!((K, A), 1) != mem::offset_of!((K, B), 1) {
+        check_layout::<A, B>(Err(MapError::FieldOffsetMismatch), name);
+    }
+}
+
+/// Panics unless `(A, C)` and `(B, C)` have the same layout, with the `C`s at the same offset.
+#[inline]
+fn assert_same_first_layout<A, B, C>(name: &'static str) {
+    check_layout::<(A, C), (B, C)>(same_layout::<(A, C), (B, C)>(), name);
+
+    if mem::offset_of!((A, C), 0) != mem::offset_of!((B, C), 0) ||
+       mem::offset_of!((A, C), 1) != mem::offset_of!((B, C), 1) {
+        check_layout::<A, B>(Err(MapError::FieldOffsetMismatch), name);
+    }
+}
+
+impl<K, A, B> MapValuesInPlace<A, B> for Vec<(K, A)> {
+    type Output = Vec<(K, B)>;
+
+    /// Only the value is moved out of each pair and passed to `f`; the key is never read or
+    /// moved.
+    ///
+    /// Panics unless `(K, A)` and `(K, B)` have the same size and alignment, and store the key
+    /// at the same offset.
+    #[inline]
+    fn map_values_in_place<F>(self, mut f: F) -> Self::Output
+        where F: FnMut(A) -> B
+    {
+        assert_same_pair_layout::<K, A, B>("map_values_in_place(Vec<(K, A)>)");
+
+        let len = self.len();
+        let cap = self.capacity();
+
+        let mut dropper = ValuesDropper::<K, A, B>::new(self);
+
+        let ptr_a = dropper.ptr;
+        let ptr_b = ptr_a as *mut (K, B);
+
+        unsafe {
+            for i in 0..len {
+                let v = ptr::read(ptr::addr_of!((*ptr_a.add(i)).1));
+                ptr::write(ptr::addr_of_mut!((*ptr_b.add(i)).1), f(v));
+
+                dropper.idx += 1;
+            }
+
+            mem::forget(dropper);
+
+            Vec::from_raw_parts(ptr_b, len, cap)
+        }
+    }
+}
+
+impl<A, B, C> MapFirstInPlace<A, B> for Vec<(A, C)> {
+    type Output = Vec<(B, C)>;
+
+    /// Only the first field is moved out of each pair and passed to `f`; the second is never
+    /// read or moved.
+    ///
+    /// Panics unless `(A, C)` and `(B, C)` have the same size and alignment, and store the `C`
+    /// at the same offset.
+    #[inline]
+    fn map_first_in_place<F>(self, mut f: F) -> Self::Output
+        where F: FnMut(A) -> B
+    {
+        assert_same_first_layout::<A, B, C>("map_first_in_place(Vec<(A, C)>)");
+
+        let len = self.len();
+        let cap = self.capacity();
+
+        let mut dropper = FirstDropper::<A, B, C>::new(self);
+
+        let ptr_a = dropper.ptr;
+        let ptr_b = ptr_a as *mut (B, C);
+
+        unsafe {
+            for i in 0..len {
+                let v = ptr::read(ptr::addr_of!((*ptr_a.add(i)).0));
+                ptr::write(ptr::addr_of_mut!((*ptr_b.add(i)).0), f(v));
+
+                dropper.idx += 1;
+            }
+
+            mem::forget(dropper);
+
+            Vec::from_raw_parts(ptr_b, len, cap)
+        }
+    }
+}
+
+impl<A, B, C> MapTuplesInPlace<A, B, C> for Vec<(A, B)> {
+    type Output = Vec<C>;
+
+    /// Each pair is moved out of its slot as a whole before being passed to `f`, so `f` is free
+    /// to move the fields out separately. If `f` panics, the pair it was given is already gone
+    /// and its slot is skipped during cleanup.
+    ///
+    /// Panics unless `(A, B)` and `C` have the same size and alignment.
+    #[inline]
+    fn map_tuples_in_place<F>(self, f: F) -> Self::Output
+        where F: FnMut((A, B)) -> C
+    {
+        check_layout::<(A, B), C>(same_layout::<(A, B), C>(), "map_tuples_in_place(Vec<(A, B)>)");
+
+        if mem::size_of::<C>() == 0 {
+            return self.into_iter().map(f).collect();
+        }
+
+        let len = self.len();
+        let cap = self.capacity();
+
+        unsafe {
+            let ptr_c = map_elements(self, f);
+
+            Vec::from_raw_parts(ptr_c, len, cap)
+        }
+    }
+}
+
+impl<A, B> UnzipInPlace for Vec<(A, B)> {
+    type Output = (Vec<A>, Vec<B>);
+
+    /// The `A`s are packed to the front of the allocation of `self`, which is kept (and shrunk
+    /// to fit a whole number of `A`s if needed), and the `B`s are moved into a new `Vec` with
+    /// exactly enough capacity.
+    ///
+    /// If `A` is zero-sized or has a different alignment than `(A, B)`, the allocation can't be
+    /// kept as a `Vec<A>`; it is freed instead, and both results are newly allocated.
+    ///
+    /// No code that could panic runs while the elements are being moved.
+    #[inline]
+    fn unzip_in_place(self) -> Self::Output {
+        if mem::size_of::<A>() == 0 || mem::align_of::<A>() != mem::align_of::<(A, B)>() {
+            return self.into_iter().unzip();
+        }
+
+        let len = self.len();
+        let cap = self.capacity();
+        let mut bs: Vec<B> = Vec::with_capacity(len);
+        let mut v = mem::ManuallyDrop::new(self);
+        let ptr_pairs = v.as_mut_ptr();
+        let ptr_a = ptr_pairs as *mut A;
+        let ptr_b = bs.as_mut_ptr();
+
+        unsafe {
+            for i in 0..len {
+                // the `A`s never reach past the pairs that have been read
+                let (a, b) = ptr::read(ptr_pairs.add(i));
+                ptr::write(ptr_a.add(i), a);
+                ptr::write(ptr_b.add(i), b);
+            }
+
+            bs.set_len(len);
+
+            let (ptr_a, cap) = shrink_allocation::<(A, B), A>(ptr_a, cap);
+
+            (Vec::from_raw_parts(ptr_a, len, cap), bs)
+        }
+    }
+}
+
+/// `idx` is the number of values that have been mapped; if `f` panics, the value of the pair
+/// at `idx` has already been moved out, but not its key.
+///
+/// The buffer is held as raw parts, and panicking destructors are handled, as in `VecDropper`.
+struct ValuesDropper<K, A, B> {
+    idx: usize,
+    ptr: *mut (K, A),
+    len: usize,
+    cap: usize,
+    _marker: ::core::marker::PhantomData<B>,
+}
+
+impl<K, A, B> ValuesDropper<K, A, B> {
+    /// Takes over the elements and the buffer of `v`.
+    #[inline]
+    fn new(v: Vec<(K, A)>) -> Self {
+        let mut v = mem::ManuallyDrop::new(v);
+
+        ValuesDropper {
+            idx: 0,
+            ptr: v.as_mut_ptr(),
+            len: v.len(),
+            cap: v.capacity(),
+            _marker: ::core::marker::PhantomData,
+        }
+    }
+}
+
+impl<K, A, B> Drop for ValuesDropper<K, A, B> {
+    #[inline]
+    fn drop(&mut self) {
+        // panicked; manual cleanup needed
+        let idx = self.idx;
+        let len = self.len;
+        let ptr_a = self.ptr;
+        let ptr_b = ptr_a as *mut (K, B);
+
+        unsafe {
+            // frees the buffer once the elements are gone
+            let _owned = Vec::from_raw_parts(ptr_a, 0, self.cap);
+
+            let _rest = DropSlice(ptr::slice_from_raw_parts_mut(ptr_a.add(idx + 1),
+                                                                len - idx - 1));
+            let key = ptr::addr_of_mut!((*ptr_a.add(idx)).0);
+            let _key = DropSlice(ptr::slice_from_raw_parts_mut(key, 1));
+
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(ptr_b, idx));
+        }
+    }
+}
+
+/// `idx` is the number of first fields that have been mapped; if `f` panics, the first field
+/// of the pair at `idx` has already been moved out, but not the second.
+///
+/// The buffer is held as raw parts, and panicking destructors are handled, as in `VecDropper`.
+struct FirstDropper<A, B, C> {
+    idx: usize,
+    ptr: *mut (A, C),
+    len: usize,
+    cap: usize,
+    _marker: ::core::marker::PhantomData<B>,
+}
+
+impl<A, B, C> FirstDropper<A, B, C> {
+    /// Takes over the elements and the buffer of `v`.
+    #[inline]
+    fn new(v: Vec<(A, C)>) -> Self {
+        let mut v = mem::ManuallyDrop::new(v);
+
+        FirstDropper {
+            idx: 0,
+            ptr: v.as_mut_ptr(),
+            len: v.len(),
+            cap: v.capacity(),
+            _marker: ::core::marker::PhantomData,
+        }
+    }
+}
+
+impl<A, B, C> Drop for FirstDropper<A, B, C> {
+    #[inline]
+    fn drop(&mut self) {
+        // panicked; manual cleanup needed
+        let idx = self.idx;
+        let len = self.len;
+        let ptr_a = self.ptr;
+        let ptr_b = ptr_a as *mut (B, C);
+
+        unsafe {
+            // frees the buffer once the elements are gone
+            let _owned = Vec::from_raw_parts(ptr_a, 0, self.cap);
+
+            let _rest = DropSlice(ptr::slice_from_raw_parts_mut(ptr_a.add(idx + 1),
+                                                                len - idx - 1));
+            let second = ptr::addr_of_mut!((*ptr_a.add(idx)).1);
+            let _second = DropSlice(ptr::slice_from_raw_parts_mut(second, 1));
+
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(ptr_b, idx));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {MapFirstInPlace, MapTuplesInPlace, MapValuesInPlace, UnzipInPlace};
+    use testing::{DropTracker, Tracked};
+
+    use std::panic::catch_unwind;
+
+    #[test]
+    fn pair_values() {
+        let v: Vec<(String, u32)> = vec![("a".into(), 1), ("b".into(), 2), ("c".into(), 3)];
+
+        let bp = v.as_ptr() as *const ();
+        let keys_bp: Vec<*const u8> = v.iter().map(|p| p.0.as_ptr()).collect();
+
+        let v = v.map_values_in_place(|x| x as f32 * 0.5);
+
+        let ap = v.as_ptr() as *const ();
+        let keys_ap: Vec<*const u8> = v.iter().map(|p| p.0.as_ptr()).collect();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(keys_bp, keys_ap);
+        assert_eq!(v, vec![("a".into(), 0.5), ("b".into(), 1.0), ("c".into(), 1.5)]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn different_sizes_pair_values() {
+        let v: Vec<(u32, u32)> = vec![(0, 1)];
+        let _ = v.map_values_in_place(|x| x as u64);
+    }
+
+    #[test]
+    fn pair_values_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = "abcd".chars().zip(1..).map(|(k, x)| (DROPS.track(k), DROPS.track(x)))
+                              .collect();
+
+        assert!(catch_unwind(|| {
+            v.map_values_in_place(|x: Tracked<u32>| {
+                if x.0 == 3 {
+                    panic!();
+                } else {
+                    DROPS.track(-(x.0 as i32))
+                }
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&[// consume Xs
+                             "1",
+                             "2",
+                             "3",
+                             // panic here
+                             // drop mapped pairs
+                             "'a'",
+                             "-1",
+                             "'b'",
+                             "-2",
+                             // drop the key whose value was taken
+                             "'c'",
+                             // drop remaining unprocessed pairs
+                             "'d'",
+                             "4"]);
+    }
+
+    #[test]
+    fn pair_first() {
+        let v: Vec<(String, String)> = vec![("B".into(), "x".into()), ("a".into(), "y".into())];
+
+        let bp = v.as_ptr() as *const ();
+        let payloads_bp: Vec<*const u8> = v.iter().map(|p| p.1.as_ptr()).collect();
+
+        let mut v = v.map_first_in_place(|k| k.to_lowercase());
+
+        let ap = v.as_ptr() as *const ();
+        let payloads_ap: Vec<*const u8> = v.iter().map(|p| p.1.as_ptr()).collect();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(payloads_bp, payloads_ap);
+
+        v.sort();
+        assert_eq!(v, vec![("a".into(), "y".into()), ("b".into(), "x".into())]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn different_sizes_pair_first() {
+        let v: Vec<(u32, u32)> = vec![(0, 1)];
+        let _ = v.map_first_in_place(|x| x as u64);
+    }
+
+    #[test]
+    fn pair_first_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (1..).zip("abcd".chars()).map(|(x, c)| (DROPS.track(x), DROPS.track(c)))
+                              .collect();
+
+        assert!(catch_unwind(|| {
+            v.map_first_in_place(|x: Tracked<u32>| {
+                if x.0 == 3 {
+                    panic!();
+                } else {
+                    DROPS.track(-(x.0 as i32))
+                }
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&[// consume Xs
+                             "1",
+                             "2",
+                             "3",
+                             // panic here
+                             // drop mapped pairs
+                             "-1",
+                             "'a'",
+                             "-2",
+                             "'b'",
+                             // drop the second field whose first was taken
+                             "'c'",
+                             // drop remaining unprocessed pairs
+                             "4",
+                             "'d'"]);
+    }
+
+    #[test]
+    fn tuples() {
+        let v: Vec<(u32, u32)> = vec![(1, 0), (2, 1), (3, 2)];
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.map_tuples_in_place(|(a, b)| [a + b, a * b]);
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![[1, 0], [3, 2], [5, 6]]);
+    }
+
+    #[test]
+    fn tuples_partial_move() {
+        let v: Vec<(String, Vec<u8>)> = vec![("a".into(), b"x".to_vec()),
+                                             ("b".into(), b"yz".to_vec())];
+
+        let v = v.map_tuples_in_place(|t| {
+            let value = String::from_utf8(t.1).unwrap();
+            [t.0, value]
+        });
+
+        assert_eq!(v, vec![["a", "x"], ["b", "yz"]]);
+    }
+
+    #[test]
+    fn tuples_zst() {
+        let v = vec![((), ()); 3];
+        let v = v.map_tuples_in_place(|_| ());
+
+        assert_eq!(v.len(), 3);
+    }
+
+    #[test]
+    #[should_panic]
+    #[cfg(target_pointer_width = "64")]
+    fn different_align_tuples() {
+        // `u64` needs a stricter alignment than the pair
+        let v: Vec<(u32, u32)> = vec![(0, 1)];
+        let _ = v.map_tuples_in_place(|(lo, hi)| (hi as u64) << 32 | lo as u64);
+    }
+
+    #[test]
+    #[should_panic]
+    fn different_sizes_tuples() {
+        let v: Vec<(u32, u32)> = vec![(0, 1)];
+        let _ = v.map_tuples_in_place(|(a, _)| a);
+    }
+
+    #[test]
+    fn tuples_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (0..3).map(|i| (DROPS.track(2 * i), DROPS.track(2 * i + 1))).collect();
+
+        assert!(catch_unwind(|| {
+            v.map_tuples_in_place(|(a, b)| {
+                if a.0 == 2 {
+                    panic!();
+                } else {
+                    [a, b]
+                }
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&[// consumed pair dropped while unwinding out of `f`
+                             "3",
+                             "2",
+                             // drop mapped elements
+                             "0",
+                             "1",
+                             // drop remaining unprocessed pairs
+                             "4",
+                             "5"]);
+    }
+
+    #[test]
+    fn unzip_vec() {
+        let v: Vec<(u64, u32)> = vec![(1, 10), (2, 20), (3, 30)];
+
+        let bp = v.as_ptr() as *const ();
+        let cap = v.capacity();
+        let (keys, values) = v.unzip_in_place();
+        let ap = keys.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // the keys keep the original allocation
+        assert_eq!(keys.capacity(), cap * 2);
+        assert_eq!(keys, vec![1, 2, 3]);
+        assert_eq!(values, vec![10, 20, 30]);
+        assert_eq!(values.capacity(), 3);
+    }
+
+    #[test]
+    fn unzip_vec_awkward_capacity() {
+        let mut v: Vec<([u16; 3], u16)> = Vec::with_capacity(5);
+        v.extend_from_slice(&[([1, 2, 3], 4), ([5, 6, 7], 8)]);
+
+        let (a, b) = v.unzip_in_place();
+
+        assert_eq!(a.capacity(), 5 * 8 / 6);
+        assert_eq!(a, vec![[1, 2, 3], [5, 6, 7]]);
+        assert_eq!(b, vec![4, 8]);
+    }
+
+    #[test]
+    fn unzip_vec_different_align() {
+        let v: Vec<(u8, u64)> = vec![(1, 10), (2, 20)];
+        let (a, b) = v.unzip_in_place();
+
+        assert_eq!(a, vec![1, 2]);
+        assert_eq!(b, vec![10, 20]);
+    }
+
+    #[test]
+    fn unzip_vec_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (0..3).map(|i| (DROPS.track(i), i.to_string())).collect();
+        let (a, b) = v.unzip_in_place();
+
+        DROPS.assert_count(0);
+        assert_eq!(b, vec!["0", "1", "2"]);
+
+        drop(a);
+        DROPS.assert_drops(&["0", "1", "2"]);
+    }
+}
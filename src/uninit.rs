@@ -0,0 +1,173 @@
+//! Conversions between `Vec<MaybeUninit<A>>` and `Vec<A>`.
+
+use alloc::vec::Vec;
+use core::mem::{self, ManuallyDrop, MaybeUninit};
+use core::ptr;
+
+use {DeinitInPlace, InitInPlace};
+
+impl<A> InitInPlace<A> for Vec<MaybeUninit<A>> {
+    type Output = Vec<A>;
+
+    /// Every slot up to the length of `self` is overwritten, whatever it held; the spare
+    /// capacity is left alone.
+    ///
+    /// If `f` panics, the elements initialized so far are dropped and the allocation is freed.
+    /// The slots that weren't reached are never dropped.
+    #[inline]
+    fn init_in_place<F>(self, mut f: F) -> Self::Output
+        where F: FnMut(usize) -> A
+    {
+        let len = self.len();
+        let cap = self.capacity();
+
+        let mut dropper = InitDropper::new(self);
+
+        let ptr = dropper.ptr;
+
+        unsafe {
+            for i in 0..len {
+                ptr::write(ptr.add(i), f(i));
+
+                dropper.idx += 1;
+            }
+
+            mem::forget(dropper);
+
+            Vec::from_raw_parts(ptr, len, cap)
+        }
+    }
+}
+
+impl<A> DeinitInPlace for Vec<A> {
+    type Output = Vec<MaybeUninit<A>>;
+
+    /// The elements are never dropped, unless they are read back out of the result.
+    #[inline]
+    fn deinit_in_place(self) -> Self::Output {
+        let mut v = ManuallyDrop::new(self);
+
+        unsafe { Vec::from_raw_parts(v.as_mut_ptr() as *mut MaybeUninit<A>, v.len(), v.capacity()) }
+    }
+}
+
+/// `idx` is the number of elements that have been initialized.
+///
+/// The buffer is held as raw parts, as in `VecDropper`.
+struct InitDropper<A> {
+    idx: usize,
+    ptr: *mut A,
+    cap: usize,
+}
+
+impl<A> InitDropper<A> {
+    /// Takes over the buffer of `v`.
+    #[inline]
+    fn new(v: Vec<MaybeUninit<A>>) -> Self {
+        let mut v = ManuallyDrop::new(v);
+
+        InitDropper {
+            idx: 0,
+            ptr: v.as_mut_ptr() as *mut A,
+            cap: v.capacity(),
+        }
+    }
+}
+
+impl<A> Drop for InitDropper<A> {
+    #[inline]
+    fn drop(&mut self) {
+        // panicked; manual cleanup needed
+        let ptr = self.ptr;
+
+        unsafe {
+            // frees the buffer once the elements are gone
+            let _owned = Vec::from_raw_parts(ptr, 0, self.cap);
+
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(ptr, self.idx));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {DeinitInPlace, InitInPlace};
+    use testing::{DropTracker, Tracked};
+
+    use std::mem::MaybeUninit;
+    use std::panic::catch_unwind;
+
+    #[test]
+    fn init_vec() {
+        let mut v: Vec<MaybeUninit<String>> = Vec::with_capacity(8);
+        v.resize_with(5, MaybeUninit::uninit);
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.init_in_place(|i| i.to_string());
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v.capacity(), 8);
+        assert_eq!(v, vec!["0", "1", "2", "3", "4"]);
+    }
+
+    #[test]
+    fn init_vec_zst() {
+        let mut calls = 0;
+        let v = vec![MaybeUninit::<()>::uninit(); 3].init_in_place(|_| calls += 1);
+
+        assert_eq!(v.len(), 3);
+        assert_eq!(calls, 3);
+    }
+
+    #[test]
+    fn deinit_init_roundtrip() {
+        let v: Vec<u32> = (0..10).collect();
+
+        let bp = v.as_ptr() as *const ();
+        let v = v.deinit_in_place();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v.len(), 10);
+
+        let v = v.init_in_place(|i| i as u32 * 2);
+        assert_eq!(v, (0..10).map(|i| i * 2).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn deinit_does_not_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v = vec![DROPS.track(0), DROPS.track(1)].deinit_in_place();
+        assert_eq!(v.len(), 2);
+
+        drop(v);
+
+        DROPS.assert_count(0);
+    }
+
+    #[test]
+    fn init_vec_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let mut v: Vec<MaybeUninit<Tracked<usize>>> = Vec::new();
+        v.resize_with(5, MaybeUninit::uninit);
+
+        assert!(catch_unwind(|| {
+            v.init_in_place(|i| {
+                if i == 3 {
+                    panic!();
+                } else {
+                    DROPS.track(i)
+                }
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&[// panic here
+                             // drop initialized elements, but not the uninitialized tail
+                             "0",
+                             "1",
+                             "2"]);
+    }
+}
@@ -0,0 +1,313 @@
+//! Mapping to and from two `Vec`s at once.
+
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::mem;
+use core::ptr;
+
+use {check_capacity, collect_new, mapped_vec, needs_new_buffer, DropSlice, MapInPlace,
+     MapSplitInPlace, ZipMapInPlace};
+
+impl<A, B> ZipMapInPlace<A, B> for Vec<A> {
+    type Output = Vec<B>;
+
+    /// The results are written over `self`, and `other` is freed once all of its elements have
+    /// been consumed. As in `map_in_place`, the results are collected into a new allocation
+    /// instead if `A` and `B` have different alignments, or if `A` is zero-sized and `B` isn't.
+    /// Panics if `B` is larger than `A` otherwise.
+    ///
+    /// If `f` panics, the `B`s produced so far and the `A`s and `C`s not yet consumed are
+    /// dropped and both allocations are freed.
+    #[inline]
+    fn zip_map_in_place<C, F>(self, other: Vec<C>, f: F) -> Result<Self::Output, (Self, Vec<C>)>
+        where F: FnMut(A, C) -> B
+    {
+        self.zip_map_in_place_recycle(other, f).map(|(mapped, _)| mapped)
+    }
+
+    /// The returned `Vec<C>` has length zero and the capacity `other` had.
+    #[inline]
+    fn zip_map_in_place_recycle<C, F>(self,
+                                      mut other: Vec<C>,
+                                      mut f: F)
+                                      -> Result<(Self::Output, Vec<C>), (Self, Vec<C>)>
+        where F: FnMut(A, C) -> B
+    {
+        if self.len() != other.len() {
+            return Err((self, other));
+        }
+
+        if needs_new_buffer::<A, B>() {
+            let mapped = collect_new(self.into_iter().zip(other.drain(..)).map(|(a, c)| f(a, c)));
+
+            return Ok((mapped, other));
+        }
+
+        let cap = self.capacity();
+        check_capacity::<A, B>(cap, "zip_map_in_place(Vec<A>)");
+        let len = self.len();
+
+        let mut owned = mem::ManuallyDrop::new(self);
+        let mut dropper = ZipDropper {
+            idx: 0,
+            ptr: owned.as_mut_ptr(),
+            cap,
+            other,
+            _marker: PhantomData::<B>,
+        };
+
+        let ptr_a = dropper.ptr;
+        let ptr_b = ptr_a as *mut B;
+        let ptr_c = dropper.other.as_ptr();
+
+        unsafe {
+            for i in 0..len {
+                let a = ptr::read(ptr_a.add(i));
+                let c = ptr::read(ptr_c.add(i));
+                ptr::write(ptr_b.add(i), f(a, c));
+
+                dropper.idx += 1;
+            }
+
+            // all of the `C`s have been consumed, the buffer of `self` now holds the `B`s
+            let mut other = ptr::read(&dropper.other);
+            other.set_len(0);
+
+            mem::forget(dropper);
+
+            Ok((mapped_vec::<A, B>(ptr_b, len, cap), other))
+        }
+    }
+}
+
+impl<A, B> MapSplitInPlace<A, B> for Vec<A> {
+    type Output = Vec<B>;
+
+    /// The `B`s are mapped in place as with `map_in_place`, and the `Vec<C>` is allocated up
+    /// front with room for exactly as many elements as `self` has.
+    ///
+    /// If `f` panics, the `C`s produced so far are dropped along with the `B`s and `A`s.
+    #[inline]
+    fn map_split_in_place<C, F>(self, mut f: F) -> (Self::Output, Vec<C>)
+        where F: FnMut(A) -> (B, C)
+    {
+        let mut split = Vec::with_capacity(self.len());
+
+        let mapped = self.map_in_place(|a| {
+            let (b, c) = f(a);
+            split.push(c);
+            b
+        });
+
+        (mapped, split)
+    }
+}
+
+/// `idx` is the number of pairs that have been mapped; if `f` panics, the elements of both
+/// `Vec`s at `idx` have already been moved out.
+///
+/// The buffer of `self` is held as raw parts, as in `VecDropper`, and has as many elements as
+/// `other`. Both buffers are only freed if `f` panicked, and otherwise the dropper is forgotten.
+/// Panicking destructors are handled as in `VecDropper`.
+struct ZipDropper<A, B, C> {
+    idx: usize,
+    ptr: *mut A,
+    cap: usize,
+    other: Vec<C>,
+    _marker: PhantomData<B>,
+}
+
+impl<A, B, C> Drop for ZipDropper<A, B, C> {
+    #[inline]
+    fn drop(&mut self) {
+        // panicked; manual cleanup needed
+        let other = &mut self.other;
+        let idx = self.idx;
+        let len = other.len();
+        let ptr_a = self.ptr;
+        let ptr_b = ptr_a as *mut B;
+        let ptr_c = other.as_mut_ptr();
+
+        unsafe {
+            // frees the buffer once the elements are gone, `other` frees its own
+            let _owned = Vec::from_raw_parts(ptr_a, 0, self.cap);
+            other.set_len(0);
+
+            let _rest_c = DropSlice(ptr::slice_from_raw_parts_mut(ptr_c.add(idx + 1),
+                                                                  len - idx - 1));
+            let _rest_a = DropSlice(ptr::slice_from_raw_parts_mut(ptr_a.add(idx + 1),
+                                                                  len - idx - 1));
+
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(ptr_b, idx));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {MapSplitInPlace, ZipMapInPlace};
+    use testing::DropTracker;
+
+    use std::panic::catch_unwind;
+
+    #[test]
+    fn zip_map_vec() {
+        let samples: Vec<f32> = vec![0.5, 1.0, -0.25];
+        let gains: Vec<f32> = vec![2.0, 0.5, 4.0];
+
+        let bp = samples.as_ptr() as *const ();
+        let mixed = samples.zip_map_in_place(gains, |s, g| s * g).unwrap();
+        let ap = mixed.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(mixed, vec![1.0, 0.5, -1.0]);
+    }
+
+    #[test]
+    fn zip_map_vec_length_mismatch() {
+        let a: Vec<u32> = vec![1, 2, 3];
+        let b: Vec<String> = vec!["x".into()];
+
+        let (a, b) = a.zip_map_in_place(b, |a, _| a).unwrap_err();
+
+        assert_eq!(a, vec![1, 2, 3]);
+        assert_eq!(b, vec!["x"]);
+    }
+
+    #[test]
+    fn zip_map_vec_zst() {
+        let a: Vec<u32> = vec![1, 2];
+        let v = a.zip_map_in_place(vec![(); 2], |_, ()| ()).unwrap();
+
+        assert_eq!(v.len(), 2);
+    }
+
+    #[test]
+    fn zip_map_vec_alignment_mismatch() {
+        // the results go in a new allocation
+        let a: Vec<u32> = vec![1, 2];
+        let v = a.zip_map_in_place(vec![3u8, 4], |a, c| a as u8 + c).unwrap();
+
+        assert_eq!(v, vec![4, 6]);
+    }
+
+    #[test]
+    fn zip_map_vec_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let a = vec![DROPS.track(1u32), DROPS.track(2)];
+        let c = vec![DROPS.track(11u32), DROPS.track(12)];
+
+        let v = a.zip_map_in_place(c, |a, c| DROPS.track(-((a.0 + c.0) as i32))).unwrap();
+
+        drop(v);
+        DROPS.assert_drops(&["11", "1", "12", "2", "-12", "-14"]);
+    }
+
+    #[test]
+    fn zip_map_vec_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let a: Vec<_> = (1..5).map(|x| DROPS.track(x)).collect();
+        let c: Vec<_> = (11..15).map(|x| DROPS.track(x as u32)).collect();
+
+        assert!(catch_unwind(|| {
+            a.zip_map_in_place(c, |a, c| {
+                if a.0 == 3 {
+                    panic!();
+                } else {
+                    DROPS.track(-((a.0 + c.0) as i32))
+                }
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&[// consume pairs
+                             "11",
+                             "1",
+                             "12",
+                             "2",
+                             "13",
+                             "3",
+                             // panic here
+                             // drop mapped elements
+                             "-12",
+                             "-14",
+                             // drop remaining unprocessed elements of both
+                             "4",
+                             "14"]);
+    }
+
+    #[test]
+    fn zip_map_vec_recycle() {
+        let a: Vec<u32> = vec![1, 2, 3];
+        let mut c: Vec<String> = Vec::with_capacity(10);
+        c.extend(vec!["a".into(), "bb".into(), "ccc".into()]);
+
+        let bp = a.as_ptr() as *const ();
+        let cp = c.as_ptr();
+        let (v, c) = a.zip_map_in_place_recycle(c, |a, c| a + c.len() as u32).unwrap();
+        let ap = v.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(v, vec![2, 4, 6]);
+        assert_eq!(cp, c.as_ptr());
+        assert_eq!(c.capacity(), 10);
+        assert!(c.is_empty());
+    }
+
+    #[test]
+    fn zip_map_vec_recycle_zst() {
+        let a: Vec<u32> = vec![1, 2];
+        let c: Vec<u64> = vec![3, 4];
+        let cp = c.as_ptr();
+
+        let (v, c) = a.zip_map_in_place_recycle(c, |_, _| ()).unwrap();
+
+        assert_eq!(v.len(), 2);
+        assert_eq!(cp, c.as_ptr());
+        assert!(c.is_empty());
+    }
+
+    #[test]
+    fn map_split_vec() {
+        let v: Vec<(u64, Box<str>)> = vec![(1, "a".into()), (2, "b".into())];
+
+        let bp = v.as_ptr() as *const ();
+        let (keys, payloads) = v.map_split_in_place(|(k, p)| ((k, k * 2), p));
+        let ap = keys.as_ptr() as *const ();
+
+        assert_eq!(bp, ap); // still at same memory addr
+        assert_eq!(keys, vec![(1, 2), (2, 4)]);
+        assert_eq!(payloads, vec!["a".into(), "b".into()]);
+        assert_eq!(payloads.capacity(), 2);
+    }
+
+    #[test]
+    fn map_split_vec_panic_drop() {
+        static DROPS: DropTracker = DropTracker::new();
+
+        let v: Vec<_> = (1..4u32).map(|i| (DROPS.track(i), DROPS.track(i + 10))).collect();
+
+        assert!(catch_unwind(|| {
+            v.map_split_in_place(|(a, c)| {
+                if a.0 == 2 {
+                    panic!();
+                } else {
+                    (DROPS.track(-(a.0 as i32)), c)
+                }
+            })
+        }).is_err());
+
+        DROPS.assert_drops(&["1",
+                             // panic here, dropping the consumed tuple
+                             "12",
+                             "2",
+                             // drop mapped elements
+                             "-1",
+                             // drop remaining unprocessed elements
+                             "3",
+                             "13",
+                             // drop the split off elements
+                             "11"]);
+    }
+}